@@ -0,0 +1,195 @@
+use chrono::{DateTime, Local};
+
+use crate::{
+    database::{model::DbType, Conn},
+    queries::{
+        migration_file::{migration_version, SyncReport, MIGRATION_DIR},
+        model::Setup,
+        schema_diff::SCHEMA_FILE,
+        Model,
+    },
+};
+
+const USAGE: &str =
+    "usage: mini-base migrate <up|down|status|to <version>|export|generate [schema.json]> [--db <path>]";
+
+pub async fn run(args: Vec<String>) -> Result<(), String> {
+    match args.first().map(|a| a.as_str()) {
+        Some("migrate") => migrate(&args[1..]).await,
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn connect(dbpath: Option<&String>) -> Result<Model, String> {
+    let mut model = Model::default();
+
+    let setup = model.jsondb.get::<Setup>("setup").ok();
+
+    let dbpath = match (dbpath, &setup) {
+        (Some(p), _) => p.clone(),
+        (None, Some(s)) if !s.dbpath.is_empty() => s.dbpath.clone(),
+        _ => return Err("no database configured, pass --db <path>".to_string()),
+    };
+
+    let dbtype = match &setup {
+        _ if dbpath.starts_with("mysql://") => DbType::Mysql,
+        Some(s) if s.dbpath == dbpath && s.dbtype == DbType::Mysql.to_string() => DbType::Mysql,
+        _ => DbType::Sqlite,
+    };
+
+    let conn = Conn::new(dbtype, &dbpath);
+    if let Some(e) = conn.err {
+        return Err(e);
+    }
+    model.conn = Some(conn);
+
+    Ok(model)
+}
+
+async fn migrate(args: &[String]) -> Result<(), String> {
+    let dbpath = args
+        .iter()
+        .position(|a| a == "--db")
+        .map(|i| args.get(i + 1).ok_or(USAGE.to_string()))
+        .transpose()?;
+
+    let model = connect(dbpath)?;
+
+    let res = match args.first().map(|a| a.as_str()) {
+        Some("up") => migrate_to(&model, None).await,
+        Some("down") => migrate_down(&model).await,
+        Some("status") => status(&model).await,
+        Some("to") => match args.get(1).and_then(|id| id.parse::<i64>().ok()) {
+            Some(id) => migrate_to(&model, Some(id)).await,
+            None => Err(USAGE.to_string()),
+        },
+        Some("export") => export(&model).await,
+        Some("generate") => match args.get(1).filter(|a| !a.starts_with("--")) {
+            Some(path) => generate(&model, path).await,
+            None => generate(&model, SCHEMA_FILE).await,
+        },
+        _ => Err(USAGE.to_string()),
+    };
+
+    model.conn.as_ref().unwrap().close().await;
+    res
+}
+
+async fn sync(model: &Model) -> Result<(), String> {
+    let SyncReport {
+        added,
+        updated,
+        warnings,
+    } = model.sync_migration_files(MIGRATION_DIR).await?;
+
+    for (_, name) in added {
+        println!("added {}", name);
+    }
+    for name in updated {
+        println!("updated {}", name);
+    }
+    for warning in warnings {
+        println!("warning: {}", warning);
+    }
+
+    Ok(())
+}
+
+async fn migrate_to(model: &Model, target: Option<i64>) -> Result<(), String> {
+    sync(model).await?;
+
+    if let Some(target) = target {
+        for migration in model.get_down_migrations().await? {
+            if migration_version(&migration.name, migration.id) > target {
+                model.revert_migration(&migration).await?;
+                println!("down {}", migration.name);
+            }
+        }
+    }
+
+    for migration in model.get_up_migrations().await? {
+        if target.map_or(true, |t| {
+            migration_version(&migration.name, migration.id) <= t
+        }) {
+            model
+                .apply_migration(&migration)
+                .await
+                .map_err(|e| format!("{}: {}", migration.name, e))?;
+            println!("up {}", migration.name);
+        }
+    }
+
+    Ok(())
+}
+
+async fn migrate_down(model: &Model) -> Result<(), String> {
+    match model.get_down_migrations().await?.first() {
+        Some(migration) => {
+            model
+                .revert_migration(migration)
+                .await
+                .map_err(|e| format!("{}: {}", migration.name, e))?;
+            println!("down {}", migration.name);
+        }
+        None => println!("nothing to roll back"),
+    }
+
+    Ok(())
+}
+
+async fn status(model: &Model) -> Result<(), String> {
+    sync(model).await?;
+
+    let drifted = model.get_drifted_migrations().await?;
+
+    for m in model.get_all_migrations().await? {
+        let state = match (m.executed, drifted.contains(&m.id)) {
+            (true, true) => "drift",
+            (true, false) => "applied",
+            (false, _) => "pending",
+        };
+
+        let applied_at = m
+            .applied_at
+            .and_then(|t| DateTime::from_timestamp(t, 0))
+            .map(|t| {
+                t.with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            })
+            .unwrap_or_default();
+
+        println!(
+            "{:>4}  {:<8} {:<40} {}",
+            migration_version(&m.name, m.id),
+            state,
+            m.name,
+            applied_at
+        );
+    }
+
+    Ok(())
+}
+
+async fn export(model: &Model) -> Result<(), String> {
+    for name in model.export_migration_files(MIGRATION_DIR).await? {
+        println!("exported {}", name);
+    }
+
+    Ok(())
+}
+
+async fn generate(model: &Model, path: &str) -> Result<(), String> {
+    match model.generate_schema_migration(path).await? {
+        Some(id) => {
+            let m = model.get_migration_name_by_id(id).await?;
+            println!(
+                "generated {} ({}), review it before running up",
+                m.name, m.id
+            );
+        }
+        None => println!("database already matches {}", path),
+    }
+
+    Ok(())
+}
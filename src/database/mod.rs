@@ -1,8 +1,11 @@
 use std::collections::BTreeMap;
 
 use sqlx::{mysql::MySqlRow, sqlite::SqliteRow, Decode, Error, FromRow, Row, Type};
+use tokio::sync::mpsc::{self, Receiver};
 
-use self::model::{ColType, DbType};
+use crate::server::metrics::time_db;
+
+use self::model::{ColType, DbType, StreamRow};
 
 pub mod model;
 pub mod mysql;
@@ -92,14 +95,14 @@ impl Conn {
     pub async fn query_all(&self, query: &str, args: Vec<ColType>) -> Result<Vec<DbRow>, String> {
         match self.dbtype {
             DbType::Sqlite => {
-                let res = self.sqlite.as_ref().unwrap().query_all(query, args).await;
+                let res = time_db(self.sqlite.as_ref().unwrap().query_all(query, args)).await;
                 match res {
                     Ok(rows) => Ok(rows.into_iter().map(DbRow::Sqlite).collect()),
                     Err(e) => Err(e),
                 }
             }
             DbType::Mysql => {
-                let res = self.mysql.as_ref().unwrap().query_all(query, args).await;
+                let res = time_db(self.mysql.as_ref().unwrap().query_all(query, args)).await;
                 match res {
                     Ok(rows) => Ok(rows.into_iter().map(DbRow::Mysql).collect()),
                     Err(e) => Err(e),
@@ -108,17 +111,47 @@ impl Conn {
         }
     }
 
+    // the query runs in its own task, a failure shows up as the last item. the task is
+    // outside the request, so callers time the receiving side instead
+    pub fn query_stream(
+        &self,
+        query: String,
+        args: Vec<ColType>,
+    ) -> Receiver<Result<StreamRow, String>> {
+        let (tx, rx) = mpsc::channel(64);
+        let conn = self.clone();
+
+        tokio::spawn(async move {
+            let res = match conn.dbtype {
+                DbType::Sqlite => {
+                    let sqlite = conn.sqlite.as_ref().unwrap();
+                    sqlite.query_stream(&query, args, tx.clone()).await
+                }
+                DbType::Mysql => {
+                    let mysql = conn.mysql.as_ref().unwrap();
+                    mysql.query_stream(&query, args, tx.clone()).await
+                }
+            };
+
+            if let Err(e) = res {
+                let _ = tx.send(Err(e)).await;
+            }
+        });
+
+        rx
+    }
+
     pub async fn query_one(&self, query: &str, args: Vec<ColType>) -> Result<DbRow, String> {
         match self.dbtype {
             DbType::Sqlite => {
-                let res = self.sqlite.as_ref().unwrap().query_one(query, args).await;
+                let res = time_db(self.sqlite.as_ref().unwrap().query_one(query, args)).await;
                 match res {
                     Ok(row) => Ok(DbRow::Sqlite(row)),
                     Err(e) => Err(e),
                 }
             }
             DbType::Mysql => {
-                let res = self.mysql.as_ref().unwrap().query_one(query, args).await;
+                let res = time_db(self.mysql.as_ref().unwrap().query_one(query, args)).await;
                 match res {
                     Ok(row) => Ok(DbRow::Mysql(row)),
                     Err(e) => Err(e),
@@ -129,51 +162,91 @@ impl Conn {
 
     pub async fn execute(&self, query: &str, args: Vec<ColType>) -> Result<u64, String> {
         match self.dbtype {
-            DbType::Sqlite => self.sqlite.as_ref().unwrap().execute(query, args).await,
-            DbType::Mysql => self.mysql.as_ref().unwrap().execute(query, args).await,
+            DbType::Sqlite => time_db(self.sqlite.as_ref().unwrap().execute(query, args)).await,
+            DbType::Mysql => time_db(self.mysql.as_ref().unwrap().execute(query, args)).await,
+        }
+    }
+
+    // returns the id of the inserted row, mysql has no RETURNING
+    pub async fn insert(&self, query: &str, args: Vec<ColType>) -> Result<i64, String> {
+        match self.dbtype {
+            DbType::Sqlite => time_db(self.sqlite.as_ref().unwrap().insert(query, args)).await,
+            DbType::Mysql => time_db(self.mysql.as_ref().unwrap().insert(query, args)).await,
+        }
+    }
+
+    pub async fn execute_in_transaction(
+        &self,
+        queries: Vec<(String, Vec<ColType>)>,
+    ) -> Result<u64, String> {
+        match self.dbtype {
+            DbType::Sqlite => {
+                time_db(
+                    self.sqlite
+                        .as_ref()
+                        .unwrap()
+                        .execute_in_transaction(queries),
+                )
+                .await
+            }
+            DbType::Mysql => {
+                time_db(self.mysql.as_ref().unwrap().execute_in_transaction(queries)).await
+            }
         }
     }
 
-    pub async fn query_all_with_type<T>(&self, query: &str) -> Result<Vec<T>, String>
+    pub async fn query_all_with_type<T>(
+        &self,
+        query: &str,
+        args: Vec<ColType>,
+    ) -> Result<Vec<T>, String>
     where
         T: for<'r> FromRow<'r, SqliteRow> + for<'r> FromRow<'r, MySqlRow> + Unpin + Send,
     {
         match self.dbtype {
             DbType::Sqlite => {
-                self.sqlite
-                    .as_ref()
-                    .unwrap()
-                    .query_all_with_type::<T>(query)
-                    .await
+                time_db(
+                    self.sqlite
+                        .as_ref()
+                        .unwrap()
+                        .query_all_with_type::<T>(query, args),
+                )
+                .await
             }
             DbType::Mysql => {
-                self.mysql
-                    .as_ref()
-                    .unwrap()
-                    .query_all_with_type::<T>(query)
-                    .await
+                time_db(
+                    self.mysql
+                        .as_ref()
+                        .unwrap()
+                        .query_all_with_type::<T>(query, args),
+                )
+                .await
             }
         }
     }
 
-    pub async fn query_one_with_type<T>(&self, query: &str) -> Result<T, String>
+    pub async fn query_one_with_type<T>(&self, query: &str, args: Vec<ColType>) -> Result<T, String>
     where
         T: for<'r> FromRow<'r, SqliteRow> + for<'r> FromRow<'r, MySqlRow> + Unpin + Send,
     {
         match self.dbtype {
             DbType::Sqlite => {
-                self.sqlite
-                    .as_ref()
-                    .unwrap()
-                    .query_one_with_type::<T>(query)
-                    .await
+                time_db(
+                    self.sqlite
+                        .as_ref()
+                        .unwrap()
+                        .query_one_with_type::<T>(query, args),
+                )
+                .await
             }
             DbType::Mysql => {
-                self.mysql
-                    .as_ref()
-                    .unwrap()
-                    .query_one_with_type::<T>(query)
-                    .await
+                time_db(
+                    self.mysql
+                        .as_ref()
+                        .unwrap()
+                        .query_one_with_type::<T>(query, args),
+                )
+                .await
             }
         }
     }
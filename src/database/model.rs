@@ -1,12 +1,16 @@
 use core::fmt;
 use std::collections::HashMap;
 
-use chrono::{DateTime, Local, NaiveTime};
+use base64::{engine::general_purpose, Engine};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime};
 use enum_iterator::Sequence;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 use serde_json::Value;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// a streamed row keeps its columns in select order, duplicate names included
+pub type StreamRow = Vec<(String, ColType)>;
+
+#[derive(Debug, Clone, Deserialize)]
 #[serde(untagged)]
 pub enum ColType {
     Integer(Option<i64>),
@@ -14,20 +18,69 @@ pub enum ColType {
     UnsignedInteger(Option<u64>),
     String(Option<String>),
     Bool(Option<bool>),
-    Date(Option<DateTime<Local>>),
+    Date(Option<NaiveDate>),
     Time(Option<NaiveTime>),
     Datetime(Option<DateTime<Local>>),
     Json(Option<String>),
     Array(Option<Vec<ColType>>),
     Object(Option<HashMap<String, Box<ColType>>>),
+    Decimal(Option<String>),
+    Binary(Option<Vec<u8>>),
+    Null,
+}
+
+impl Serialize for ColType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            ColType::Integer(t) => t.serialize(serializer),
+            ColType::Real(t) => t.serialize(serializer),
+            ColType::UnsignedInteger(t) => t.serialize(serializer),
+            ColType::String(t) | ColType::Decimal(t) => t.serialize(serializer),
+            ColType::Bool(t) => t.serialize(serializer),
+            ColType::Date(t) => t.serialize(serializer),
+            ColType::Time(t) => t.serialize(serializer),
+            ColType::Datetime(t) => t.serialize(serializer),
+            // stored json is returned nested, text that isn't valid json stays a string
+            ColType::Json(t) => match t.as_deref().map(serde_json::from_str::<Value>) {
+                Some(Ok(v)) => v.serialize(serializer),
+                _ => t.serialize(serializer),
+            },
+            ColType::Array(t) => t.serialize(serializer),
+            ColType::Object(t) => t.serialize(serializer),
+            ColType::Binary(t) => t
+                .as_ref()
+                .map(|t| general_purpose::STANDARD.encode(t))
+                .serialize(serializer),
+            ColType::Null => serializer.serialize_none(),
+        }
+    }
 }
 
 impl ColType {
+    // arrays and objects are bound as json text, the other variants bind as they are
+    pub fn into_bind(self) -> ColType {
+        match self {
+            ColType::Array(Some(_)) | ColType::Object(Some(_)) => {
+                ColType::Json(serde_json::to_string(&self).ok())
+            }
+            ColType::Array(None) | ColType::Object(None) | ColType::Null => ColType::String(None),
+            ColType::Decimal(t) => ColType::String(t),
+            t => t,
+        }
+    }
+
     pub fn get_col_type_from_value(val: Value) -> ColType {
         match val {
-            Value::Null => ColType::Bool(None),
+            Value::Null => ColType::Null,
             Value::Bool(t) => ColType::Bool(Some(t)),
-            Value::Number(t) => ColType::Real(t.as_f64()),
+            Value::Number(t) => match (t.as_i64(), t.as_u64()) {
+                (Some(i), _) => ColType::Integer(Some(i)),
+                (None, Some(u)) => ColType::UnsignedInteger(Some(u)),
+                _ => ColType::Real(t.as_f64()),
+            },
             Value::String(t) => ColType::String(Some(t)),
             Value::Array(t) => ColType::Array(Some(
                 t.into_iter()
@@ -45,7 +98,7 @@ impl ColType {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 pub struct ColInfo {
     pub cid: i64,
     pub name: String,
@@ -70,3 +123,57 @@ impl fmt::Display for DbType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::ColType;
+
+    #[test]
+    fn test1() {
+        let value = json!({
+            "id": 9007199254740993i64,
+            "big": 18446744073709551615u64,
+            "price": 1.5,
+            "note": null,
+            "tags": ["a", "b"]
+        });
+
+        let ColType::Object(Some(map)) = ColType::get_col_type_from_value(value) else {
+            panic!("expected an object");
+        };
+
+        assert!(matches!(
+            *map["id"],
+            ColType::Integer(Some(9007199254740993))
+        ));
+        assert!(matches!(
+            *map["big"],
+            ColType::UnsignedInteger(Some(u64::MAX))
+        ));
+        assert!(matches!(*map["price"], ColType::Real(Some(_))));
+        assert!(matches!(*map["note"], ColType::Null));
+
+        match map["tags"].clone().into_bind() {
+            ColType::Json(Some(t)) => assert_eq!(t, "[\"a\",\"b\"]"),
+            t => panic!("unexpected bind {:?}", t),
+        }
+    }
+
+    #[test]
+    fn test2() {
+        let values = vec![
+            ColType::Json(Some("{\"a\":[1,2]}".to_string())),
+            ColType::Json(Some("not json".to_string())),
+            ColType::Binary(Some(vec![0, 1, 2, 255])),
+            ColType::Decimal(Some("12345678901234567890.12".to_string())),
+            ColType::Null,
+        ];
+
+        assert_eq!(
+            serde_json::to_value(values).unwrap(),
+            json!([{"a": [1, 2]}, "not json", "AAEC/w==", "12345678901234567890.12", null])
+        );
+    }
+}
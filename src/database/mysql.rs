@@ -1,12 +1,14 @@
 use std::collections::BTreeMap;
 
-use chrono::{DateTime, Local, NaiveTime};
+use futures::TryStreamExt;
 use sqlx::{
-    mysql::{MySqlPool, MySqlPoolOptions, MySqlRow},
-    query_as, Column, Error, FromRow, Row, TypeInfo,
+    mysql::{MySql, MySqlPool, MySqlPoolOptions, MySqlRow},
+    query_as, Column, Decode, Error, FromRow, Row, TypeInfo, ValueRef,
 };
 
-use super::model::ColType;
+use tokio::sync::mpsc::Sender;
+
+use super::model::{ColType, StreamRow};
 
 #[derive(Debug, Clone)]
 pub struct Mysql {
@@ -19,90 +21,223 @@ impl Mysql {
 
         match opt_connection {
             Ok(connection) => {
-                let query = "
-                    CREATE TABLE IF NOT EXISTS
+                // mysql only runs one statement per query and does not allow literal defaults on TEXT
+                let queries = [
+                    "CREATE TABLE IF NOT EXISTS
                         roles (
-                            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+                            id INTEGER NOT NULL PRIMARY KEY AUTO_INCREMENT,
                             name VARCHAR(255) UNIQUE NOT NULL,
                             is_default TINYINT(1) NOT NULL DEFAULT 0,
                             can_read TINYINT(1) NOT NULL DEFAULT 0,
                             can_write TINYINT(1) NOT NULL DEFAULT 0,
                             can_delete TINYINT(1) NOT NULL DEFAULT 0
-                        );
-                    
-                    CREATE TABLE IF NOT EXISTS
+                        )",
+                    "CREATE TABLE IF NOT EXISTS
                         users (
-                            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+                            id INTEGER NOT NULL PRIMARY KEY AUTO_INCREMENT,
                             email VARCHAR(100) UNIQUE NOT NULL,
                             password VARCHAR(255) NOT NULL,
                             role_id INTEGER,
                             FOREIGN KEY (role_id) REFERENCES roles (id)
-                        );
-                    
-                    CREATE TABLE IF NOT EXISTS
+                        )",
+                    "CREATE TABLE IF NOT EXISTS
                         storage (
-                            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+                            id INTEGER NOT NULL PRIMARY KEY AUTO_INCREMENT,
                             file_name VARCHAR(255) NOT NULL,
-                            unique_name VARCHAR(36) UNIQUE NOT NULL,
+                            unique_name VARCHAR(64) UNIQUE NOT NULL,
                             uploaded_by INTEGER NOT NULL,
                             FOREIGN KEY (uploaded_by) REFERENCES users (id)
-                        );
-                    
-                    CREATE TABLE IF NOT EXISTS
+                        )",
+                    "CREATE TABLE IF NOT EXISTS
+                        tus_uploads (
+                            id VARCHAR(36) NOT NULL PRIMARY KEY,
+                            file_name VARCHAR(255) NOT NULL,
+                            upload_length BIGINT NOT NULL,
+                            upload_offset BIGINT NOT NULL DEFAULT 0,
+                            uploaded_by INTEGER NOT NULL,
+                            FOREIGN KEY (uploaded_by) REFERENCES users (id)
+                        )",
+                    "CREATE TABLE IF NOT EXISTS
                         queries (
-                            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+                            id INTEGER NOT NULL PRIMARY KEY AUTO_INCREMENT,
                             name VARCHAR(255) UNIQUE NOT NULL,
-                            exec_type VARCHAR(50) NOT NULL DEFAULT 'get' CHECK (exec_type IN ('get', 'post', 'delete', 'put')),
-                            query TEXT DEFAULT ''
-                        );
-                    
-                    CREATE TABLE IF NOT EXISTS
+                            exec_type VARCHAR(50) NOT NULL DEFAULT 'get',
+                            query TEXT NOT NULL DEFAULT (''),
+                            pagination VARCHAR(50) NOT NULL DEFAULT 'none',
+                            page_size INTEGER NOT NULL DEFAULT 100,
+                            cursor_column VARCHAR(255) NOT NULL DEFAULT '',
+                            with_total BOOLEAN NOT NULL DEFAULT FALSE,
+                            shape VARCHAR(50) NOT NULL DEFAULT 'flat',
+                            group_by VARCHAR(255) NOT NULL DEFAULT '',
+                            max_rows INTEGER NOT NULL DEFAULT 0,
+                            cache_ttl INTEGER NOT NULL DEFAULT 0,
+                            cache_params VARCHAR(255) NOT NULL DEFAULT '',
+                            cache_per_user BOOLEAN NOT NULL DEFAULT FALSE,
+                            cache_max_entries INTEGER NOT NULL DEFAULT 100,
+                            CHECK (exec_type IN ('get', 'post', 'delete', 'put')),
+                            CHECK (pagination IN ('none', 'offset', 'cursor')),
+                            CHECK (shape IN ('flat', 'nested', 'single'))
+                        )",
+                    "CREATE TABLE IF NOT EXISTS
                         role_access (
                             role_id INTEGER NOT NULL,
                             query_id INTEGER NOT NULL,
                             FOREIGN KEY (role_id) REFERENCES roles (id) ON DELETE CASCADE,
                             FOREIGN KEY (query_id) REFERENCES queries (id) ON DELETE CASCADE,
                             UNIQUE (role_id, query_id)
-                        );
-                    
-                    CREATE TABLE IF NOT EXISTS
+                        )",
+                    "CREATE TABLE IF NOT EXISTS
                         migrations (
-                            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+                            id INTEGER NOT NULL PRIMARY KEY AUTO_INCREMENT,
                             name VARCHAR(255) UNIQUE NOT NULL,
-                            up_query TEXT DEFAULT '',
-                            down_query TEXT DEFAULT '',
-                            executed TINYINT(1) DEFAULT 0
-                        );
-                    
-                    CREATE TABLE IF NOT EXISTS
+                            up_query TEXT NOT NULL DEFAULT (''),
+                            down_query TEXT NOT NULL DEFAULT (''),
+                            executed TINYINT(1) DEFAULT 0,
+                            checksum VARCHAR(64) NOT NULL DEFAULT '',
+                            applied_at BIGINT
+                        )",
+                    "CREATE TABLE IF NOT EXISTS
                         webhooks (
-                            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+                            id INTEGER NOT NULL PRIMARY KEY AUTO_INCREMENT,
                             name VARCHAR(255) UNIQUE NOT NULL,
-                            exec_type VARCHAR(50) NOT NULL DEFAULT 'get' CHECK (exec_type IN ('get', 'post', 'delete', 'put')),
-                            action VARCHAR(50) NOT NULL DEFAULT 'before' CHECK (action IN ('before', 'after')),
-                            url TEXT DEFAULT '',
-                            args JSON DEFAULT '{}',
-                            is_returned TINYINT(1) DEFAULT 0
-                        );
-                    
-                    CREATE TABLE IF NOT EXISTS
+                            exec_type VARCHAR(50) NOT NULL DEFAULT 'get',
+                            action VARCHAR(50) NOT NULL DEFAULT 'before',
+                            url TEXT NOT NULL DEFAULT (''),
+                            args TEXT NOT NULL DEFAULT ('{}'),
+                            is_returned TINYINT(1) DEFAULT 0,
+                            return_mode VARCHAR(50) NOT NULL DEFAULT 'keep',
+                            secret VARCHAR(255) NOT NULL DEFAULT '',
+                            CHECK (exec_type IN ('get', 'post', 'delete', 'put')),
+                            CHECK (action IN ('before', 'after')),
+                            CHECK (return_mode IN ('keep', 'merge', 'replace'))
+                        )",
+                    "CREATE TABLE IF NOT EXISTS
+                        webhook_outbox (
+                            id INTEGER NOT NULL PRIMARY KEY AUTO_INCREMENT,
+                            webhook_id INTEGER NOT NULL,
+                            method VARCHAR(50) NOT NULL,
+                            url TEXT NOT NULL,
+                            payload TEXT NOT NULL,
+                            attempts INTEGER NOT NULL DEFAULT 0,
+                            next_attempt_at BIGINT NOT NULL DEFAULT 0,
+                            status VARCHAR(50) NOT NULL DEFAULT 'pending',
+                            FOREIGN KEY (webhook_id) REFERENCES webhooks (id) ON DELETE CASCADE,
+                            CHECK (status IN ('pending', 'delivered', 'failed'))
+                        )",
+                    "CREATE TABLE IF NOT EXISTS
+                        webhook_deliveries (
+                            id INTEGER NOT NULL PRIMARY KEY AUTO_INCREMENT,
+                            webhook_id INTEGER NOT NULL,
+                            method VARCHAR(50) NOT NULL,
+                            url TEXT NOT NULL,
+                            payload TEXT NOT NULL,
+                            status_code INTEGER,
+                            latency_ms BIGINT NOT NULL DEFAULT 0,
+                            response TEXT NOT NULL,
+                            error TEXT NOT NULL,
+                            created_at BIGINT NOT NULL,
+                            FOREIGN KEY (webhook_id) REFERENCES webhooks (id) ON DELETE CASCADE
+                        )",
+                    "CREATE TABLE IF NOT EXISTS
+                        table_triggers (
+                            id INTEGER NOT NULL PRIMARY KEY AUTO_INCREMENT,
+                            webhook_id INTEGER NOT NULL,
+                            table_name VARCHAR(255) NOT NULL,
+                            event VARCHAR(50) NOT NULL,
+                            FOREIGN KEY (webhook_id) REFERENCES webhooks (id) ON DELETE CASCADE,
+                            UNIQUE (webhook_id, table_name, event),
+                            CHECK (event IN ('insert', 'update', 'delete'))
+                        )",
+                    "CREATE TABLE IF NOT EXISTS
+                        change_log (
+                            id INTEGER NOT NULL PRIMARY KEY AUTO_INCREMENT,
+                            table_name VARCHAR(255) NOT NULL,
+                            event VARCHAR(50) NOT NULL,
+                            old_row TEXT,
+                            new_row TEXT,
+                            processed TINYINT(1) NOT NULL DEFAULT 0
+                        )",
+                    "CREATE TABLE IF NOT EXISTS
+                        schedules (
+                            id INTEGER NOT NULL PRIMARY KEY AUTO_INCREMENT,
+                            name VARCHAR(255) NOT NULL UNIQUE,
+                            cron VARCHAR(255) NOT NULL DEFAULT '0 0 * * * *',
+                            target_type VARCHAR(50) NOT NULL DEFAULT 'query',
+                            target_id INTEGER NOT NULL DEFAULT 0,
+                            args TEXT NOT NULL DEFAULT ('{}'),
+                            enabled TINYINT(1) NOT NULL DEFAULT 0,
+                            next_run_at BIGINT NOT NULL DEFAULT 0,
+                            CHECK (target_type IN ('query', 'webhook'))
+                        )",
+                    "CREATE TABLE IF NOT EXISTS
+                        schedule_runs (
+                            id INTEGER NOT NULL PRIMARY KEY AUTO_INCREMENT,
+                            schedule_id INTEGER NOT NULL,
+                            started_at BIGINT NOT NULL,
+                            duration_ms BIGINT NOT NULL DEFAULT 0,
+                            status VARCHAR(50) NOT NULL,
+                            output TEXT NOT NULL,
+                            FOREIGN KEY (schedule_id) REFERENCES schedules (id) ON DELETE CASCADE,
+                            CHECK (status IN ('success', 'failed'))
+                        )",
+                    "CREATE TABLE IF NOT EXISTS
                         webhook_query (
                             webhook_id INTEGER NOT NULL,
                             query_id INTEGER NOT NULL,
                             FOREIGN KEY (webhook_id) REFERENCES webhooks (id) ON DELETE CASCADE,
                             FOREIGN KEY (query_id) REFERENCES queries (id) ON DELETE CASCADE,
                             UNIQUE (webhook_id, query_id)
-                        );
-                    ";
-
-                let q = sqlx::query(query);
-                match q.execute(&connection).await {
-                    Ok(_) => Self {
-                        connection: Ok(connection),
-                    },
-                    Err(e) => Self {
-                        connection: Err(e.to_string()),
-                    },
+                        )",
+                    "CREATE TABLE IF NOT EXISTS
+                        rest_tables (
+                            id INTEGER NOT NULL PRIMARY KEY AUTO_INCREMENT,
+                            table_name VARCHAR(255) NOT NULL UNIQUE
+                        )",
+                    "CREATE TABLE IF NOT EXISTS
+                        rest_access (
+                            role_id INTEGER NOT NULL,
+                            rest_table_id INTEGER NOT NULL,
+                            operation VARCHAR(50) NOT NULL,
+                            FOREIGN KEY (role_id) REFERENCES roles (id) ON DELETE CASCADE,
+                            FOREIGN KEY (rest_table_id) REFERENCES rest_tables (id) ON DELETE CASCADE,
+                            UNIQUE (role_id, rest_table_id, operation),
+                            CHECK (operation IN ('list', 'get', 'insert', 'update', 'delete'))
+                        )",
+                ];
+
+                // columns added after the initial schema, errors for columns that already exist are ignored
+                let alter_queries = [
+                    "ALTER TABLE webhooks ADD COLUMN return_mode VARCHAR(50) NOT NULL DEFAULT 'keep'",
+                    "ALTER TABLE webhooks ADD COLUMN secret VARCHAR(255) NOT NULL DEFAULT ''",
+                    "ALTER TABLE migrations ADD COLUMN checksum VARCHAR(64) NOT NULL DEFAULT ''",
+                    "ALTER TABLE migrations ADD COLUMN applied_at BIGINT",
+                    "ALTER TABLE queries ADD COLUMN pagination VARCHAR(50) NOT NULL DEFAULT 'none'",
+                    "ALTER TABLE queries ADD COLUMN page_size INTEGER NOT NULL DEFAULT 100",
+                    "ALTER TABLE queries ADD COLUMN cursor_column VARCHAR(255) NOT NULL DEFAULT ''",
+                    "ALTER TABLE queries ADD COLUMN with_total BOOLEAN NOT NULL DEFAULT FALSE",
+                    "ALTER TABLE queries ADD COLUMN shape VARCHAR(50) NOT NULL DEFAULT 'flat'",
+                    "ALTER TABLE queries ADD COLUMN group_by VARCHAR(255) NOT NULL DEFAULT ''",
+                    "ALTER TABLE queries ADD COLUMN max_rows INTEGER NOT NULL DEFAULT 0",
+                    "ALTER TABLE queries ADD COLUMN cache_ttl INTEGER NOT NULL DEFAULT 0",
+                    "ALTER TABLE queries ADD COLUMN cache_params VARCHAR(255) NOT NULL DEFAULT ''",
+                    "ALTER TABLE queries ADD COLUMN cache_per_user BOOLEAN NOT NULL DEFAULT FALSE",
+                    "ALTER TABLE queries ADD COLUMN cache_max_entries INTEGER NOT NULL DEFAULT 100",
+                ];
+
+                for query in queries {
+                    if let Err(e) = sqlx::query(query).execute(&connection).await {
+                        return Self {
+                            connection: Err(e.to_string()),
+                        };
+                    }
+                }
+
+                for alter_query in alter_queries {
+                    let _ = sqlx::query(alter_query).execute(&connection).await;
+                }
+
+                Self {
+                    connection: Ok(connection),
                 }
             }
             Err(e) => Self {
@@ -128,7 +263,7 @@ impl Mysql {
         let mut q = sqlx::query(query);
 
         for arg in args {
-            q = match arg {
+            q = match arg.into_bind() {
                 ColType::Integer(t) => q.bind(t),
                 ColType::Real(t) => q.bind(t),
                 ColType::UnsignedInteger(t) => q.bind(t),
@@ -138,13 +273,14 @@ impl Mysql {
                 ColType::Time(t) => q.bind(t),
                 ColType::Datetime(t) => q.bind(t),
                 ColType::Json(t) => q.bind(t),
+                ColType::Binary(t) => q.bind(t),
                 _ => return Err("wrong type".to_string()),
             };
         }
 
         let conn = match &self.connection {
             Ok(conn) => conn,
-            Err(e) => panic!("{}", e),
+            Err(e) => return Err(e.clone()),
         };
 
         match q.fetch_all(conn).await {
@@ -153,11 +289,51 @@ impl Mysql {
         }
     }
 
+    // rows are parsed and sent as they are fetched, fetching stops once the receiver is gone
+    pub async fn query_stream(
+        &self,
+        query: &str,
+        args: Vec<ColType>,
+        tx: Sender<Result<StreamRow, String>>,
+    ) -> Result<(), String> {
+        let mut q = sqlx::query(query);
+
+        for arg in args {
+            q = match arg.into_bind() {
+                ColType::Integer(t) => q.bind(t),
+                ColType::Real(t) => q.bind(t),
+                ColType::UnsignedInteger(t) => q.bind(t),
+                ColType::String(t) => q.bind(t),
+                ColType::Bool(t) => q.bind(t),
+                ColType::Date(t) => q.bind(t),
+                ColType::Time(t) => q.bind(t),
+                ColType::Datetime(t) => q.bind(t),
+                ColType::Json(t) => q.bind(t),
+                ColType::Binary(t) => q.bind(t),
+                _ => return Err("wrong type".to_string()),
+            };
+        }
+
+        let conn = match &self.connection {
+            Ok(conn) => conn,
+            Err(e) => return Err(e.clone()),
+        };
+
+        let mut rows = q.fetch(conn);
+        while let Some(row) = rows.try_next().await.map_err(|e| e.to_string())? {
+            if tx.send(parse_columns(&row)).await.is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
     pub async fn query_one(&self, query: &str, args: Vec<ColType>) -> Result<MySqlRow, String> {
         let mut q = sqlx::query(query);
 
         for arg in args {
-            q = match arg {
+            q = match arg.into_bind() {
                 ColType::Integer(t) => q.bind(t),
                 ColType::Real(t) => q.bind(t),
                 ColType::UnsignedInteger(t) => q.bind(t),
@@ -167,13 +343,14 @@ impl Mysql {
                 ColType::Time(t) => q.bind(t),
                 ColType::Datetime(t) => q.bind(t),
                 ColType::Json(t) => q.bind(t),
+                ColType::Binary(t) => q.bind(t),
                 _ => return Err("wrong type".to_string()),
             };
         }
 
         let conn = match &self.connection {
             Ok(conn) => conn,
-            Err(e) => panic!("{}", e),
+            Err(e) => return Err(e.clone()),
         };
 
         match q.fetch_one(conn).await {
@@ -186,22 +363,24 @@ impl Mysql {
         let mut q = sqlx::query(query);
 
         for arg in args {
-            q = match arg {
+            q = match arg.into_bind() {
                 ColType::Integer(t) => q.bind(t),
                 ColType::Real(t) => q.bind(t),
                 ColType::UnsignedInteger(t) => q.bind(t),
                 ColType::String(t) => q.bind(t),
                 ColType::Bool(t) => q.bind(t),
+                ColType::Date(t) => q.bind(t),
                 ColType::Time(t) => q.bind(t),
                 ColType::Datetime(t) => q.bind(t),
                 ColType::Json(t) => q.bind(t),
+                ColType::Binary(t) => q.bind(t),
                 _ => return Err("wrong type".to_string()),
             };
         }
 
         let conn = match &self.connection {
             Ok(conn) => conn,
-            Err(e) => panic!("{}", e),
+            Err(e) => return Err(e.clone()),
         };
 
         match q.execute(conn).await {
@@ -210,16 +389,115 @@ impl Mysql {
         }
     }
 
-    pub async fn query_all_with_type<T>(&self, query: &str) -> Result<Vec<T>, String>
+    pub async fn insert(&self, query: &str, args: Vec<ColType>) -> Result<i64, String> {
+        let mut q = sqlx::query(query);
+
+        for arg in args {
+            q = match arg.into_bind() {
+                ColType::Integer(t) => q.bind(t),
+                ColType::Real(t) => q.bind(t),
+                ColType::UnsignedInteger(t) => q.bind(t),
+                ColType::String(t) => q.bind(t),
+                ColType::Bool(t) => q.bind(t),
+                ColType::Date(t) => q.bind(t),
+                ColType::Time(t) => q.bind(t),
+                ColType::Datetime(t) => q.bind(t),
+                ColType::Json(t) => q.bind(t),
+                ColType::Binary(t) => q.bind(t),
+                _ => return Err("wrong type".to_string()),
+            };
+        }
+
+        let conn = match &self.connection {
+            Ok(conn) => conn,
+            Err(e) => return Err(e.clone()),
+        };
+
+        match q.execute(conn).await {
+            Ok(out) => Ok(out.last_insert_id() as i64),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub async fn execute_in_transaction(
+        &self,
+        queries: Vec<(String, Vec<ColType>)>,
+    ) -> Result<u64, String> {
+        let conn = match &self.connection {
+            Ok(conn) => conn,
+            Err(e) => return Err(e.clone()),
+        };
+
+        let mut tx = match conn.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let mut rows_affected = 0;
+        for (query, args) in queries {
+            let mut q = sqlx::query(&query);
+
+            for arg in args {
+                q = match arg.into_bind() {
+                    ColType::Integer(t) => q.bind(t),
+                    ColType::Real(t) => q.bind(t),
+                    ColType::UnsignedInteger(t) => q.bind(t),
+                    ColType::String(t) => q.bind(t),
+                    ColType::Bool(t) => q.bind(t),
+                    ColType::Date(t) => q.bind(t),
+                    ColType::Time(t) => q.bind(t),
+                    ColType::Datetime(t) => q.bind(t),
+                    ColType::Json(t) => q.bind(t),
+                    ColType::Binary(t) => q.bind(t),
+                    _ => return Err("wrong type".to_string()),
+                };
+            }
+
+            // dropping the transaction on error rolls it back
+            match q.execute(&mut *tx).await {
+                Ok(out) => rows_affected += out.rows_affected(),
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+
+        match tx.commit().await {
+            Ok(_) => Ok(rows_affected),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub async fn query_all_with_type<T>(
+        &self,
+        query: &str,
+        args: Vec<ColType>,
+    ) -> Result<Vec<T>, String>
     where
         T: for<'r> FromRow<'r, MySqlRow> + Unpin + Send,
     {
+        let mut q = query_as(query);
+
+        for arg in args {
+            q = match arg.into_bind() {
+                ColType::Integer(t) => q.bind(t),
+                ColType::Real(t) => q.bind(t),
+                ColType::UnsignedInteger(t) => q.bind(t),
+                ColType::String(t) => q.bind(t),
+                ColType::Bool(t) => q.bind(t),
+                ColType::Date(t) => q.bind(t),
+                ColType::Time(t) => q.bind(t),
+                ColType::Datetime(t) => q.bind(t),
+                ColType::Json(t) => q.bind(t),
+                ColType::Binary(t) => q.bind(t),
+                _ => return Err("wrong type".to_string()),
+            };
+        }
+
         let conn = match &self.connection {
             Ok(conn) => conn,
-            Err(e) => panic!("{}", e),
+            Err(e) => return Err(e.clone()),
         };
 
-        let r_out: Result<Vec<T>, Error> = query_as(query).fetch_all(conn).await;
+        let r_out: Result<Vec<T>, Error> = q.fetch_all(conn).await;
 
         match r_out {
             Ok(out) => Ok(out),
@@ -227,16 +505,34 @@ impl Mysql {
         }
     }
 
-    pub async fn query_one_with_type<T>(&self, query: &str) -> Result<T, String>
+    pub async fn query_one_with_type<T>(&self, query: &str, args: Vec<ColType>) -> Result<T, String>
     where
         T: for<'r> FromRow<'r, MySqlRow> + Unpin + Send,
     {
+        let mut q = query_as(query);
+
+        for arg in args {
+            q = match arg.into_bind() {
+                ColType::Integer(t) => q.bind(t),
+                ColType::Real(t) => q.bind(t),
+                ColType::UnsignedInteger(t) => q.bind(t),
+                ColType::String(t) => q.bind(t),
+                ColType::Bool(t) => q.bind(t),
+                ColType::Date(t) => q.bind(t),
+                ColType::Time(t) => q.bind(t),
+                ColType::Datetime(t) => q.bind(t),
+                ColType::Json(t) => q.bind(t),
+                ColType::Binary(t) => q.bind(t),
+                _ => return Err("wrong type".to_string()),
+            };
+        }
+
         let conn = match &self.connection {
             Ok(conn) => conn,
-            Err(e) => panic!("{}", e),
+            Err(e) => return Err(e.clone()),
         };
 
-        let r_out: Result<T, Error> = query_as(query).fetch_one(conn).await;
+        let r_out: Result<T, Error> = q.fetch_one(conn).await;
 
         match r_out {
             Ok(out) => Ok(out),
@@ -248,53 +544,78 @@ impl Mysql {
         let mut table_data = vec![];
 
         for row in rows {
-            let mut map: BTreeMap<String, ColType> = BTreeMap::new();
+            table_data.push(parse_row(&row)?);
+        }
 
-            for i in 0..row.len() {
-                let row_value = match row.column(i).type_info().name() {
-                    "TEXT" | "VARCHAR" | "ENUM" | "TINYTEXT" | "CHAR" => {
-                        let t = row.get::<Option<String>, _>(i);
-                        ColType::String(t)
-                    }
-                    "INTEGER" | "INT" | "BIGINT" | "TINYINT" | "SMALLINT" | "MEDIUMINT"
-                    | "DECIMAL" => {
-                        let t = row.get::<Option<i64>, _>(i);
-                        ColType::Integer(t)
-                    }
-                    "BIGINT UNSIGNED" | "TINYINT UNSIGNED" | "SMALLINT UNSIGNED"
-                    | "INT UNSIGNED" | "MEDIUMINT UNSIGNED" | "TIMESTAMP" => {
-                        let t = row.get::<Option<u64>, _>(i);
-                        ColType::UnsignedInteger(t)
-                    }
-                    "FLOAT" | "DOUBLE" => {
-                        let t = row.get::<Option<f64>, _>(i);
-                        ColType::Real(t)
-                    }
-                    "BOOLEAN" => {
-                        let t = row.get::<Option<bool>, _>(i);
-                        ColType::Bool(t)
-                    }
-                    "DATETIME" => {
-                        let t = row.get::<Option<DateTime<Local>>, _>(i);
-                        ColType::Datetime(t)
-                    }
-                    "TIME" => {
-                        let t = row.get::<Option<NaiveTime>, _>(i);
-                        ColType::Time(t)
-                    }
-                    "JSON" => {
-                        let t = row.get::<Option<String>, _>(i);
-                        ColType::Json(t)
-                    }
-                    _ => return Err("wrong type".to_string()),
-                };
+        Ok(table_data)
+    }
+}
 
-                map.insert(row.column(i).name().to_string(), row_value);
-            }
+fn parse_row(row: &MySqlRow) -> Result<BTreeMap<String, ColType>, String> {
+    Ok(parse_columns(row)?.into_iter().collect())
+}
 
-            table_data.push(map);
-        }
+fn parse_columns(row: &MySqlRow) -> Result<StreamRow, String> {
+    let mut columns = vec![];
 
-        Ok(table_data)
+    for i in 0..row.len() {
+        let row_value = parse_value(row, i)?;
+
+        columns.push((row.column(i).name().to_string(), row_value));
+    }
+
+    Ok(columns)
+}
+
+fn parse_value(row: &MySqlRow, i: usize) -> Result<ColType, String> {
+    let raw = row.try_get_raw(i).map_err(|e| e.to_string())?;
+    if raw.is_null() {
+        return Ok(ColType::Null);
     }
+
+    let value = match row.column(i).type_info().name() {
+        "TEXT" | "VARCHAR" | "ENUM" | "SET" | "CHAR" | "TINYTEXT" | "MEDIUMTEXT" | "LONGTEXT" => {
+            ColType::String(Some(get(row, i)?))
+        }
+        "INTEGER" | "INT" | "BIGINT" | "TINYINT" | "SMALLINT" | "MEDIUMINT" => {
+            ColType::Integer(Some(get(row, i)?))
+        }
+        "BIGINT UNSIGNED" | "TINYINT UNSIGNED" | "SMALLINT UNSIGNED" | "INT UNSIGNED"
+        | "MEDIUMINT UNSIGNED" | "YEAR" => ColType::UnsignedInteger(Some(get(row, i)?)),
+        // widening through the shortest representation keeps 0.1 from turning into 0.100000001
+        "FLOAT" => {
+            let t = get::<f32>(row, i)?;
+            ColType::Real(Some(t.to_string().parse().unwrap_or(t as f64)))
+        }
+        "DOUBLE" => ColType::Real(Some(get(row, i)?)),
+        // decimals travel as text in both protocols, keeping them as text keeps their precision
+        "DECIMAL" => ColType::Decimal(Some(text(get(row, i)?))),
+        "BOOLEAN" => ColType::Bool(Some(get(row, i)?)),
+        "DATETIME" | "TIMESTAMP" => ColType::Datetime(Some(get(row, i)?)),
+        "DATE" => ColType::Date(Some(get(row, i)?)),
+        "TIME" => ColType::Time(Some(get(row, i)?)),
+        "JSON" => ColType::Json(Some(text(get(row, i)?))),
+        "BLOB" | "TINYBLOB" | "MEDIUMBLOB" | "LONGBLOB" | "BINARY" | "VARBINARY" | "BIT"
+        | "GEOMETRY" => ColType::Binary(Some(get(row, i)?)),
+        _ => {
+            let t = get::<Vec<u8>>(row, i)?;
+            match String::from_utf8(t) {
+                Ok(t) => ColType::String(Some(t)),
+                Err(e) => ColType::Binary(Some(e.into_bytes())),
+            }
+        }
+    };
+
+    Ok(value)
+}
+
+fn get<T>(row: &MySqlRow, i: usize) -> Result<T, String>
+where
+    T: for<'r> Decode<'r, MySql>,
+{
+    row.try_get_unchecked::<T, _>(i).map_err(|e| e.to_string())
+}
+
+fn text(bytes: Vec<u8>) -> String {
+    String::from_utf8_lossy(&bytes).to_string()
 }
@@ -1,13 +1,17 @@
 use std::{collections::BTreeMap, fs::File};
 
-use chrono::{DateTime, Local, NaiveTime};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime};
+use futures::TryStreamExt;
+use serde_json::Value;
 use sqlx::{
     query_as,
-    sqlite::{SqlitePool, SqlitePoolOptions, SqliteRow},
-    Column, Error, FromRow, Row, TypeInfo,
+    sqlite::{SqliteConnection, SqlitePool, SqlitePoolOptions, SqliteRow},
+    Column, Connection, Error, FromRow, Row, TypeInfo, ValueRef,
 };
 
-use super::model::ColType;
+use tokio::sync::mpsc::Sender;
+
+use super::model::{ColType, StreamRow};
 
 #[derive(Debug, Clone)]
 pub struct Sqlite {
@@ -55,12 +59,33 @@ impl Sqlite {
                             FOREIGN KEY (uploaded_by) REFERENCES users (id)
                         );
                     
+                    CREATE TABLE IF NOT EXISTS
+                        tus_uploads (
+                            id VARCHAR(36) NOT NULL PRIMARY KEY,
+                            file_name VARCHAR(255) NOT NULL,
+                            upload_length INTEGER NOT NULL,
+                            upload_offset INTEGER NOT NULL DEFAULT 0,
+                            uploaded_by INTEGER NOT NULL,
+                            FOREIGN KEY (uploaded_by) REFERENCES users (id)
+                        );
+                    
                     CREATE TABLE IF NOT EXISTS
                         queries (
                             id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
                             name VARCHAR(255) UNIQUE NOT NULL,
                             exec_type VARCHAR(50) NOT NULL DEFAULT 'get' CHECK (exec_type IN ('get', 'post', 'delete', 'put')),
-                            query TEXT DEFAULT ''
+                            query TEXT DEFAULT '',
+                            pagination VARCHAR(50) NOT NULL DEFAULT 'none' CHECK (pagination IN ('none', 'offset', 'cursor')),
+                            page_size INTEGER NOT NULL DEFAULT 100,
+                            cursor_column VARCHAR(255) NOT NULL DEFAULT '',
+                            with_total BOOLEAN NOT NULL DEFAULT FALSE,
+                            shape VARCHAR(50) NOT NULL DEFAULT 'flat' CHECK (shape IN ('flat', 'nested', 'single')),
+                            group_by VARCHAR(255) NOT NULL DEFAULT '',
+                            max_rows INTEGER NOT NULL DEFAULT 0,
+                            cache_ttl INTEGER NOT NULL DEFAULT 0,
+                            cache_params VARCHAR(255) NOT NULL DEFAULT '',
+                            cache_per_user BOOLEAN NOT NULL DEFAULT FALSE,
+                            cache_max_entries INTEGER NOT NULL DEFAULT 100
                         );
                     
                     CREATE TABLE IF NOT EXISTS
@@ -78,7 +103,9 @@ impl Sqlite {
                             name VARCHAR(255) UNIQUE NOT NULL,
                             up_query TEXT DEFAULT '',
                             down_query TEXT DEFAULT '',
-                            executed TINYINT(1) DEFAULT 0
+                            executed TINYINT(1) DEFAULT 0,
+                            checksum VARCHAR(64) NOT NULL DEFAULT '',
+                            applied_at INTEGER
                         );
 
                     CREATE TABLE IF NOT EXISTS
@@ -89,9 +116,82 @@ impl Sqlite {
                             action VARCHAR(50) NOT NULL DEFAULT 'before' CHECK (action IN ('before', 'after')),
                             url TEXT DEFAULT '',
                             args JSON DEFAULT '{}',
-                            is_returned TINYINT(1) DEFAULT 0
+                            is_returned TINYINT(1) DEFAULT 0,
+                            return_mode VARCHAR(50) NOT NULL DEFAULT 'keep' CHECK (return_mode IN ('keep', 'merge', 'replace')),
+                            secret VARCHAR(255) NOT NULL DEFAULT ''
                         );
                     
+                    CREATE TABLE IF NOT EXISTS
+                        webhook_outbox (
+                            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+                            webhook_id INTEGER NOT NULL,
+                            method VARCHAR(50) NOT NULL,
+                            url TEXT NOT NULL,
+                            payload TEXT NOT NULL,
+                            attempts INTEGER NOT NULL DEFAULT 0,
+                            next_attempt_at INTEGER NOT NULL DEFAULT 0,
+                            status VARCHAR(50) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'delivered', 'failed')),
+                            FOREIGN KEY (webhook_id) REFERENCES webhooks (id) ON DELETE CASCADE
+                        );
+
+                    CREATE TABLE IF NOT EXISTS
+                        webhook_deliveries (
+                            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+                            webhook_id INTEGER NOT NULL,
+                            method VARCHAR(50) NOT NULL,
+                            url TEXT NOT NULL,
+                            payload TEXT NOT NULL,
+                            status_code INTEGER,
+                            latency_ms INTEGER NOT NULL DEFAULT 0,
+                            response TEXT NOT NULL,
+                            error TEXT NOT NULL,
+                            created_at INTEGER NOT NULL,
+                            FOREIGN KEY (webhook_id) REFERENCES webhooks (id) ON DELETE CASCADE
+                        );
+                    
+                    CREATE TABLE IF NOT EXISTS
+                        table_triggers (
+                            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+                            webhook_id INTEGER NOT NULL,
+                            table_name VARCHAR(255) NOT NULL,
+                            event VARCHAR(50) NOT NULL CHECK (event IN ('insert', 'update', 'delete')),
+                            FOREIGN KEY (webhook_id) REFERENCES webhooks (id) ON DELETE CASCADE,
+                            UNIQUE (webhook_id, table_name, event)
+                        );
+
+                    CREATE TABLE IF NOT EXISTS
+                        change_log (
+                            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+                            table_name VARCHAR(255) NOT NULL,
+                            event VARCHAR(50) NOT NULL,
+                            old_row TEXT,
+                            new_row TEXT,
+                            processed TINYINT(1) NOT NULL DEFAULT 0
+                        );
+                    
+                    CREATE TABLE IF NOT EXISTS
+                        schedules (
+                            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+                            name VARCHAR(255) NOT NULL UNIQUE,
+                            cron VARCHAR(255) NOT NULL DEFAULT '0 0 * * * *',
+                            target_type VARCHAR(50) NOT NULL DEFAULT 'query' CHECK (target_type IN ('query', 'webhook')),
+                            target_id INTEGER NOT NULL DEFAULT 0,
+                            args TEXT NOT NULL DEFAULT '{}',
+                            enabled TINYINT(1) NOT NULL DEFAULT 0,
+                            next_run_at INTEGER NOT NULL DEFAULT 0
+                        );
+
+                    CREATE TABLE IF NOT EXISTS
+                        schedule_runs (
+                            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+                            schedule_id INTEGER NOT NULL,
+                            started_at INTEGER NOT NULL,
+                            duration_ms INTEGER NOT NULL DEFAULT 0,
+                            status VARCHAR(50) NOT NULL CHECK (status IN ('success', 'failed')),
+                            output TEXT NOT NULL,
+                            FOREIGN KEY (schedule_id) REFERENCES schedules (id) ON DELETE CASCADE
+                        );
+
                     CREATE TABLE IF NOT EXISTS
                         webhook_query (
                             webhook_id INTEGER NOT NULL,
@@ -100,13 +200,54 @@ impl Sqlite {
                             FOREIGN KEY (query_id) REFERENCES queries (id) ON DELETE CASCADE,
                             UNIQUE (webhook_id, query_id)
                         );
+
+                    CREATE TABLE IF NOT EXISTS
+                        rest_tables (
+                            id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+                            table_name VARCHAR(255) NOT NULL UNIQUE
+                        );
+
+                    CREATE TABLE IF NOT EXISTS
+                        rest_access (
+                            role_id INTEGER NOT NULL,
+                            rest_table_id INTEGER NOT NULL,
+                            operation VARCHAR(50) NOT NULL CHECK (operation IN ('list', 'get', 'insert', 'update', 'delete')),
+                            FOREIGN KEY (role_id) REFERENCES roles (id) ON DELETE CASCADE,
+                            FOREIGN KEY (rest_table_id) REFERENCES rest_tables (id) ON DELETE CASCADE,
+                            UNIQUE (role_id, rest_table_id, operation)
+                        );
                     ";
 
+                // columns added after the initial schema, errors for columns that already exist are ignored
+                let alter_queries = [
+                    "ALTER TABLE webhooks ADD COLUMN return_mode VARCHAR(50) NOT NULL DEFAULT 'keep' CHECK (return_mode IN ('keep', 'merge', 'replace'))",
+                    "ALTER TABLE webhooks ADD COLUMN secret VARCHAR(255) NOT NULL DEFAULT ''",
+                    "ALTER TABLE migrations ADD COLUMN checksum VARCHAR(64) NOT NULL DEFAULT ''",
+                    "ALTER TABLE migrations ADD COLUMN applied_at INTEGER",
+                    "ALTER TABLE queries ADD COLUMN pagination VARCHAR(50) NOT NULL DEFAULT 'none' CHECK (pagination IN ('none', 'offset', 'cursor'))",
+                    "ALTER TABLE queries ADD COLUMN page_size INTEGER NOT NULL DEFAULT 100",
+                    "ALTER TABLE queries ADD COLUMN cursor_column VARCHAR(255) NOT NULL DEFAULT ''",
+                    "ALTER TABLE queries ADD COLUMN with_total BOOLEAN NOT NULL DEFAULT FALSE",
+                    "ALTER TABLE queries ADD COLUMN shape VARCHAR(50) NOT NULL DEFAULT 'flat' CHECK (shape IN ('flat', 'nested', 'single'))",
+                    "ALTER TABLE queries ADD COLUMN group_by VARCHAR(255) NOT NULL DEFAULT ''",
+                    "ALTER TABLE queries ADD COLUMN max_rows INTEGER NOT NULL DEFAULT 0",
+                    "ALTER TABLE queries ADD COLUMN cache_ttl INTEGER NOT NULL DEFAULT 0",
+                    "ALTER TABLE queries ADD COLUMN cache_params VARCHAR(255) NOT NULL DEFAULT ''",
+                    "ALTER TABLE queries ADD COLUMN cache_per_user BOOLEAN NOT NULL DEFAULT FALSE",
+                    "ALTER TABLE queries ADD COLUMN cache_max_entries INTEGER NOT NULL DEFAULT 100",
+                ];
+
                 let q = sqlx::query(query);
                 match q.execute(&connection).await {
-                    Ok(_) => Self {
-                        connection: Ok(connection),
-                    },
+                    Ok(_) => {
+                        for alter_query in alter_queries {
+                            let _ = sqlx::query(alter_query).execute(&connection).await;
+                        }
+
+                        Self {
+                            connection: Ok(connection),
+                        }
+                    }
                     Err(e) => Self {
                         connection: Err(e.to_string()),
                     },
@@ -135,22 +276,25 @@ impl Sqlite {
         let mut q = sqlx::query(query);
 
         for arg in args {
-            q = match arg {
+            q = match arg.into_bind() {
                 ColType::Integer(t) => q.bind(t),
                 ColType::Real(t) => q.bind(t),
+                // sqlite has no unsigned 64 bit integers, text keeps the value intact
+                ColType::UnsignedInteger(t) => q.bind(t.map(|t| t.to_string())),
                 ColType::String(t) => q.bind(t),
                 ColType::Bool(t) => q.bind(t),
                 ColType::Date(t) => q.bind(t),
                 ColType::Time(t) => q.bind(t),
                 ColType::Datetime(t) => q.bind(t),
                 ColType::Json(t) => q.bind(t),
+                ColType::Binary(t) => q.bind(t),
                 _ => return Err("wrong type".to_string()),
             };
         }
 
         let conn = match &self.connection {
             Ok(conn) => conn,
-            Err(e) => panic!("{}", e),
+            Err(e) => return Err(e.clone()),
         };
 
         match q.fetch_all(conn).await {
@@ -159,26 +303,68 @@ impl Sqlite {
         }
     }
 
+    // rows are parsed and sent as they are fetched, fetching stops once the receiver is gone
+    pub async fn query_stream(
+        &self,
+        query: &str,
+        args: Vec<ColType>,
+        tx: Sender<Result<StreamRow, String>>,
+    ) -> Result<(), String> {
+        let mut q = sqlx::query(query);
+
+        for arg in args {
+            q = match arg.into_bind() {
+                ColType::Integer(t) => q.bind(t),
+                ColType::Real(t) => q.bind(t),
+                ColType::UnsignedInteger(t) => q.bind(t.map(|t| t.to_string())),
+                ColType::String(t) => q.bind(t),
+                ColType::Bool(t) => q.bind(t),
+                ColType::Date(t) => q.bind(t),
+                ColType::Time(t) => q.bind(t),
+                ColType::Datetime(t) => q.bind(t),
+                ColType::Json(t) => q.bind(t),
+                ColType::Binary(t) => q.bind(t),
+                _ => return Err("wrong type".to_string()),
+            };
+        }
+
+        let conn = match &self.connection {
+            Ok(conn) => conn,
+            Err(e) => return Err(e.clone()),
+        };
+
+        let mut rows = q.fetch(conn);
+        while let Some(row) = rows.try_next().await.map_err(|e| e.to_string())? {
+            if tx.send(parse_columns(&row)).await.is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
     pub async fn query_one(&self, query: &str, args: Vec<ColType>) -> Result<SqliteRow, String> {
         let mut q = sqlx::query(query);
 
         for arg in args {
-            q = match arg {
+            q = match arg.into_bind() {
                 ColType::Integer(t) => q.bind(t),
                 ColType::Real(t) => q.bind(t),
+                ColType::UnsignedInteger(t) => q.bind(t.map(|t| t.to_string())),
                 ColType::String(t) => q.bind(t),
                 ColType::Bool(t) => q.bind(t),
                 ColType::Date(t) => q.bind(t),
                 ColType::Time(t) => q.bind(t),
                 ColType::Datetime(t) => q.bind(t),
                 ColType::Json(t) => q.bind(t),
+                ColType::Binary(t) => q.bind(t),
                 _ => return Err("wrong type".to_string()),
             };
         }
 
         let conn = match &self.connection {
             Ok(conn) => conn,
-            Err(e) => panic!("{}", e),
+            Err(e) => return Err(e.clone()),
         };
 
         match q.fetch_one(conn).await {
@@ -191,22 +377,24 @@ impl Sqlite {
         let mut q = sqlx::query(query);
 
         for arg in args {
-            q = match arg {
+            q = match arg.into_bind() {
                 ColType::Integer(t) => q.bind(t),
                 ColType::Real(t) => q.bind(t),
+                ColType::UnsignedInteger(t) => q.bind(t.map(|t| t.to_string())),
                 ColType::String(t) => q.bind(t),
                 ColType::Bool(t) => q.bind(t),
                 ColType::Date(t) => q.bind(t),
                 ColType::Time(t) => q.bind(t),
                 ColType::Datetime(t) => q.bind(t),
                 ColType::Json(t) => q.bind(t),
+                ColType::Binary(t) => q.bind(t),
                 _ => return Err("wrong type".to_string()),
             };
         }
 
         let conn = match &self.connection {
             Ok(conn) => conn,
-            Err(e) => panic!("{}", e),
+            Err(e) => return Err(e.clone()),
         };
 
         match q.execute(conn).await {
@@ -215,16 +403,190 @@ impl Sqlite {
         }
     }
 
-    pub async fn query_all_with_type<T>(&self, query: &str) -> Result<Vec<T>, String>
+    pub async fn insert(&self, query: &str, args: Vec<ColType>) -> Result<i64, String> {
+        let mut q = sqlx::query(query);
+
+        for arg in args {
+            q = match arg.into_bind() {
+                ColType::Integer(t) => q.bind(t),
+                ColType::Real(t) => q.bind(t),
+                ColType::UnsignedInteger(t) => q.bind(t.map(|t| t.to_string())),
+                ColType::String(t) => q.bind(t),
+                ColType::Bool(t) => q.bind(t),
+                ColType::Date(t) => q.bind(t),
+                ColType::Time(t) => q.bind(t),
+                ColType::Datetime(t) => q.bind(t),
+                ColType::Json(t) => q.bind(t),
+                ColType::Binary(t) => q.bind(t),
+                _ => return Err("wrong type".to_string()),
+            };
+        }
+
+        let conn = match &self.connection {
+            Ok(conn) => conn,
+            Err(e) => return Err(e.clone()),
+        };
+
+        match q.execute(conn).await {
+            Ok(out) => Ok(out.last_insert_rowid()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub async fn execute_in_transaction(
+        &self,
+        queries: Vec<(String, Vec<ColType>)>,
+    ) -> Result<u64, String> {
+        let pool = match &self.connection {
+            Ok(conn) => conn,
+            Err(e) => return Err(e.clone()),
+        };
+
+        let mut conn = match pool.acquire().await {
+            Ok(conn) => conn,
+            Err(e) => return Err(e.to_string()),
+        };
+
+        // rebuilding a table drops it, with foreign keys on that cascades into referencing tables.
+        // the pragma is ignored inside a transaction so it is switched off around it and
+        // foreign_key_check runs before commit instead, only rows it breaks count
+        if let Err(e) = sqlx::query("PRAGMA foreign_keys=OFF")
+            .execute(&mut *conn)
+            .await
+        {
+            return Err(e.to_string());
+        }
+
+        let res = Self::run_transaction(&mut conn, queries).await;
+
+        let _ = sqlx::query("PRAGMA foreign_keys=ON")
+            .execute(&mut *conn)
+            .await;
+
+        res
+    }
+
+    async fn run_transaction(
+        conn: &mut SqliteConnection,
+        queries: Vec<(String, Vec<ColType>)>,
+    ) -> Result<u64, String> {
+        let mut tx = match conn.begin().await {
+            Ok(tx) => tx,
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let existing = Self::foreign_key_violations(&mut tx).await?;
+
+        let mut rows_affected = 0;
+        for (query, args) in queries {
+            let mut q = sqlx::query(&query);
+
+            for arg in args {
+                q = match arg.into_bind() {
+                    ColType::Integer(t) => q.bind(t),
+                    ColType::Real(t) => q.bind(t),
+                    ColType::UnsignedInteger(t) => q.bind(t.map(|t| t.to_string())),
+                    ColType::String(t) => q.bind(t),
+                    ColType::Bool(t) => q.bind(t),
+                    ColType::Date(t) => q.bind(t),
+                    ColType::Time(t) => q.bind(t),
+                    ColType::Datetime(t) => q.bind(t),
+                    ColType::Json(t) => q.bind(t),
+                    ColType::Binary(t) => q.bind(t),
+                    _ => return Err("wrong type".to_string()),
+                };
+            }
+
+            // dropping the transaction on error rolls it back
+            match q.execute(&mut *tx).await {
+                Ok(out) => rows_affected += out.rows_affected(),
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+
+        let violations = Self::foreign_key_violations(&mut tx)
+            .await?
+            .into_iter()
+            .filter(|v| !existing.contains(v))
+            .collect::<Vec<(String, Option<i64>, String, i64)>>();
+
+        if !violations.is_empty() {
+            let mut tables = violations
+                .iter()
+                .map(|(table, _, parent, _)| format!("{} -> {}", table, parent))
+                .collect::<Vec<String>>();
+            tables.sort();
+            tables.dedup();
+
+            return Err(format!(
+                "foreign key check failed for {} rows ({})",
+                violations.len(),
+                tables.join(", ")
+            ));
+        }
+
+        match tx.commit().await {
+            Ok(_) => Ok(rows_affected),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    // (table, rowid, parent, fkid) of every row pointing at a missing parent
+    async fn foreign_key_violations(
+        conn: &mut SqliteConnection,
+    ) -> Result<Vec<(String, Option<i64>, String, i64)>, String> {
+        let rows = match sqlx::query("PRAGMA foreign_key_check")
+            .fetch_all(&mut *conn)
+            .await
+        {
+            Ok(rows) => rows,
+            Err(e) => return Err(e.to_string()),
+        };
+
+        rows.iter()
+            .map(|row| {
+                Ok((
+                    row.try_get("table").map_err(|e| e.to_string())?,
+                    row.try_get("rowid").map_err(|e| e.to_string())?,
+                    row.try_get("parent").map_err(|e| e.to_string())?,
+                    row.try_get("fkid").map_err(|e| e.to_string())?,
+                ))
+            })
+            .collect()
+    }
+
+    pub async fn query_all_with_type<T>(
+        &self,
+        query: &str,
+        args: Vec<ColType>,
+    ) -> Result<Vec<T>, String>
     where
         T: for<'r> FromRow<'r, SqliteRow> + Unpin + Send,
     {
+        let mut q = query_as(query);
+
+        for arg in args {
+            q = match arg.into_bind() {
+                ColType::Integer(t) => q.bind(t),
+                ColType::Real(t) => q.bind(t),
+                ColType::UnsignedInteger(t) => q.bind(t.map(|t| t.to_string())),
+                ColType::String(t) => q.bind(t),
+                ColType::Bool(t) => q.bind(t),
+                ColType::Date(t) => q.bind(t),
+                ColType::Time(t) => q.bind(t),
+                ColType::Datetime(t) => q.bind(t),
+                ColType::Json(t) => q.bind(t),
+                ColType::Binary(t) => q.bind(t),
+                _ => return Err("wrong type".to_string()),
+            };
+        }
+
         let conn = match &self.connection {
             Ok(conn) => conn,
-            Err(e) => panic!("{}", e),
+            Err(e) => return Err(e.clone()),
         };
 
-        let r_out: Result<Vec<T>, Error> = query_as(query).fetch_all(conn).await;
+        let r_out: Result<Vec<T>, Error> = q.fetch_all(conn).await;
 
         match r_out {
             Ok(out) => Ok(out),
@@ -232,16 +594,34 @@ impl Sqlite {
         }
     }
 
-    pub async fn query_one_with_type<T>(&self, query: &str) -> Result<T, String>
+    pub async fn query_one_with_type<T>(&self, query: &str, args: Vec<ColType>) -> Result<T, String>
     where
         T: for<'r> FromRow<'r, SqliteRow> + Unpin + Send,
     {
+        let mut q = query_as(query);
+
+        for arg in args {
+            q = match arg.into_bind() {
+                ColType::Integer(t) => q.bind(t),
+                ColType::Real(t) => q.bind(t),
+                ColType::UnsignedInteger(t) => q.bind(t.map(|t| t.to_string())),
+                ColType::String(t) => q.bind(t),
+                ColType::Bool(t) => q.bind(t),
+                ColType::Date(t) => q.bind(t),
+                ColType::Time(t) => q.bind(t),
+                ColType::Datetime(t) => q.bind(t),
+                ColType::Json(t) => q.bind(t),
+                ColType::Binary(t) => q.bind(t),
+                _ => return Err("wrong type".to_string()),
+            };
+        }
+
         let conn = match &self.connection {
             Ok(conn) => conn,
-            Err(e) => panic!("{}", e),
+            Err(e) => return Err(e.clone()),
         };
 
-        let r_out: Result<T, Error> = query_as(query).fetch_one(conn).await;
+        let r_out: Result<T, Error> = q.fetch_one(conn).await;
 
         match r_out {
             Ok(out) => Ok(out),
@@ -256,51 +636,196 @@ impl Sqlite {
         let mut table_data = vec![];
 
         for row in rows {
-            let mut map: BTreeMap<String, ColType> = BTreeMap::new();
+            table_data.push(parse_row(&row)?);
+        }
 
-            for i in 0..row.len() {
-                let row_value = match row.column(i).type_info().name() {
-                    "TEXT" | "VARCHAR" => {
-                        let t = row.get::<Option<String>, _>(i);
-                        ColType::String(t)
-                    }
-                    "INTEGER" => {
-                        let t = row.get::<Option<i64>, _>(i);
-                        ColType::Integer(t)
-                    }
-                    "REAL" | "NUMERIC" => {
-                        let t = row.get::<Option<f64>, _>(i);
-                        ColType::Real(t)
-                    }
-                    "BOOLEAN" => {
-                        let t = row.get::<Option<bool>, _>(i);
-                        ColType::Bool(t)
-                    }
-                    "DATETIME" => {
-                        let t = row.get::<Option<DateTime<Local>>, _>(i);
-                        ColType::Datetime(t)
-                    }
-                    "DATE" => {
-                        let t = row.get::<Option<DateTime<Local>>, _>(i);
-                        ColType::Date(t)
-                    }
-                    "TIME" => {
-                        let t = row.get::<Option<NaiveTime>, _>(i);
-                        ColType::Time(t)
-                    }
-                    "JSON" => {
-                        let t = row.get::<Option<String>, _>(i);
-                        ColType::Json(t)
-                    }
-                    _ => return Err("wrong type".to_string()),
-                };
+        Ok(table_data)
+    }
+}
 
-                map.insert(row.column(i).name().to_string(), row_value);
-            }
+fn parse_row(row: &SqliteRow) -> Result<BTreeMap<String, ColType>, String> {
+    Ok(parse_columns(row)?.into_iter().collect())
+}
+
+fn parse_columns(row: &SqliteRow) -> Result<StreamRow, String> {
+    let mut columns = vec![];
+
+    for i in 0..row.len() {
+        let row_value = parse_value(row, i)?;
+
+        columns.push((row.column(i).name().to_string(), row_value));
+    }
+
+    Ok(columns)
+}
 
-            table_data.push(map);
+// the declared type of a column only hints at what sqlite stored, so the value's
+// own storage class decides and the declaration refines it
+fn parse_value(row: &SqliteRow, i: usize) -> Result<ColType, String> {
+    let raw = row.try_get_raw(i).map_err(|e| e.to_string())?;
+    if raw.is_null() {
+        return Ok(ColType::Null);
+    }
+
+    let declared = row.column(i).type_info().name();
+    let stored = raw.type_info().name().to_string();
+
+    let value = match (declared, stored.as_str()) {
+        ("BOOLEAN", "INTEGER") => ColType::Bool(row.try_get_unchecked(i).ok()),
+        ("DATETIME", _) => match row.try_get_unchecked::<DateTime<Local>, _>(i) {
+            Ok(t) => ColType::Datetime(Some(t)),
+            Err(_) => text_or_number(row, i, &stored)?,
+        },
+        ("DATE", "TEXT") => match row.try_get_unchecked::<NaiveDate, _>(i) {
+            Ok(t) => ColType::Date(Some(t)),
+            Err(_) => text_or_number(row, i, &stored)?,
+        },
+        ("TIME", "TEXT") => match row.try_get_unchecked::<NaiveTime, _>(i) {
+            Ok(t) => ColType::Time(Some(t)),
+            Err(_) => text_or_number(row, i, &stored)?,
+        },
+        ("TEXT", "TEXT") => text_or_number(row, i, &stored)?,
+        // declarations sqlx doesn't know (JSON, DECIMAL, expressions) come back as text,
+        // text holding an object or array is most likely json
+        (_, "TEXT") => {
+            let t = row
+                .try_get_unchecked::<String, _>(i)
+                .map_err(|e| e.to_string())?;
+            match t.starts_with(['{', '[']) && serde_json::from_str::<Value>(&t).is_ok() {
+                true => ColType::Json(Some(t)),
+                false => ColType::String(Some(t)),
+            }
         }
+        _ => text_or_number(row, i, &stored)?,
+    };
 
-        Ok(table_data)
+    Ok(value)
+}
+
+fn text_or_number(row: &SqliteRow, i: usize, stored: &str) -> Result<ColType, String> {
+    let value = match stored {
+        "INTEGER" => ColType::Integer(Some(row.try_get_unchecked(i).map_err(|e| e.to_string())?)),
+        "REAL" => ColType::Real(Some(row.try_get_unchecked(i).map_err(|e| e.to_string())?)),
+        "BLOB" => ColType::Binary(Some(row.try_get_unchecked(i).map_err(|e| e.to_string())?)),
+        _ => ColType::String(Some(row.try_get_unchecked(i).map_err(|e| e.to_string())?)),
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::Sqlite;
+    use crate::database::model::ColType;
+
+    #[tokio::test]
+    async fn test1() {
+        let dir = std::env::temp_dir().join(format!("mini-base-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sqlite = Sqlite::new(dir.join("test.db").to_str().unwrap()).await;
+
+        sqlite
+            .execute(
+                "CREATE TABLE items (
+                    id BIGINT PRIMARY KEY,
+                    price DECIMAL(10, 2),
+                    data BLOB,
+                    meta JSON,
+                    day DATE,
+                    flag BOOLEAN,
+                    note TEXT
+                )",
+                vec![],
+            )
+            .await
+            .unwrap();
+
+        sqlite
+            .execute(
+                "INSERT INTO items VALUES (?, ?, ?, ?, ?, ?, ?)",
+                vec![
+                    ColType::Integer(Some(9007199254740993)),
+                    ColType::Real(Some(2.5)),
+                    ColType::Binary(Some(vec![0, 255])),
+                    ColType::get_col_type_from_value(json!({"a": [1, 2]})),
+                    ColType::String(Some("2024-02-29".to_string())),
+                    ColType::Bool(Some(true)),
+                    ColType::Null,
+                ],
+            )
+            .await
+            .unwrap();
+
+        let rows = sqlite
+            .query_all("SELECT *, 1 AS one FROM items", vec![])
+            .await
+            .unwrap();
+        let rows = sqlite.parse_all(rows).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&rows[0]).unwrap(),
+            json!({
+                "id": 9007199254740993i64,
+                "price": 2.5,
+                "data": "AP8=",
+                "meta": {"a": [1, 2]},
+                "day": "2024-02-29",
+                "flag": true,
+                "note": null,
+                "one": 1
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test2() {
+        let dir = std::env::temp_dir().join(format!("mini-base-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sqlite = Sqlite::new(dir.join("test.db").to_str().unwrap()).await;
+
+        sqlite
+            .execute("CREATE TABLE parent (id INTEGER PRIMARY KEY)", vec![])
+            .await
+            .unwrap();
+        sqlite
+            .execute(
+                "CREATE TABLE child (id INTEGER PRIMARY KEY, parent_id INTEGER REFERENCES parent(id))",
+                vec![],
+            )
+            .await
+            .unwrap();
+
+        // a row that was already dangling before any migration ran
+        let mut conn = sqlite.connection.as_ref().unwrap().acquire().await.unwrap();
+        for query in [
+            "PRAGMA foreign_keys=OFF",
+            "INSERT INTO child VALUES (1, 5)",
+            "PRAGMA foreign_keys=ON",
+        ] {
+            sqlx::query(query).execute(&mut *conn).await.unwrap();
+        }
+        drop(conn);
+
+        let res = sqlite
+            .execute_in_transaction(vec![(
+                "CREATE TABLE other (id INTEGER)".to_string(),
+                vec![],
+            )])
+            .await;
+        assert_eq!(res, Ok(0));
+
+        let res = sqlite
+            .execute_in_transaction(vec![
+                ("INSERT INTO parent VALUES (1)".to_string(), vec![]),
+                ("INSERT INTO child VALUES (2, 1)".to_string(), vec![]),
+                ("DELETE FROM parent".to_string(), vec![]),
+            ])
+            .await;
+        assert_eq!(
+            res,
+            Err("foreign key check failed for 1 rows (child -> parent)".to_string())
+        );
     }
 }
@@ -0,0 +1,157 @@
+use std::fmt;
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+
+use crate::server::metrics;
+
+#[derive(Debug, Clone)]
+pub enum AppError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    Unprocessable(String),
+    Webhook(StatusCode, String),
+    Database(String),
+    Internal(String),
+}
+
+impl AppError {
+    // sql errors only reach the client when they are caused by the request itself
+    pub fn from_db(e: String) -> Self {
+        let error = e.to_lowercase();
+
+        if error.contains("unique constraint") || error.contains("duplicate entry") {
+            AppError::Conflict("a row with the same unique value already exists".to_string())
+        } else if error.contains("foreign key constraint") {
+            AppError::BadRequest("a referenced row does not exist or is still in use".to_string())
+        } else if error.contains("not null constraint") || error.contains("cannot be null") {
+            AppError::BadRequest("a required value is missing".to_string())
+        } else if error.contains("check constraint") {
+            AppError::BadRequest("a value is not allowed".to_string())
+        } else {
+            AppError::Database(e)
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "bad_request",
+            AppError::Unauthorized(_) => "unauthorized",
+            AppError::Forbidden(_) => "forbidden",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::Unprocessable(_) => "unprocessable",
+            AppError::Webhook(_, _) => "webhook_failed",
+            AppError::Database(_) => "database_error",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Webhook(status, _) => *status,
+            AppError::Database(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    // internal errors are logged with an id the client can report back, never echoed
+    pub fn to_json(&self) -> Value {
+        let (message, details) = match self {
+            AppError::Database(e) | AppError::Internal(e) => {
+                let request_id =
+                    metrics::request_id().unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+                log::error!("request {}: {}", request_id, e);
+
+                (
+                    "internal server error".to_string(),
+                    json!({ "request_id": request_id }),
+                )
+            }
+            AppError::Webhook(status, e) => (e.clone(), json!({ "status": status.as_u16() })),
+            AppError::BadRequest(e)
+            | AppError::Unauthorized(e)
+            | AppError::Forbidden(e)
+            | AppError::NotFound(e)
+            | AppError::Conflict(e)
+            | AppError::Unprocessable(e) => (e.clone(), Value::Null),
+        };
+
+        json!({
+            "code": self.code(),
+            "message": message,
+            "details": details,
+        })
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::BadRequest(e)
+            | AppError::Unauthorized(e)
+            | AppError::Forbidden(e)
+            | AppError::NotFound(e)
+            | AppError::Conflict(e)
+            | AppError::Unprocessable(e)
+            | AppError::Webhook(_, e)
+            | AppError::Database(e)
+            | AppError::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        (self.status(), Json(self.to_json())).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    use super::AppError;
+
+    #[test]
+    fn test1() {
+        let e = AppError::from_db("UNIQUE constraint failed: users.email".to_string());
+        assert_eq!(e.status(), StatusCode::CONFLICT);
+        assert!(!e.to_string().contains("users.email"));
+
+        let e = AppError::from_db(
+            "Cannot delete or update a parent row: a foreign key constraint fails".to_string(),
+        );
+        assert_eq!(e.code(), "bad_request");
+
+        let e = AppError::from_db("no such column: passwrd".to_string());
+        assert_eq!(e.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[test]
+    fn test2() {
+        assert_eq!(
+            AppError::NotFound("row not found".to_string()).to_json(),
+            json!({ "code": "not_found", "message": "row not found", "details": null })
+        );
+
+        let body = AppError::Database("no such table: todos".to_string()).to_json();
+        assert_eq!(body["code"], "database_error");
+        assert_eq!(body["message"], "internal server error");
+        assert!(body["details"]["request_id"].is_string());
+        assert!(!body.to_string().contains("todos"));
+    }
+}
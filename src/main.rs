@@ -1,8 +1,10 @@
-use std::{fs, io::ErrorKind};
+use std::{env, fs, io::ErrorKind};
 
 use flexi_logger::{Age, Cleanup, Criterion, Logger, Naming};
 
+mod cli;
 mod database;
+mod error;
 mod parser;
 mod queries;
 mod server;
@@ -33,5 +35,14 @@ async fn main() {
         .start()
         .expect("failed to initialize logger!");
 
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    if !args.is_empty() {
+        if let Err(e) = cli::run(args).await {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     tui::run();
 }
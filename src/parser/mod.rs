@@ -2,11 +2,11 @@ use std::collections::HashMap;
 
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take, take_until},
-    character::complete::{alpha1, alphanumeric1, digit1, multispace1},
+    bytes::complete::{tag, tag_no_case, take, take_until, take_while1},
+    character::complete::{alpha1, alphanumeric1, digit1, multispace0, multispace1},
     combinator::{peek, value},
-    multi::{many0, many1},
-    sequence::{delimited, preceded},
+    multi::{many0, many1, separated_list1},
+    sequence::{delimited, preceded, separated_pair, tuple},
     IResult,
 };
 
@@ -80,15 +80,221 @@ pub fn parse_query(input: &str) -> IResult<&str, Vec<&str>> {
     Ok((input, variables))
 }
 
-pub fn replace_variables_in_query(input: &str, variables: Vec<&str>) -> String {
-    let mut out = input.to_string();
+fn parse_sql_identifier(input: &str) -> IResult<&str, &str> {
+    preceded(
+        peek(alt((alpha1, tag("_")))),
+        take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.'),
+    )(input)
+}
+
+// `${@sort in created_at,title}` is replaced by one of the listed identifiers
+fn parse_identifier_placeholder(input: &str) -> IResult<&str, (&str, Vec<&str>)> {
+    delimited(
+        tag("${@"),
+        separated_pair(
+            parse_sql_identifier,
+            tuple((multispace1, tag_no_case("in"), multispace1)),
+            separated_list1(
+                tag(","),
+                delimited(multispace0, parse_sql_identifier, multispace0),
+            ),
+        ),
+        tag("}"),
+    )(input)
+}
+
+pub fn get_identifier_placeholders(input: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut placeholders = vec![];
+    let mut rest = input;
+
+    while let Some(start) = rest.find("${@") {
+        let (after, (name, allowed)) = parse_identifier_placeholder(&rest[start..])
+            .map_err(|_| format!("invalid identifier placeholder at {}", &rest[start..]))?;
+
+        placeholders.push((
+            name.to_string(),
+            allowed.into_iter().map(|a| a.to_string()).collect(),
+        ));
+        rest = after;
+    }
+
+    Ok(placeholders)
+}
+
+// values are `column` or `column asc|desc`, anything not listed in the placeholder is
+// rejected and a missing value falls back to the first listed identifier
+pub fn replace_identifier_placeholders(
+    input: &str,
+    values: &HashMap<String, String>,
+) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = input;
+
+    while let Some(start) = rest.find("${@") {
+        let (after, (name, allowed)) = parse_identifier_placeholder(&rest[start..])
+            .map_err(|_| format!("invalid identifier placeholder at {}", &rest[start..]))?;
+
+        let value = match values.get(name) {
+            Some(value) => value.as_str(),
+            None => allowed[0],
+        };
+
+        let identifier = match value.split_whitespace().collect::<Vec<&str>>()[..] {
+            [column] if allowed.contains(&column) => column.to_string(),
+            [column, direction]
+                if allowed.contains(&column)
+                    && (direction.eq_ignore_ascii_case("asc")
+                        || direction.eq_ignore_ascii_case("desc")) =>
+            {
+                format!("{} {}", column, direction.to_uppercase())
+            }
+            _ => {
+                return Err(format!(
+                    "{} is not allowed for {}, expected one of {}",
+                    value,
+                    name,
+                    allowed.join(", ")
+                ))
+            }
+        };
+
+        out += &rest[..start];
+        out += &identifier;
+        rest = after;
+    }
+
+    Ok(out + rest)
+}
+
+// the name a placeholder is looked up by, `@sort in a,b` is looked up as `sort`
+fn placeholder_name(variable: &str) -> &str {
+    match variable.strip_prefix('@') {
+        Some(placeholder) => placeholder.split_whitespace().next().unwrap_or(""),
+        None => variable,
+    }
+}
+
+fn parse_optional_block(input: &str) -> IResult<&str, &str> {
+    delimited(tag("[["), take_until("]]"), tag("]]"))(input)
+}
+
+// `[[ AND status = ${status} ]]` keeps its content when any variable inside is present
+// and is dropped otherwise
+pub fn resolve_optional_blocks<F>(input: &str, is_present: F) -> Result<String, String>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut out = String::new();
+    let mut rest = input;
+
+    loop {
+        let start = rest.find("[[").unwrap_or(rest.len());
+        if rest[..start].contains("]]") {
+            return Err("optional block closed with ]] without an opening [[".to_string());
+        }
+
+        out += &rest[..start];
+        if start == rest.len() {
+            return Ok(out);
+        }
+
+        let (after, block) = parse_optional_block(&rest[start..])
+            .map_err(|_| "optional block opened with [[ is never closed".to_string())?;
+        if block.contains("[[") {
+            return Err("optional blocks can't be nested".to_string());
+        }
+
+        let variables = many0(take_variables)(block)
+            .map(|(_, variables)| variables)
+            .unwrap_or_default();
+        if variables.is_empty() {
+            return Err(format!("optional block [[{}]] has no variables", block));
+        }
 
-    variables.into_iter().enumerate().for_each(|(_i, var)| {
+        if variables.into_iter().map(placeholder_name).any(&is_present) {
+            out += block;
+        }
+        rest = after;
+    }
+}
+
+pub fn validate_query(input: &str) -> Result<(), String> {
+    resolve_optional_blocks(input, |_| true)?;
+    get_identifier_placeholders(input)?;
+
+    Ok(())
+}
+
+// list_lengths holds, per variable occurrence, how many values an expanded IN list takes
+pub fn replace_variables_in_query(
+    input: &str,
+    variables: Vec<&str>,
+    list_lengths: &[Option<usize>],
+) -> String {
+    let mut out = String::new();
+    let mut rest = input;
+
+    for (i, var) in variables.into_iter().enumerate() {
         let from = format!("${{{var}}}");
+        let Some(start) = rest.find(&from) else {
+            continue;
+        };
+
+        out += &rest[..start];
+        out += &match list_lengths.get(i).copied().flatten() {
+            // `x NOT IN (NULL)` is NULL for every row, an empty subquery keeps it true
+            Some(0) if is_not_in(&out) => "SELECT NULL WHERE 1=0".to_string(),
+            // `IN (NULL)` is valid sql on every backend and matches no rows
+            Some(0) => "NULL".to_string(),
+            Some(n) => vec!["?"; n].join(", "),
+            None => "?".to_string(),
+        };
+        rest = &rest[start + from.len()..];
+    }
 
-        out = out.replace::<&str>(from.as_ref(), "?");
-    });
-    out
+    out + rest
+}
+
+fn is_not_in(before: &str) -> bool {
+    let mut words = before
+        .trim_end()
+        .trim_end_matches('(')
+        .rsplit(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter(|w| !w.is_empty());
+
+    matches!(
+        (words.next(), words.next()),
+        (Some(i), Some(n)) if i.eq_ignore_ascii_case("in") && n.eq_ignore_ascii_case("not")
+    )
+}
+
+// per variable occurrence, whether it is the whole of an `IN (...)` list
+pub fn get_in_list_variables(input: &str, variables: &[&str]) -> Vec<bool> {
+    let mut offset = 0;
+
+    variables
+        .iter()
+        .map(|var| {
+            let from = format!("${{{var}}}");
+            let Some(start) = input[offset..].find(&from) else {
+                return false;
+            };
+
+            let start = offset + start;
+            offset = start + from.len();
+
+            let before = match input[..start].trim_end().strip_suffix('(') {
+                Some(before) => before.trim_end(),
+                None => return false,
+            };
+            let keyword = before
+                .rsplit(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .next()
+                .unwrap_or("");
+
+            keyword.eq_ignore_ascii_case("in") && input[offset..].trim_start().starts_with(')')
+        })
+        .collect()
 }
 
 pub fn replace_variables_with_values(
@@ -101,67 +307,62 @@ pub fn replace_variables_with_values(
     let variables = many0(take_variables)(&binding).unwrap().1;
 
     for variable in variables {
-        let mut t_variable = variable;
-        if variable.contains("res.") {
-            t_variable = "res";
-        }
-
-        if let Some(var) = values.get(t_variable) {
-            out = replace_coltype_value(out, var.clone(), variable.split('.').collect(), variable);
+        if let Some(var) = get_variable_value(&values, variable) {
+            out = replace_coltype_value(out, var, variable);
         }
     }
     out
 }
 
-fn replace_coltype_value(
-    out: String,
-    variable: Option<ColType>,
-    mut res_var: Vec<&str>,
-    original_var: &str,
-) -> String {
+pub fn get_variable_value(
+    values: &HashMap<String, Option<ColType>>,
+    variable: &str,
+) -> Option<ColType> {
+    if let Some(var) = values.get(variable) {
+        return var.clone();
+    }
+
+    if variable.starts_with('.') {
+        return None;
+    }
+
+    let mut path = variable.split('.');
+    let mut current = values.get(path.next()?)?.clone()?;
+
+    for key in path {
+        current = match current {
+            ColType::Array(Some(t)) => t.get(key.parse::<usize>().ok()?)?.clone(),
+            ColType::Object(Some(t)) => *t.get(key)?.clone(),
+            _ => return None,
+        };
+    }
+
+    Some(current)
+}
+
+fn replace_coltype_value(out: String, variable: ColType, original_var: &str) -> String {
     let from = format!("${{{original_var}}}");
 
     match variable {
-        Some(var) => match var {
-            ColType::Integer(t) => out.replace::<&str>(from.as_ref(), &t.unwrap().to_string()),
-            ColType::Real(t) => out.replace::<&str>(from.as_ref(), &t.unwrap().to_string()),
-            ColType::UnsignedInteger(t) => {
-                out.replace::<&str>(from.as_ref(), &t.unwrap().to_string())
-            }
-            ColType::String(t) => {
-                let nt = t.unwrap();
-                out.replace::<&str>(from.as_ref(), &format!("\"{nt}\""))
-            }
-            ColType::Bool(t) => out.replace::<&str>(from.as_ref(), &t.unwrap().to_string()),
-            ColType::Date(t) => out.replace::<&str>(from.as_ref(), &t.unwrap().to_string()),
-            ColType::Time(t) => out.replace::<&str>(from.as_ref(), &t.unwrap().to_string()),
-            ColType::Datetime(t) => out.replace::<&str>(from.as_ref(), &t.unwrap().to_string()),
-            ColType::Array(t) => {
-                res_var.remove(0);
-                let rvf = res_var.first().unwrap();
-                if let Ok(i) = rvf.parse::<usize>() {
-                    let t = t.unwrap();
-                    let l = t.get(i).unwrap();
-
-                    replace_coltype_value(out, Some(l.clone()), res_var, original_var)
-                } else {
-                    "".to_string()
-                }
-            }
-            ColType::Object(t) => {
-                res_var.remove(0);
-                let rvf = res_var.first().unwrap();
-                let t = t.unwrap();
-                let l = t.get(*rvf).unwrap();
-
-                replace_coltype_value(out, Some(*l.clone()), res_var, original_var)
-            }
-            ColType::Json(_) => out.clone(),
-        },
-        None => out,
+        ColType::Integer(Some(t)) => out.replace::<&str>(from.as_ref(), &t.to_string()),
+        ColType::Real(Some(t)) => out.replace::<&str>(from.as_ref(), &t.to_string()),
+        ColType::UnsignedInteger(Some(t)) => out.replace::<&str>(from.as_ref(), &t.to_string()),
+        // row values can hold anything, quoted as json so they can't break out of the template
+        ColType::String(Some(t)) => out.replace::<&str>(from.as_ref(), &json_string(&t)),
+        ColType::Bool(Some(t)) => out.replace::<&str>(from.as_ref(), &t.to_string()),
+        ColType::Date(Some(t)) => out.replace::<&str>(from.as_ref(), &json_string(&t)),
+        ColType::Time(Some(t)) => out.replace::<&str>(from.as_ref(), &json_string(&t)),
+        ColType::Datetime(Some(t)) => out.replace::<&str>(from.as_ref(), &json_string(&t)),
+        ColType::Decimal(Some(t)) => out.replace::<&str>(from.as_ref(), &t),
+        ColType::Null => out.replace::<&str>(from.as_ref(), "null"),
+        _ => out,
     }
 }
 
+fn json_string<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or("null".to_string())
+}
+
 #[test]
 fn test_replace_variables_with_values() {
     let out = "
@@ -184,6 +385,32 @@ fn test_replace_variables_with_values() {
     dbg!(res);
 }
 
+#[test]
+fn test_replace_webhook_variables_with_values() {
+    let out = "{ \"body\": { \"plan\": ${wh.billing.plan}, \"seats\": ${wh.billing.seats}, \"missing\": ${wh.billing.x} } }";
+
+    let mut billing = HashMap::new();
+    billing.insert(
+        "plan".to_string(),
+        Box::new(ColType::String(Some("pro".to_string()))),
+    );
+    billing.insert("seats".to_string(), Box::new(ColType::Integer(Some(5))));
+
+    let mut wh = HashMap::new();
+    wh.insert(
+        "billing".to_string(),
+        Box::new(ColType::Object(Some(billing))),
+    );
+
+    let mut values = HashMap::new();
+    values.insert("wh".to_string(), Some(ColType::Object(Some(wh))));
+
+    assert_eq!(
+        replace_variables_with_values(out, values),
+        "{ \"body\": { \"plan\": \"pro\", \"seats\": 5, \"missing\": ${wh.billing.x} } }"
+    );
+}
+
 fn take_variables(input: &str) -> IResult<&str, &str> {
     delimited(
         preceded(take_until("${"), take(2usize)),
@@ -213,9 +440,68 @@ pub fn parse_type(input: &str) -> &str {
     }
 }
 
+fn sql_tokens(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| c.is_whitespace() || [',', '(', ')', ';'].contains(&c))
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_string())
+        .collect()
+}
+
+fn table_name(token: &str) -> Option<String> {
+    let name = token
+        .rsplit('.')
+        .next()?
+        .trim_matches(|c| c == '`' || c == '"' || c == '[' || c == ']');
+
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Some(name.to_lowercase())
+    } else {
+        None
+    }
+}
+
+fn tables_after(tokens: &[String], keywords: &[&str]) -> Vec<String> {
+    let mut tables = vec![];
+
+    for (i, token) in tokens.iter().enumerate() {
+        if !keywords.contains(&token.to_uppercase().as_str()) {
+            continue;
+        }
+
+        if let Some(table) = tokens.get(i + 1).and_then(|t| table_name(t)) {
+            if !tables.contains(&table) {
+                tables.push(table);
+            }
+        }
+    }
+    tables
+}
+
+pub fn get_tables(query: &str) -> Vec<String> {
+    tables_after(&sql_tokens(query), &["FROM", "JOIN", "INTO", "UPDATE"])
+}
+
+pub fn get_written_tables(query: &str) -> Vec<String> {
+    let tokens = sql_tokens(query);
+
+    match tokens.first().map(|t| t.to_uppercase()).as_deref() {
+        Some("INSERT") | Some("REPLACE") => tables_after(&tokens, &["INTO"]),
+        Some("UPDATE") => tables_after(&tokens, &["UPDATE"]),
+        Some("DELETE") => tables_after(&tokens, &["FROM"]),
+        _ => vec![],
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::parser::{parse_query, parse_type, replace_variables_in_query};
+    use std::collections::HashMap;
+
+    use crate::parser::{
+        get_in_list_variables, get_tables, get_written_tables, parse_query, parse_type,
+        replace_identifier_placeholders, replace_variables_in_query, resolve_optional_blocks,
+        validate_query,
+    };
 
     #[test]
     fn test() {
@@ -240,7 +526,7 @@ mod tests {
         let (_, parmas) = parse_query(query).unwrap();
 
         assert_eq!(
-            replace_variables_in_query(query, parmas),
+            replace_variables_in_query(query, parmas, &[]),
             String::from("SELECT * FROM todos where user_id=?;")
         )
     }
@@ -259,8 +545,108 @@ mod tests {
         let (_, parmas) = parse_query(query).unwrap();
 
         assert_eq!(
-            replace_variables_in_query(query, parmas),
+            replace_variables_in_query(query, parmas, &[]),
             String::from("INSERT INTO todos VALUES (?, ?, ?);")
         )
     }
+
+    #[test]
+    fn test5() {
+        let query = "SELECT t.id, u.email FROM todos t INNER JOIN `users` u ON u.id=t.user_id WHERE t.id=${id};";
+
+        assert_eq!(get_tables(query), vec!["todos", "users"]);
+        assert_eq!(get_written_tables(query), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test6() {
+        assert_eq!(
+            get_written_tables("INSERT INTO todos(title) VALUES (${title});"),
+            vec!["todos"]
+        );
+        assert_eq!(
+            get_written_tables("update Todos SET done=1 WHERE id=${id}"),
+            vec!["todos"]
+        );
+        assert_eq!(
+            get_written_tables("DELETE FROM main.todos WHERE id=${id}"),
+            vec!["todos"]
+        );
+    }
+
+    #[test]
+    fn test7() {
+        let query = "SELECT * FROM todos WHERE id IN (${ids}) AND tag NOT in(${tags}) AND user_id=${ids} AND done IN (${done}, 1)";
+        let (_, params) = parse_query(query).unwrap();
+
+        assert_eq!(
+            get_in_list_variables(query, &params),
+            vec![true, true, false, false]
+        );
+        assert_eq!(
+            replace_variables_in_query(query, params, &[Some(3), Some(0), None, None]),
+            "SELECT * FROM todos WHERE id IN (?, ?, ?) AND tag NOT in(SELECT NULL WHERE 1=0) AND user_id=? AND done IN (?, 1)"
+        );
+        assert_eq!(
+            replace_variables_in_query("SELECT * FROM t WHERE a IN (${a})", vec!["a"], &[Some(0)]),
+            "SELECT * FROM t WHERE a IN (NULL)"
+        );
+    }
+
+    #[test]
+    fn test8() {
+        let query = "SELECT * FROM todos WHERE user_id=${.USER_ID}[[ AND status=${status}]][[ AND title LIKE ${title}]] ORDER BY id";
+
+        assert_eq!(
+            resolve_optional_blocks(query, |v| v == "status").unwrap(),
+            "SELECT * FROM todos WHERE user_id=${.USER_ID} AND status=${status} ORDER BY id"
+        );
+        assert_eq!(
+            resolve_optional_blocks(query, |_| false).unwrap(),
+            "SELECT * FROM todos WHERE user_id=${.USER_ID} ORDER BY id"
+        );
+    }
+
+    #[test]
+    fn test9() {
+        assert!(validate_query("SELECT * FROM todos[[ WHERE id=${id}]]").is_ok());
+        assert!(validate_query("SELECT * FROM todos[[ WHERE id=${id}").is_err());
+        assert!(validate_query("SELECT * FROM todos WHERE id=${id}]]").is_err());
+        assert!(validate_query("SELECT * FROM todos[[ WHERE done=1]]").is_err());
+        assert!(validate_query("SELECT * FROM todos[[ WHERE [[id=${id}]] ]]").is_err());
+    }
+
+    #[test]
+    fn test10() {
+        let query =
+            "SELECT * FROM todos[[ WHERE done=${done}]] ORDER BY ${@sort in created_at, title}";
+        let values = |v: &str| HashMap::from([("sort".to_string(), v.to_string())]);
+
+        assert!(validate_query(query).is_ok());
+        assert!(validate_query("SELECT * FROM todos ORDER BY ${@sort}").is_err());
+        assert!(validate_query("SELECT * FROM todos ORDER BY ${@sort in a;b}").is_err());
+
+        assert_eq!(
+            resolve_optional_blocks("SELECT 1[[ ORDER BY ${@sort in id}]]", |v| v == "sort")
+                .unwrap(),
+            "SELECT 1 ORDER BY ${@sort in id}"
+        );
+        assert_eq!(
+            replace_identifier_placeholders(query, &HashMap::new()).unwrap(),
+            "SELECT * FROM todos[[ WHERE done=${done}]] ORDER BY created_at"
+        );
+        assert_eq!(
+            replace_identifier_placeholders(query, &values("title desc")).unwrap(),
+            "SELECT * FROM todos[[ WHERE done=${done}]] ORDER BY title DESC"
+        );
+
+        for value in [
+            "id",
+            "title; DROP TABLE todos",
+            "title desc, id",
+            "title sideways",
+        ] {
+            assert!(replace_identifier_placeholders(query, &values(value)).is_err());
+        }
+    }
 }
@@ -1,102 +1,160 @@
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+
 use crate::database::model::ColType;
 
 use super::{
-    model::{Migration, MigrationDown, MigrationName, MigrationUp},
+    migration_file::migration_version,
+    model::{
+        Migration, MigrationChecksum, MigrationDetail, MigrationDown, MigrationName, MigrationUp,
+    },
     Model,
 };
 
+pub fn migration_checksum(up_query: &str, down_query: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(up_query);
+    hasher.update([0]);
+    hasher.update(down_query);
+
+    format!("{:x}", hasher.finalize())
+}
+
 impl Model {
     pub async fn get_all_migrations(&self) -> Result<Vec<MigrationName>, String> {
-        let query = "SELECT id, name, executed FROM migrations ORDER BY id";
+        let query = "SELECT id, name, executed, applied_at FROM migrations";
 
-        self.conn
+        let mut migrations = self
+            .conn
             .as_ref()
             .unwrap()
-            .query_all_with_type::<MigrationName>(query)
-            .await
+            .query_all_with_type::<MigrationName>(query, vec![])
+            .await?;
+
+        migrations.sort_by_key(|m| (migration_version(&m.name, m.id), m.id));
+
+        Ok(migrations)
     }
 
     pub async fn get_migration_name_by_id(
         &self,
         migration_id: i64,
     ) -> Result<MigrationName, String> {
-        let query = format!(
-            "SELECT id, name, executed FROM migrations WHERE id={}",
-            migration_id
-        );
+        let query = "SELECT id, name, executed, applied_at FROM migrations WHERE id=?";
+        let args = vec![ColType::Integer(Some(migration_id))];
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_one_with_type::<MigrationName>(&query)
+            .query_one_with_type::<MigrationName>(query, args)
             .await
     }
 
-    pub async fn get_up_migration_by_id(&self, migration_id: i64) -> Result<MigrationUp, String> {
-        let query = format!(
-            "SELECT id, up_query FROM migrations WHERE id={}",
-            migration_id
-        );
+    pub async fn get_all_migration_details(&self) -> Result<Vec<MigrationDetail>, String> {
+        let query = "SELECT id, name, up_query, down_query, executed FROM migrations ORDER BY id";
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_one_with_type::<MigrationUp>(&query)
+            .query_all_with_type::<MigrationDetail>(query, vec![])
             .await
     }
 
-    pub async fn get_up_migrations(&self) -> Result<Vec<MigrationUp>, String> {
-        let query = "SELECT id, up_query FROM migrations WHERE executed=0 ORDER BY id ASC";
+    pub async fn rename_migration(&self, migration_id: i64, name: String) -> Result<u64, String> {
+        let query = "UPDATE migrations SET name=? WHERE id=?";
+        let args = vec![
+            ColType::String(Some(name)),
+            ColType::Integer(Some(migration_id)),
+        ];
+
+        self.conn.as_ref().unwrap().execute(query, args).await
+    }
+
+    pub async fn get_up_migration_by_id(&self, migration_id: i64) -> Result<MigrationUp, String> {
+        let query = "SELECT id, up_query FROM migrations WHERE id=?";
+        let args = vec![ColType::Integer(Some(migration_id))];
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_all_with_type::<MigrationUp>(query)
+            .query_one_with_type::<MigrationUp>(query, args)
             .await
     }
 
+    pub async fn get_up_migrations(&self) -> Result<Vec<Migration>, String> {
+        let query = "SELECT id, name, up_query, down_query FROM migrations WHERE executed=0";
+
+        let mut migrations = self
+            .conn
+            .as_ref()
+            .unwrap()
+            .query_all_with_type::<Migration>(query, vec![])
+            .await?;
+
+        migrations.sort_by_key(|m| (migration_version(&m.name, m.id), m.id));
+
+        Ok(migrations)
+    }
+
     pub async fn get_down_migration_by_id(
         &self,
         migration_id: i64,
     ) -> Result<MigrationDown, String> {
-        let query = format!(
-            "SELECT id, down_query FROM migrations WHERE id={}",
-            migration_id
-        );
+        let query = "SELECT id, down_query FROM migrations WHERE id=?";
+        let args = vec![ColType::Integer(Some(migration_id))];
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_one_with_type::<MigrationDown>(&query)
+            .query_one_with_type::<MigrationDown>(query, args)
             .await
     }
 
-    pub async fn get_down_migrations(&self) -> Result<Vec<MigrationDown>, String> {
-        let query = "SELECT id, down_query FROM migrations WHERE executed=1 ORDER BY id DESC";
+    pub async fn get_down_migrations(&self) -> Result<Vec<Migration>, String> {
+        let query = "SELECT id, name, up_query, down_query FROM migrations WHERE executed=1";
 
-        self.conn
+        let mut migrations = self
+            .conn
             .as_ref()
             .unwrap()
-            .query_all_with_type::<MigrationDown>(query)
-            .await
+            .query_all_with_type::<Migration>(query, vec![])
+            .await?;
+
+        migrations.sort_by_key(|m| std::cmp::Reverse((migration_version(&m.name, m.id), m.id)));
+
+        Ok(migrations)
+    }
+
+    pub async fn get_drifted_migrations(&self) -> Result<Vec<i64>, String> {
+        let query = "SELECT id, up_query, down_query, checksum FROM migrations WHERE executed=1 AND checksum<>''";
+
+        let migrations = self
+            .conn
+            .as_ref()
+            .unwrap()
+            .query_all_with_type::<MigrationChecksum>(query, vec![])
+            .await?;
+
+        Ok(migrations
+            .into_iter()
+            .filter(|m| migration_checksum(&m.up_query, &m.down_query) != m.checksum)
+            .map(|m| m.id)
+            .collect())
     }
 
     pub async fn add_new_migration(&self, name: String) -> Result<i64, String> {
-        let query = "INSERT INTO migrations(name) VALUES (?) RETURNING id";
+        let query = "INSERT INTO migrations(name) VALUES (?)";
         let args = vec![ColType::String(Some(name))];
 
-        let row = self.conn.as_ref().unwrap().query_one(query, args).await;
-
-        match row {
-            Ok(r) => Ok(r.get::<i64>(0).unwrap()),
-            Err(e) => Err(e),
-        }
+        self.conn.as_ref().unwrap().insert(query, args).await
     }
 
     pub async fn edit_up_migration(&self, migration: Migration) -> Result<u64, String> {
+        self.ensure_migration_editable(migration.id).await?;
+
         let query = "UPDATE migrations
                   SET up_query=?
-                  WHERE id=?";
+                  WHERE id=? AND executed=0";
         let args = vec![
             ColType::String(Some(migration.up_query)),
             ColType::Integer(Some(migration.id)),
@@ -106,7 +164,9 @@ impl Model {
     }
 
     pub async fn edit_down_migration(&self, migration: Migration) -> Result<u64, String> {
-        let query = "UPDATE migrations SET down_query=? WHERE id=?";
+        self.ensure_migration_editable(migration.id).await?;
+
+        let query = "UPDATE migrations SET down_query=? WHERE id=? AND executed=0";
         let args = vec![
             ColType::String(Some(migration.down_query)),
             ColType::Integer(Some(migration.id)),
@@ -115,22 +175,57 @@ impl Model {
         self.conn.as_ref().unwrap().execute(query, args).await
     }
 
-    pub async fn run_migration(&self, query: String) -> Result<u64, String> {
-        self.conn.as_ref().unwrap().execute(&query, vec![]).await
+    async fn ensure_migration_editable(&self, migration_id: i64) -> Result<(), String> {
+        let migration = self.get_migration_name_by_id(migration_id).await?;
+
+        if migration.executed {
+            return Err(format!(
+                "migration {} is already applied, run down before editing it",
+                migration.name
+            ));
+        }
+        Ok(())
     }
 
-    pub async fn update_executed_migration(
-        &self,
-        migration_id: i64,
-        executed: bool,
-    ) -> Result<u64, String> {
-        let query = "UPDATE migrations SET executed=? WHERE id=?";
-        let args = vec![
-            ColType::Bool(Some(executed)),
-            ColType::Integer(Some(migration_id)),
+    // mysql commits DDL implicitly, there only the bookkeeping update is transactional
+    pub async fn apply_migration(&self, migration: &Migration) -> Result<u64, String> {
+        let queries = vec![
+            (migration.up_query.clone(), vec![]),
+            (
+                "UPDATE migrations SET executed=1, checksum=?, applied_at=? WHERE id=?".to_string(),
+                vec![
+                    ColType::String(Some(migration_checksum(
+                        &migration.up_query,
+                        &migration.down_query,
+                    ))),
+                    ColType::Integer(Some(Utc::now().timestamp())),
+                    ColType::Integer(Some(migration.id)),
+                ],
+            ),
         ];
 
-        self.conn.as_ref().unwrap().execute(query, args).await
+        self.conn
+            .as_ref()
+            .unwrap()
+            .execute_in_transaction(queries)
+            .await
+    }
+
+    pub async fn revert_migration(&self, migration: &Migration) -> Result<u64, String> {
+        let queries = vec![
+            (migration.down_query.clone(), vec![]),
+            (
+                "UPDATE migrations SET executed=0, checksum='', applied_at=NULL WHERE id=?"
+                    .to_string(),
+                vec![ColType::Integer(Some(migration.id))],
+            ),
+        ];
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .execute_in_transaction(queries)
+            .await
     }
 
     pub async fn delete_migration(&self, migration_id: i64) -> Result<u64, String> {
@@ -140,3 +235,22 @@ impl Model {
         self.conn.as_ref().unwrap().execute(query, args).await
     }
 }
+
+#[test]
+fn test_migration_checksum() {
+    let checksum = migration_checksum("CREATE TABLE todos (id INTEGER);", "DROP TABLE todos;");
+
+    assert_eq!(checksum.len(), 64);
+    assert_eq!(
+        checksum,
+        migration_checksum("CREATE TABLE todos (id INTEGER);", "DROP TABLE todos;")
+    );
+    assert_ne!(
+        checksum,
+        migration_checksum(
+            "CREATE TABLE todos (id INTEGER, done INTEGER);",
+            "DROP TABLE todos;"
+        )
+    );
+    assert_ne!(migration_checksum("ab", "c"), migration_checksum("a", "bc"));
+}
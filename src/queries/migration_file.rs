@@ -0,0 +1,247 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use super::{model::Migration, Model};
+
+pub const MIGRATION_DIR: &str = "migrations";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationFile {
+    pub version: i64,
+    pub name: String,
+    pub up_query: String,
+    pub down_query: String,
+}
+
+impl MigrationFile {
+    pub fn stem(&self) -> String {
+        format!("{:04}_{}", self.version, self.name)
+    }
+}
+
+pub fn parse_migration_file_name(file_name: &str) -> Option<(i64, String, bool)> {
+    let (stem, is_up) = match file_name.strip_suffix(".up.sql") {
+        Some(stem) => (stem, true),
+        None => (file_name.strip_suffix(".down.sql")?, false),
+    };
+
+    let (version, name) = stem.split_once('_')?;
+    if name.is_empty() || !version.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    Some((version.parse().ok()?, name.to_string(), is_up))
+}
+
+// rows made in the tui have no version in their name and sort by id
+pub fn migration_version(name: &str, id: i64) -> i64 {
+    parse_migration_file_name(&format!("{}.up.sql", name))
+        .map(|(version, _, _)| version)
+        .unwrap_or(id)
+}
+
+pub fn slugify(name: &str) -> String {
+    let slug = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+
+    let slug = slug
+        .split('_')
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<&str>>()
+        .join("_");
+
+    match slug.is_empty() {
+        true => "migration".to_string(),
+        false => slug,
+    }
+}
+
+pub fn read_migration_files(dir: &str) -> Result<Vec<MigrationFile>, String> {
+    if !Path::new(dir).is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut files: BTreeMap<(i64, String), MigrationFile> = BTreeMap::new();
+
+    for entry in fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        let file_name = path.file_name().and_then(|f| f.to_str()).unwrap_or("");
+
+        let (version, name, is_up) = match parse_migration_file_name(file_name) {
+            Some(p) => p,
+            None => continue,
+        };
+
+        let content = fs::read_to_string(&path).map_err(|e| format!("{}: {}", file_name, e))?;
+
+        let file = files
+            .entry((version, name.clone()))
+            .or_insert(MigrationFile {
+                version,
+                name,
+                up_query: "".to_string(),
+                down_query: "".to_string(),
+            });
+
+        match is_up {
+            true => file.up_query = content,
+            false => file.down_query = content,
+        }
+    }
+
+    let files = files.into_values().collect::<Vec<MigrationFile>>();
+
+    for pair in files.windows(2) {
+        if pair[0].version == pair[1].version {
+            return Err(format!(
+                "duplicate migration version {:04}: {} and {}",
+                pair[0].version, pair[0].name, pair[1].name
+            ));
+        }
+    }
+
+    Ok(files)
+}
+
+pub fn write_migration_file(dir: &str, file: &MigrationFile) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    let up_path = Path::new(dir).join(format!("{}.up.sql", file.stem()));
+    let down_path = Path::new(dir).join(format!("{}.down.sql", file.stem()));
+
+    fs::write(up_path, &file.up_query).map_err(|e| e.to_string())?;
+    fs::write(down_path, &file.down_query).map_err(|e| e.to_string())
+}
+
+pub struct SyncReport {
+    pub added: Vec<(i64, String)>,
+    pub updated: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl Model {
+    pub async fn sync_migration_files(&self, dir: &str) -> Result<SyncReport, String> {
+        let files = read_migration_files(dir)?;
+        let migrations = self.get_all_migration_details().await?;
+
+        let mut report = SyncReport {
+            added: vec![],
+            updated: vec![],
+            warnings: vec![],
+        };
+
+        for file in files {
+            let stem = file.stem();
+
+            match migrations.iter().find(|m| m.name == stem) {
+                Some(m) if m.up_query == file.up_query && m.down_query == file.down_query => {}
+                Some(m) if m.executed => report.warnings.push(format!(
+                    "{} is applied but its file changed, run down before changing it",
+                    stem
+                )),
+                Some(m) => {
+                    self.set_migration_queries(m.id, &file).await?;
+                    report.updated.push(stem);
+                }
+                None => {
+                    let id = self.add_new_migration(stem.clone()).await?;
+                    self.set_migration_queries(id, &file).await?;
+                    report.added.push((id, stem));
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    async fn set_migration_queries(
+        &self,
+        migration_id: i64,
+        file: &MigrationFile,
+    ) -> Result<(), String> {
+        self.edit_up_migration(Migration {
+            id: migration_id,
+            name: "".to_string(),
+            up_query: file.up_query.clone(),
+            down_query: "".to_string(),
+        })
+        .await?;
+
+        self.edit_down_migration(Migration {
+            id: migration_id,
+            name: "".to_string(),
+            up_query: "".to_string(),
+            down_query: file.down_query.clone(),
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn export_migration_files(&self, dir: &str) -> Result<Vec<String>, String> {
+        let migrations = self.get_all_migration_details().await?;
+        let mut exported = vec![];
+
+        for m in migrations {
+            let file = match parse_migration_file_name(&format!("{}.up.sql", m.name)) {
+                Some((version, name, _)) => MigrationFile {
+                    version,
+                    name,
+                    up_query: m.up_query,
+                    down_query: m.down_query,
+                },
+                None => MigrationFile {
+                    version: m.id,
+                    name: slugify(&m.name),
+                    up_query: m.up_query,
+                    down_query: m.down_query,
+                },
+            };
+
+            write_migration_file(dir, &file)?;
+
+            // link the row to its file so the next sync does not add it again
+            if file.stem() != m.name {
+                self.rename_migration(m.id, file.stem()).await?;
+            }
+            exported.push(file.stem());
+        }
+
+        Ok(exported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{migration_version, parse_migration_file_name, slugify};
+
+    #[test]
+    fn test1() {
+        assert_eq!(
+            parse_migration_file_name("0001_create_todos.up.sql"),
+            Some((1, "create_todos".to_string(), true))
+        );
+        assert_eq!(
+            parse_migration_file_name("0012_add_done.down.sql"),
+            Some((12, "add_done".to_string(), false))
+        );
+        assert_eq!(parse_migration_file_name("0001_create_todos.sql"), None);
+        assert_eq!(parse_migration_file_name("abc_create_todos.up.sql"), None);
+        assert_eq!(parse_migration_file_name("0001_.up.sql"), None);
+    }
+
+    #[test]
+    fn test2() {
+        assert_eq!(slugify("Create Todos Table!"), "create_todos_table");
+        assert_eq!(slugify("add-done  column"), "add_done_column");
+        assert_eq!(slugify("!!"), "migration");
+    }
+
+    #[test]
+    fn test3() {
+        assert_eq!(migration_version("0007_add_done", 2), 7);
+        assert_eq!(migration_version("add done", 3), 3);
+    }
+}
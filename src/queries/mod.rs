@@ -1,14 +1,37 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
 use axum_server::Handle;
 use jfs::{Config, Store};
+use tokio::sync::broadcast;
 
-use crate::{database::Conn, parser::sql_parser::Trie, server::utils::Utils};
+use crate::{
+    database::Conn,
+    parser::sql_parser::Trie,
+    server::{cache::ResponseCache, metrics::Metrics, tus::UploadLocks, utils::Utils},
+};
 
-use self::model::{Offset, Temp};
+use self::{
+    model::{Offset, Temp},
+    route::RouteTable,
+};
 mod migration;
+pub mod migration_file;
 pub mod model;
+#[cfg(test)]
+mod mysql_tests;
 mod query;
+pub mod rest;
 mod role;
+mod route;
+mod schedule;
+pub mod schema;
+pub mod schema_diff;
 mod storage;
+pub mod table;
+mod trigger;
 mod user;
 mod webhook;
 
@@ -21,16 +44,27 @@ pub struct Model {
     pub utils: Utils,
     pub jsondb: Store,
     pub trie: Trie,
+    pub http: reqwest::Client,
+    pub changes: broadcast::Sender<Vec<String>>,
+    pub metrics: Arc<Metrics>,
+    pub routes: Arc<RwLock<RouteTable>>,
+    pub cache: Arc<ResponseCache>,
+    pub tus_locks: Arc<UploadLocks>,
 }
 
 impl Model {
     pub fn default() -> Self {
+        Self::with_config_path("config")
+    }
+
+    pub fn with_config_path(config_path: &str) -> Self {
         Self {
             conn: None,
             handle: None,
             offset: Offset {
                 user: 0,
                 storage: 0,
+                table: 0,
             },
             temp: Temp {
                 query_access: vec![],
@@ -46,14 +80,20 @@ impl Model {
                 editor_popup_active: false,
                 webhook_query: vec![],
                 webhook_query_update: false,
+                query_pagination: None,
+                query_shape: None,
+                query_cache: None,
             },
             utils: Utils {
                 auth_secret: String::from("secret"),
                 storage_secret: String::from("secret"),
                 ips: vec![],
+                max_image_dimension: 2048,
+                max_in_list: 1000,
+                metrics_token: "".to_string(),
             },
             jsondb: jfs::Store::new_with_cfg(
-                "config",
+                config_path,
                 Config {
                     pretty: true,
                     indent: 4,
@@ -62,6 +102,15 @@ impl Model {
             )
             .unwrap(),
             trie: Trie::new(),
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap(),
+            changes: broadcast::channel(256).0,
+            metrics: Arc::new(Metrics::default()),
+            routes: Arc::new(RwLock::new(RouteTable::default())),
+            cache: Arc::new(ResponseCache::default()),
+            tus_locks: Arc::new(UploadLocks::default()),
         }
     }
 }
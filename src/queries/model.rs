@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 pub struct Offset {
     pub user: i64,
     pub storage: i64,
+    pub table: i64,
 }
 
 #[derive(Debug, Clone)]
@@ -27,6 +28,9 @@ pub struct Temp {
     pub editor_popup_active: bool,
     pub webhook_query: Vec<WebhookQuery>,
     pub webhook_query_update: bool,
+    pub query_pagination: Option<QueryPagination>,
+    pub query_shape: Option<QueryShape>,
+    pub query_cache: Option<QueryCache>,
 }
 
 #[derive(Debug, Clone, sqlx::FromRow)]
@@ -70,6 +74,24 @@ pub struct MigrationName {
     pub id: i64,
     pub name: String,
     pub executed: bool,
+    pub applied_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct MigrationDetail {
+    pub id: i64,
+    pub name: String,
+    pub up_query: String,
+    pub down_query: String,
+    pub executed: bool,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct MigrationChecksum {
+    pub id: i64,
+    pub up_query: String,
+    pub down_query: String,
+    pub checksum: String,
 }
 
 #[derive(Debug, Clone, sqlx::FromRow)]
@@ -135,6 +157,72 @@ pub struct QueryString {
     pub query: String,
 }
 
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct QueryPagination {
+    pub pagination: String,
+    pub page_size: i64,
+    pub cursor_column: String,
+    pub with_total: bool,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct QueryShape {
+    pub shape: String,
+    pub group_by: String,
+    pub max_rows: i64,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct QueryCache {
+    pub cache_ttl: i64,
+    pub cache_params: String,
+    pub cache_per_user: bool,
+    pub cache_max_entries: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub id: i64,
+    pub name: String,
+    pub exec_type: String,
+    pub query: String,
+    pub params: Result<Vec<String>, String>,
+    pub tables: Vec<String>,
+    pub role_access: Vec<RoleAccess>,
+    pub webhooks: Vec<Webhook>,
+    pub pagination: QueryPagination,
+    pub shape: QueryShape,
+    pub cache: QueryCache,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RouteRow {
+    pub id: i64,
+    pub name: String,
+    pub exec_type: String,
+    pub query: String,
+    #[sqlx(flatten)]
+    pub pagination: QueryPagination,
+    #[sqlx(flatten)]
+    pub shape: QueryShape,
+    #[sqlx(flatten)]
+    pub cache: QueryCache,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RouteRoleAccess {
+    pub query_id: i64,
+    #[sqlx(flatten)]
+    pub access: RoleAccess,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RouteWebhook {
+    pub query_id: i64,
+    #[sqlx(flatten)]
+    pub webhook: Webhook,
+}
+
 #[derive(Debug, Clone, sqlx::FromRow)]
 pub struct WebhookName {
     pub id: i64,
@@ -150,6 +238,33 @@ pub struct Webhook {
     pub url: String,
     pub args: String,
     pub is_returned: bool,
+    pub return_mode: String,
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct WebhookOutbox {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub method: String,
+    pub url: String,
+    pub payload: String,
+    pub attempts: i64,
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub method: String,
+    pub url: String,
+    pub payload: String,
+    pub status_code: Option<i64>,
+    pub latency_ms: i64,
+    pub response: String,
+    pub error: String,
+    pub created_at: i64,
 }
 
 #[derive(Debug, Clone, sqlx::FromRow)]
@@ -159,6 +274,84 @@ pub struct WebhookQuery {
     pub is_connected: bool,
 }
 
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ScheduleName {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Schedule {
+    pub id: i64,
+    pub name: String,
+    pub cron: String,
+    pub target_type: String,
+    pub target_id: i64,
+    pub args: String,
+    pub enabled: bool,
+    pub next_run_at: i64,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ScheduleRun {
+    pub id: i64,
+    pub schedule_id: i64,
+    pub started_at: i64,
+    pub duration_ms: i64,
+    pub status: String,
+    pub output: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TableName {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct IndexColumn {
+    pub name: String,
+    pub is_unique: bool,
+    pub column_name: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ForeignKeyColumn {
+    pub name: String,
+    pub column_name: String,
+    pub references_table: String,
+    pub foreign_column: String,
+    pub on_delete: Option<String>,
+    pub on_update: Option<String>,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SqlText {
+    pub sql: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TableTrigger {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub table_name: String,
+    pub event: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct RestTable {
+    pub id: i64,
+    pub table_name: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ChangeLog {
+    pub id: i64,
+    pub table_name: String,
+    pub event: String,
+    pub old_row: Option<String>,
+    pub new_row: Option<String>,
+}
+
 #[derive(Debug, Clone, sqlx::FromRow)]
 pub struct DefaultRole {
     pub role: Option<String>,
@@ -171,9 +364,20 @@ pub struct Storage {
     pub unique_name: String,
 }
 
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TusUpload {
+    pub id: String,
+    pub file_name: String,
+    pub upload_length: i64,
+    pub upload_offset: i64,
+    pub uploaded_by: i64,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Setup {
     pub dbpath: String,
+    #[serde(default)]
+    pub dbtype: String,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -181,4 +385,10 @@ pub struct Config {
     pub ips: String,
     pub auth_secret: String,
     pub storage_secret: String,
+    #[serde(default)]
+    pub max_image_dimension: String,
+    #[serde(default)]
+    pub max_in_list: String,
+    #[serde(default)]
+    pub metrics_token: String,
 }
@@ -3,7 +3,10 @@ use std::cmp;
 use crate::database::model::ColType;
 
 use super::{
-    model::{Query, QueryAccess, QueryName, QueryString, RoleAccess, WebhookQuery},
+    model::{
+        Query, QueryAccess, QueryCache, QueryName, QueryPagination, QueryShape, QueryString,
+        WebhookQuery,
+    },
     Model,
 };
 
@@ -14,106 +17,112 @@ impl Model {
         self.conn
             .as_ref()
             .unwrap()
-            .query_all_with_type::<QueryName>(query)
+            .query_all_with_type::<QueryName>(query, vec![])
             .await
     }
 
-    pub async fn get_all_role_access_by_query_id(
-        &self,
-        query_id: i64,
-    ) -> Result<Vec<RoleAccess>, String> {
-        let query = format!(
-            "SELECT role_id FROM role_access WHERE query_id={}",
-            query_id
-        );
+    pub async fn get_all_apis(&self) -> Result<Vec<Query>, String> {
+        let query = "SELECT id, name, exec_type FROM queries ORDER BY id";
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_all_with_type::<RoleAccess>(&query)
+            .query_all_with_type::<Query>(query, vec![])
             .await
     }
 
-    pub async fn get_all_apis(&self) -> Result<Vec<Query>, String> {
-        let query = "SELECT id, name, exec_type FROM queries ORDER BY id";
+    pub async fn get_query_cache_by_id(&self, query_id: i64) -> Result<QueryCache, String> {
+        let query = "SELECT cache_ttl, cache_params, cache_per_user, cache_max_entries FROM queries WHERE id=?";
+        let args = vec![ColType::Integer(Some(query_id))];
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_all_with_type::<Query>(query)
+            .query_one_with_type::<QueryCache>(query, args)
             .await
     }
 
     pub async fn get_query_by_id(&self, role_id: i64) -> Result<Query, String> {
-        let query = format!(
-            "SELECT id, name, exec_type 
+        let query = "SELECT id, name, exec_type 
              FROM queries 
-             WHERE id={}",
-            role_id
-        );
+             WHERE id=?";
+        let args = vec![ColType::Integer(Some(role_id))];
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_one_with_type::<Query>(&query)
+            .query_one_with_type::<Query>(query, args)
             .await
     }
 
     pub async fn get_query_string_by_id(&self, query_id: i64) -> Result<QueryString, String> {
-        let query = format!("SELECT query FROM queries WHERE id={}", query_id);
+        let query = "SELECT query FROM queries WHERE id=?";
+        let args = vec![ColType::Integer(Some(query_id))];
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_one_with_type::<QueryString>(&query)
+            .query_one_with_type::<QueryString>(query, args)
             .await
     }
 
-    pub async fn get_query_by_name(&self, query_name: &str) -> Result<Query, String> {
-        let query = format!(
-            "SELECT id, name, exec_type FROM queries WHERE name='{}'",
-            query_name
-        );
+    pub async fn get_query_pagination_by_id(
+        &self,
+        query_id: i64,
+    ) -> Result<QueryPagination, String> {
+        let query =
+            "SELECT pagination, page_size, cursor_column, with_total FROM queries WHERE id=?";
+        let args = vec![ColType::Integer(Some(query_id))];
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_one_with_type::<Query>(&query)
+            .query_one_with_type::<QueryPagination>(query, args)
+            .await
+    }
+
+    pub async fn get_query_shape_by_id(&self, query_id: i64) -> Result<QueryShape, String> {
+        let query = "SELECT shape, group_by, max_rows FROM queries WHERE id=?";
+        let args = vec![ColType::Integer(Some(query_id))];
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_one_with_type::<QueryShape>(query, args)
             .await
     }
 
     pub async fn add_new_query(&self, name: String) -> Result<i64, String> {
-        let query = "INSERT INTO queries(name) VALUES (?) RETURNING id";
+        let query = "INSERT INTO queries(name) VALUES (?)";
         let args = vec![ColType::String(Some(name))];
 
-        let row = self.conn.as_ref().unwrap().query_one(query, args).await;
+        let res = self.conn.as_ref().unwrap().insert(query, args).await?;
+        self.refresh_routes().await?;
 
-        match row {
-            Ok(r) => Ok(r.get::<i64>(0).unwrap()),
-            Err(e) => Err(e),
-        }
+        Ok(res)
     }
 
     pub async fn delete_query(&self, role_id: i64) -> Result<u64, String> {
         let query = "DELETE FROM queries WHERE id=?";
         let args = vec![ColType::Integer(Some(role_id))];
 
-        self.conn.as_ref().unwrap().execute(query, args).await
+        let res = self.conn.as_ref().unwrap().execute(query, args).await?;
+        self.refresh_routes().await?;
+
+        Ok(res)
     }
 
     pub async fn get_query_access_by_id(&self, query_id: i64) -> Result<Vec<QueryAccess>, String> {
-        let query = format!(
-            "SELECT id, 
+        let query = "SELECT id, 
              name, 
-             (SELECT TRUE FROM role_access WHERE role_id=roles.id AND query_id={}) AS has_access 
-             FROM roles",
-            query_id
-        );
+             (SELECT TRUE FROM role_access WHERE role_id=roles.id AND query_id=?) AS has_access 
+             FROM roles";
+        let args = vec![ColType::Integer(Some(query_id))];
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_all_with_type::<QueryAccess>(&query)
+            .query_all_with_type::<QueryAccess>(query, args)
             .await
     }
 
@@ -121,18 +130,16 @@ impl Model {
         &self,
         query_id: i64,
     ) -> Result<Vec<WebhookQuery>, String> {
-        let query = format!(
-            "SELECT id,
+        let query = "SELECT id,
              name, 
-             (SELECT TRUE FROM webhook_query WHERE webhook_id=webhooks.id AND query_id={}) AS is_connected 
-             FROM webhooks",
-            query_id
-        );
+             (SELECT TRUE FROM webhook_query WHERE webhook_id=webhooks.id AND query_id=?) AS is_connected 
+             FROM webhooks";
+        let args = vec![ColType::Integer(Some(query_id))];
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_all_with_type::<WebhookQuery>(&query)
+            .query_all_with_type::<WebhookQuery>(query, args)
             .await
     }
 
@@ -148,7 +155,10 @@ impl Model {
             ColType::Integer(Some(query_id)),
         ];
 
-        self.conn.as_ref().unwrap().execute(query, args).await
+        let res = self.conn.as_ref().unwrap().execute(query, args).await?;
+        self.refresh_routes().await?;
+
+        Ok(res)
     }
 
     pub async fn edit_query(&self, q: Query) -> Result<u64, String> {
@@ -160,7 +170,64 @@ impl Model {
             ColType::Integer(Some(q.id)),
         ];
 
-        self.conn.as_ref().unwrap().execute(query, args).await
+        let res = self.conn.as_ref().unwrap().execute(query, args).await?;
+        self.refresh_routes().await?;
+
+        Ok(res)
+    }
+
+    pub async fn edit_query_pagination(
+        &self,
+        query_id: i64,
+        p: QueryPagination,
+    ) -> Result<u64, String> {
+        let query = "UPDATE queries SET pagination=?, page_size=?, cursor_column=?, with_total=? WHERE id=?";
+
+        let args = vec![
+            ColType::String(Some(p.pagination)),
+            ColType::Integer(Some(p.page_size)),
+            ColType::String(Some(p.cursor_column)),
+            ColType::Bool(Some(p.with_total)),
+            ColType::Integer(Some(query_id)),
+        ];
+
+        let res = self.conn.as_ref().unwrap().execute(query, args).await?;
+        self.refresh_routes().await?;
+
+        Ok(res)
+    }
+
+    pub async fn edit_query_shape(&self, query_id: i64, shape: QueryShape) -> Result<u64, String> {
+        let query = "UPDATE queries SET shape=?, group_by=?, max_rows=? WHERE id=?";
+
+        let args = vec![
+            ColType::String(Some(shape.shape)),
+            ColType::String(Some(shape.group_by)),
+            ColType::Integer(Some(shape.max_rows)),
+            ColType::Integer(Some(query_id)),
+        ];
+
+        let res = self.conn.as_ref().unwrap().execute(query, args).await?;
+        self.refresh_routes().await?;
+
+        Ok(res)
+    }
+
+    pub async fn edit_query_cache(&self, query_id: i64, cache: QueryCache) -> Result<u64, String> {
+        let query = "UPDATE queries SET cache_ttl=?, cache_params=?, cache_per_user=?, cache_max_entries=? WHERE id=?";
+
+        let args = vec![
+            ColType::Integer(Some(cache.cache_ttl)),
+            ColType::String(Some(cache.cache_params)),
+            ColType::Bool(Some(cache.cache_per_user)),
+            ColType::Integer(Some(cache.cache_max_entries)),
+            ColType::Integer(Some(query_id)),
+        ];
+
+        let res = self.conn.as_ref().unwrap().execute(query, args).await?;
+        self.refresh_routes().await?;
+
+        Ok(res)
     }
 
     pub async fn edit_query_access(
@@ -209,6 +276,8 @@ impl Model {
             }
         }
 
+        self.refresh_routes().await?;
+
         Ok(1)
     }
 
@@ -258,11 +327,13 @@ impl Model {
             }
         }
 
+        self.refresh_routes().await?;
+
         Ok(1)
     }
 }
 
-fn remaining_ids(arr1: Vec<i64>, arr2: Vec<i64>) -> (Vec<i64>, Vec<i64>) {
+pub(super) fn remaining_ids(arr1: Vec<i64>, arr2: Vec<i64>) -> (Vec<i64>, Vec<i64>) {
     let mut i = 0;
     let mut j = 0;
 
@@ -0,0 +1,333 @@
+use crate::database::model::{ColInfo, ColType, DbType};
+
+use super::{
+    model::{Query, QueryAccess, RestTable, RoleAccess},
+    query::remaining_ids,
+    schema::is_valid_identifier,
+    Model,
+};
+
+pub const REST_OPERATIONS: [&str; 5] = ["list", "get", "insert", "update", "delete"];
+
+impl Model {
+    pub async fn get_all_rest_tables(&self) -> Result<Vec<RestTable>, String> {
+        let query = "SELECT id, table_name FROM rest_tables ORDER BY id";
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_all_with_type::<RestTable>(query, vec![])
+            .await
+    }
+
+    pub async fn get_rest_table_by_name(&self, table_name: &str) -> Result<RestTable, String> {
+        if !is_valid_identifier(table_name) {
+            return Err(format!("invalid table name: {}", table_name));
+        }
+
+        let query = "SELECT id, table_name FROM rest_tables WHERE table_name=?";
+        let args = vec![ColType::String(Some(table_name.to_string()))];
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_one_with_type::<RestTable>(query, args)
+            .await
+    }
+
+    pub async fn add_rest_table(&self, table_name: String) -> Result<i64, String> {
+        if !is_valid_identifier(&table_name) {
+            return Err(format!("invalid table name: {}", table_name));
+        }
+
+        let query = "INSERT INTO rest_tables(table_name) VALUES (?)";
+        let args = vec![ColType::String(Some(table_name))];
+
+        self.conn.as_ref().unwrap().insert(query, args).await
+    }
+
+    pub async fn delete_rest_table(&self, rest_table_id: i64) -> Result<u64, String> {
+        let query = "DELETE FROM rest_tables WHERE id=?";
+        let args = vec![ColType::Integer(Some(rest_table_id))];
+
+        self.conn.as_ref().unwrap().execute(query, args).await
+    }
+
+    pub async fn get_all_role_access_by_rest_table(
+        &self,
+        rest_table_id: i64,
+        operation: &str,
+    ) -> Result<Vec<RoleAccess>, String> {
+        let query = "SELECT role_id FROM rest_access WHERE rest_table_id=? AND operation=?";
+        let args = vec![
+            ColType::Integer(Some(rest_table_id)),
+            ColType::String(Some(operation.to_string())),
+        ];
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_all_with_type::<RoleAccess>(query, args)
+            .await
+    }
+
+    pub async fn get_rest_access_by_id(
+        &self,
+        rest_table_id: i64,
+        operation: &str,
+    ) -> Result<Vec<QueryAccess>, String> {
+        let query = "SELECT id,
+             name,
+             (SELECT TRUE FROM rest_access WHERE role_id=roles.id AND rest_table_id=? AND operation=?) AS has_access
+             FROM roles";
+        let args = vec![
+            ColType::Integer(Some(rest_table_id)),
+            ColType::String(Some(operation.to_string())),
+        ];
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_all_with_type::<QueryAccess>(query, args)
+            .await
+    }
+
+    pub async fn edit_rest_access(
+        &self,
+        rest_table_id: i64,
+        operation: &str,
+        rest_access: Vec<QueryAccess>,
+    ) -> Result<u64, String> {
+        let old_access = self.get_rest_access_by_id(rest_table_id, operation).await?;
+
+        let (insertable, deletable) = remaining_ids(
+            old_access
+                .iter()
+                .filter(|q| q.has_access)
+                .map(|q| q.id)
+                .collect(),
+            rest_access
+                .iter()
+                .filter(|q| q.has_access)
+                .map(|q| q.id)
+                .collect(),
+        );
+
+        for r in insertable {
+            let query =
+                "INSERT INTO rest_access(role_id, rest_table_id, operation) VALUES(?, ?, ?)";
+            let args = vec![
+                ColType::Integer(Some(r)),
+                ColType::Integer(Some(rest_table_id)),
+                ColType::String(Some(operation.to_string())),
+            ];
+
+            self.conn.as_ref().unwrap().execute(query, args).await?;
+        }
+
+        for r in deletable {
+            let query =
+                "DELETE FROM rest_access WHERE role_id=? AND rest_table_id=? AND operation=?";
+            let args = vec![
+                ColType::Integer(Some(r)),
+                ColType::Integer(Some(rest_table_id)),
+                ColType::String(Some(operation.to_string())),
+            ];
+
+            self.conn.as_ref().unwrap().execute(query, args).await?;
+        }
+
+        Ok(1)
+    }
+
+    // copies the generated endpoints into editable queries rows, existing names are left alone
+    pub async fn materialize_rest_table(
+        &self,
+        rest_table: &RestTable,
+    ) -> Result<Vec<Query>, String> {
+        let columns = self.get_table_columns(&rest_table.table_name).await?;
+        let existing = self.get_all_queries().await?;
+
+        let mut queries = vec![];
+
+        for (operation, exec_type, query_string) in generate_rest_queries(
+            &rest_table.table_name,
+            &columns,
+            &self.conn.as_ref().unwrap().dbtype,
+        ) {
+            let name = format!("{}/{}", rest_table.table_name, operation);
+            if existing.iter().any(|q| q.name == name) {
+                continue;
+            }
+
+            let id = self.add_new_query(name.clone()).await?;
+            let query = Query {
+                id,
+                name,
+                exec_type: exec_type.to_string(),
+            };
+            self.edit_query(query.clone()).await?;
+            self.edit_query_string(id, query_string).await?;
+
+            let access = self.get_rest_access_by_id(rest_table.id, operation).await?;
+            self.edit_query_access(id, access).await?;
+
+            queries.push(query);
+        }
+
+        Ok(queries)
+    }
+}
+
+pub fn generate_rest_queries(
+    table_name: &str,
+    columns: &[ColInfo],
+    dbtype: &DbType,
+) -> Vec<(&'static str, &'static str, String)> {
+    let returning = match dbtype {
+        DbType::Sqlite => " RETURNING *",
+        DbType::Mysql => "",
+    };
+    let pk = columns.iter().filter(|c| c.pk).collect::<Vec<&ColInfo>>();
+
+    // a single integer primary key is generated by the database
+    let insertable = columns
+        .iter()
+        .filter(|c| !(c.pk && pk.len() == 1 && c.ctype.to_uppercase().contains("INT")))
+        .collect::<Vec<&ColInfo>>();
+
+    let mut queries = vec![("list", "get", format!("SELECT * FROM {}", table_name))];
+
+    if !insertable.is_empty() {
+        queries.push((
+            "insert",
+            "post",
+            format!(
+                "INSERT INTO {}({}) VALUES ({}){}",
+                table_name,
+                insertable
+                    .iter()
+                    .map(|c| c.name.clone())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                insertable
+                    .iter()
+                    .map(|c| format!("${{{}}}", c.name))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                returning
+            ),
+        ));
+    }
+
+    if pk.len() != 1 {
+        return queries;
+    }
+
+    let pk = &pk[0].name;
+    let filter = format!("{}=${{{}}}", pk, pk);
+
+    queries.push((
+        "get",
+        "get",
+        format!("SELECT * FROM {} WHERE {}", table_name, filter),
+    ));
+
+    let updatable = columns.iter().filter(|c| !c.pk).collect::<Vec<&ColInfo>>();
+    if !updatable.is_empty() {
+        queries.push((
+            "update",
+            "put",
+            format!(
+                "UPDATE {} SET {} WHERE {}{}",
+                table_name,
+                updatable
+                    .iter()
+                    .map(|c| format!("{}=${{{}}}", c.name, c.name))
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                filter,
+                returning
+            ),
+        ));
+    }
+
+    queries.push((
+        "delete",
+        "delete",
+        format!("DELETE FROM {} WHERE {}", table_name, filter),
+    ));
+
+    queries
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::model::{ColInfo, DbType};
+
+    use super::generate_rest_queries;
+
+    fn col(cid: i64, name: &str, ctype: &str, pk: bool) -> ColInfo {
+        ColInfo {
+            cid,
+            name: name.to_string(),
+            ctype: ctype.to_string(),
+            notnull: pk,
+            dflt_value: None,
+            pk,
+        }
+    }
+
+    #[test]
+    fn test1() {
+        let columns = vec![
+            col(0, "id", "INTEGER", true),
+            col(1, "title", "TEXT", false),
+            col(2, "done", "BOOLEAN", false),
+        ];
+
+        assert_eq!(
+            generate_rest_queries("todos", &columns, &DbType::Sqlite),
+            vec![
+                ("list", "get", "SELECT * FROM todos".to_string()),
+                (
+                    "insert",
+                    "post",
+                    "INSERT INTO todos(title, done) VALUES (${title}, ${done}) RETURNING *"
+                        .to_string()
+                ),
+                (
+                    "get",
+                    "get",
+                    "SELECT * FROM todos WHERE id=${id}".to_string()
+                ),
+                (
+                    "update",
+                    "put",
+                    "UPDATE todos SET title=${title}, done=${done} WHERE id=${id} RETURNING *"
+                        .to_string()
+                ),
+                (
+                    "delete",
+                    "delete",
+                    "DELETE FROM todos WHERE id=${id}".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test2() {
+        let columns = vec![
+            col(0, "user_id", "INTEGER", true),
+            col(1, "tag", "TEXT", true),
+        ];
+
+        let operations = generate_rest_queries("user_tags", &columns, &DbType::Sqlite)
+            .into_iter()
+            .map(|(o, _, _)| o)
+            .collect::<Vec<&str>>();
+
+        assert_eq!(operations, vec!["list", "insert"]);
+    }
+}
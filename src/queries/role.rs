@@ -12,35 +12,28 @@ impl Model {
         self.conn
             .as_ref()
             .unwrap()
-            .query_all_with_type::<RoleName>(query)
+            .query_all_with_type::<RoleName>(query, vec![])
             .await
     }
 
     pub async fn get_role_by_id(&self, role_id: i64) -> Result<Role, String> {
-        let query = format!(
-            "SELECT id, name, is_default, can_read, can_write, can_delete 
+        let query = "SELECT id, name, is_default, can_read, can_write, can_delete 
              FROM roles 
-             WHERE id={}",
-            role_id
-        );
+             WHERE id=?";
+        let args = vec![ColType::Integer(Some(role_id))];
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_one_with_type::<Role>(&query)
+            .query_one_with_type::<Role>(query, args)
             .await
     }
 
     pub async fn add_new_role(&self, name: String) -> Result<i64, String> {
-        let query = "INSERT INTO roles(name) VALUES (?) RETURNING id";
+        let query = "INSERT INTO roles(name) VALUES (?)";
         let args = vec![ColType::String(Some(name))];
 
-        let row = self.conn.as_ref().unwrap().query_one(query, args).await;
-
-        match row {
-            Ok(r) => Ok(r.get::<i64>(0).unwrap()),
-            Err(e) => Err(e),
-        }
+        self.conn.as_ref().unwrap().insert(query, args).await
     }
 
     pub async fn unset_default_role(&self) -> Result<u64, String> {
@@ -70,6 +63,9 @@ impl Model {
         let query = "DELETE FROM roles WHERE id=?";
         let args = vec![ColType::Integer(Some(role_id))];
 
-        self.conn.as_ref().unwrap().execute(query, args).await
+        let res = self.conn.as_ref().unwrap().execute(query, args).await?;
+        self.refresh_routes().await?;
+
+        Ok(res)
     }
 }
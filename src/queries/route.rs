@@ -0,0 +1,96 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::parser;
+
+use super::{
+    model::{Route, RouteRoleAccess, RouteRow, RouteWebhook},
+    Model,
+};
+
+#[derive(Debug, Default)]
+pub struct RouteTable {
+    ids: HashMap<String, i64>,
+    routes: HashMap<i64, Arc<Route>>,
+}
+
+impl Model {
+    // the whole table is rebuilt and swapped in at once, so a request never sees half an edit
+    pub async fn refresh_routes(&self) -> Result<(), String> {
+        let conn = self.conn.as_ref().unwrap();
+
+        let rows = conn
+            .query_all_with_type::<RouteRow>(
+                "SELECT id, name, exec_type, query, pagination, page_size, cursor_column, with_total, shape, group_by, max_rows, cache_ttl, cache_params, cache_per_user, cache_max_entries FROM queries",
+                vec![],
+            )
+            .await?;
+
+        let role_access = conn
+            .query_all_with_type::<RouteRoleAccess>(
+                "SELECT query_id, role_id FROM role_access",
+                vec![],
+            )
+            .await?;
+
+        let webhooks = conn
+            .query_all_with_type::<RouteWebhook>(
+                "SELECT query_id, id, name, exec_type, action, url, args, is_returned, return_mode, secret FROM webhooks 
+                INNER JOIN webhook_query ON webhooks.id = webhook_id",
+                vec![],
+            )
+            .await?;
+
+        let mut table = RouteTable::default();
+
+        for row in rows {
+            let params = parser::parse_query(&row.query)
+                .map(|(_, params)| params.into_iter().map(|p| p.to_string()).collect())
+                .map_err(|e| e.to_string());
+
+            let route = Route {
+                id: row.id,
+                name: row.name,
+                exec_type: row.exec_type,
+                params,
+                tables: parser::get_tables(&row.query),
+                query: row.query,
+                role_access: role_access
+                    .iter()
+                    .filter(|r| r.query_id == row.id)
+                    .map(|r| r.access.clone())
+                    .collect(),
+                webhooks: webhooks
+                    .iter()
+                    .filter(|w| w.query_id == row.id)
+                    .map(|w| w.webhook.clone())
+                    .collect(),
+                pagination: row.pagination,
+                shape: row.shape,
+                cache: row.cache,
+            };
+
+            table.ids.insert(route.name.clone(), route.id);
+            table.routes.insert(route.id, Arc::new(route));
+        }
+
+        *self.routes.write().unwrap() = table;
+        // a cached response may come from the old version of a query
+        self.cache.clear();
+
+        Ok(())
+    }
+
+    pub fn get_route_by_name(&self, name: &str) -> Option<Arc<Route>> {
+        let table = self.routes.read().unwrap();
+
+        table
+            .ids
+            .get(name)
+            .and_then(|id| table.routes.get(id))
+            .cloned()
+    }
+
+    pub fn get_route_by_id(&self, query_id: i64) -> Option<Arc<Route>> {
+        self.routes.read().unwrap().routes.get(&query_id).cloned()
+    }
+}
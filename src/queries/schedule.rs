@@ -0,0 +1,143 @@
+use crate::database::model::ColType;
+
+use super::{
+    model::{Schedule, ScheduleName, ScheduleRun},
+    Model,
+};
+
+impl Model {
+    pub async fn get_all_schedules(&self) -> Result<Vec<ScheduleName>, String> {
+        let query = "SELECT id, name FROM schedules ORDER BY id";
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_all_with_type::<ScheduleName>(query, vec![])
+            .await
+    }
+
+    pub async fn get_schedule_by_id(&self, schedule_id: i64) -> Result<Schedule, String> {
+        let query = "SELECT id, name, cron, target_type, target_id, args, enabled, next_run_at FROM schedules WHERE id=?";
+        let args = vec![ColType::Integer(Some(schedule_id))];
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_one_with_type::<Schedule>(query, args)
+            .await
+    }
+
+    pub async fn add_new_schedule(&self, name: String) -> Result<i64, String> {
+        let query = "INSERT INTO schedules(name) VALUES (?)";
+        let args = vec![ColType::String(Some(name))];
+
+        self.conn.as_ref().unwrap().insert(query, args).await
+    }
+
+    pub async fn edit_schedule(&self, schedule: Schedule) -> Result<u64, String> {
+        let query = "UPDATE schedules SET name=?, cron=?, target_type=?, target_id=?, args=?, enabled=?, next_run_at=? WHERE id=?";
+
+        let args = vec![
+            ColType::String(Some(schedule.name)),
+            ColType::String(Some(schedule.cron)),
+            ColType::String(Some(schedule.target_type)),
+            ColType::Integer(Some(schedule.target_id)),
+            ColType::String(Some(schedule.args)),
+            ColType::Bool(Some(schedule.enabled)),
+            ColType::Integer(Some(schedule.next_run_at)),
+            ColType::Integer(Some(schedule.id)),
+        ];
+
+        self.conn.as_ref().unwrap().execute(query, args).await
+    }
+
+    pub async fn disable_schedule(&self, schedule_id: i64) -> Result<u64, String> {
+        let query = "UPDATE schedules SET enabled=0, next_run_at=0 WHERE id=?";
+        let args = vec![ColType::Integer(Some(schedule_id))];
+
+        self.conn.as_ref().unwrap().execute(query, args).await
+    }
+
+    pub async fn delete_schedule(&self, schedule_id: i64) -> Result<u64, String> {
+        let query = "DELETE FROM schedules WHERE id=?";
+        let args = vec![ColType::Integer(Some(schedule_id))];
+
+        self.conn.as_ref().unwrap().execute(query, args).await
+    }
+
+    pub async fn get_due_schedules(&self, now: i64) -> Result<Vec<Schedule>, String> {
+        let query = "SELECT id, name, cron, target_type, target_id, args, enabled, next_run_at FROM schedules
+             WHERE enabled=1 AND next_run_at>0 AND next_run_at<=?
+             ORDER BY next_run_at";
+        let args = vec![ColType::Integer(Some(now))];
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_all_with_type::<Schedule>(query, args)
+            .await
+    }
+
+    pub async fn claim_schedule(
+        &self,
+        schedule_id: i64,
+        current_run_at: i64,
+        next_run_at: i64,
+    ) -> Result<bool, String> {
+        // only the instance that moves next_run_at forward gets to run the job
+        let query = "UPDATE schedules SET next_run_at=? WHERE id=? AND enabled=1 AND next_run_at=?";
+
+        let args = vec![
+            ColType::Integer(Some(next_run_at)),
+            ColType::Integer(Some(schedule_id)),
+            ColType::Integer(Some(current_run_at)),
+        ];
+
+        let res = self.conn.as_ref().unwrap().execute(query, args).await?;
+
+        Ok(res == 1)
+    }
+
+    pub async fn add_schedule_run(&self, run: ScheduleRun) -> Result<u64, String> {
+        let query = "INSERT INTO schedule_runs(schedule_id, started_at, duration_ms, status, output) VALUES (?, ?, ?, ?, ?)";
+
+        let args = vec![
+            ColType::Integer(Some(run.schedule_id)),
+            ColType::Integer(Some(run.started_at)),
+            ColType::Integer(Some(run.duration_ms)),
+            ColType::String(Some(run.status)),
+            ColType::String(Some(run.output)),
+        ];
+
+        self.conn.as_ref().unwrap().execute(query, args).await
+    }
+
+    pub async fn get_schedule_runs_by_schedule_id(
+        &self,
+        schedule_id: i64,
+    ) -> Result<Vec<ScheduleRun>, String> {
+        let query = "SELECT id, schedule_id, started_at, duration_ms, status, output
+             FROM schedule_runs
+             WHERE schedule_id=?
+             ORDER BY id DESC
+             LIMIT 100";
+        let args = vec![ColType::Integer(Some(schedule_id))];
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_all_with_type::<ScheduleRun>(query, args)
+            .await
+    }
+
+    pub async fn get_schedule_run_by_id(&self, run_id: i64) -> Result<ScheduleRun, String> {
+        let query = "SELECT id, schedule_id, started_at, duration_ms, status, output FROM schedule_runs WHERE id=?";
+        let args = vec![ColType::Integer(Some(run_id))];
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_one_with_type::<ScheduleRun>(query, args)
+            .await
+    }
+}
@@ -0,0 +1,97 @@
+use crate::database::model::{ColInfo, ColType, DbType};
+
+use super::{model::TableName, Model};
+
+pub const SYSTEM_TABLES: [&str; 18] = [
+    "roles",
+    "users",
+    "storage",
+    "tus_uploads",
+    "queries",
+    "role_access",
+    "migrations",
+    "webhooks",
+    "webhook_outbox",
+    "webhook_deliveries",
+    "table_triggers",
+    "change_log",
+    "schedules",
+    "schedule_runs",
+    "webhook_query",
+    "rest_tables",
+    "rest_access",
+    "sqlite_sequence",
+];
+
+pub fn is_valid_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl Model {
+    pub async fn get_all_tables(&self) -> Result<Vec<TableName>, String> {
+        let conn = self.conn.as_ref().unwrap();
+
+        let query = match conn.dbtype {
+            DbType::Sqlite => "SELECT name FROM sqlite_master WHERE type='table' ORDER BY name",
+            DbType::Mysql => {
+                "SELECT TABLE_NAME AS name FROM information_schema.tables
+                 WHERE table_schema=DATABASE() AND table_type='BASE TABLE'
+                 ORDER BY TABLE_NAME"
+            }
+        };
+
+        let tables = conn.query_all_with_type::<TableName>(query, vec![]).await?;
+
+        Ok(tables
+            .into_iter()
+            .filter(|t| !SYSTEM_TABLES.contains(&t.name.as_str()))
+            .collect())
+    }
+
+    pub async fn get_table_columns(&self, table_name: &str) -> Result<Vec<ColInfo>, String> {
+        if !is_valid_identifier(table_name) {
+            return Err(format!("invalid table name: {}", table_name));
+        }
+
+        let conn = self.conn.as_ref().unwrap();
+
+        let query = match conn.dbtype {
+            DbType::Sqlite => {
+                "SELECT cid, name, type AS ctype, \"notnull\", dflt_value, pk > 0 AS pk
+                 FROM pragma_table_info(?)
+                 ORDER BY cid"
+            }
+            DbType::Mysql => {
+                "SELECT CAST(ORDINAL_POSITION - 1 AS SIGNED) AS cid,
+                 COLUMN_NAME AS name,
+                 UPPER(COLUMN_TYPE) AS ctype,
+                 IS_NULLABLE='NO' AS `notnull`,
+                 COLUMN_DEFAULT AS dflt_value,
+                 COLUMN_KEY='PRI' AS pk
+                 FROM information_schema.columns
+                 WHERE table_schema=DATABASE() AND table_name=?
+                 ORDER BY ORDINAL_POSITION"
+            }
+        };
+
+        let args = vec![ColType::String(Some(table_name.to_string()))];
+        let columns = conn.query_all_with_type::<ColInfo>(query, args).await?;
+
+        if columns.is_empty() {
+            return Err(format!("table {} does not exist", table_name));
+        }
+
+        Ok(columns)
+    }
+}
+
+#[test]
+fn test_is_valid_identifier() {
+    assert!(is_valid_identifier("todos"));
+    assert!(is_valid_identifier("todo_items2"));
+    assert!(!is_valid_identifier("2todos"));
+    assert!(!is_valid_identifier("todos; DROP TABLE users"));
+    assert!(!is_valid_identifier(""));
+}
@@ -0,0 +1,960 @@
+use std::fs;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::database::model::{ColType, DbType};
+
+use super::{
+    model::{ForeignKeyColumn, IndexColumn, Migration, SqlText, TableName},
+    schema::is_valid_identifier,
+    table::quote_identifier,
+    Model,
+};
+
+pub const SCHEMA_FILE: &str = "schema.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeclaredSchema {
+    pub tables: Vec<TableDef>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TableDef {
+    pub name: String,
+    pub columns: Vec<ColumnDef>,
+    #[serde(default)]
+    pub indexes: Vec<IndexDef>,
+    #[serde(default)]
+    pub foreign_keys: Vec<ForeignKeyDef>,
+    #[serde(skip)]
+    pub triggers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ColumnDef {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ctype: String,
+    #[serde(default)]
+    pub primary_key: bool,
+    #[serde(default)]
+    pub auto_increment: bool,
+    #[serde(default)]
+    pub not_null: bool,
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexDef {
+    pub name: String,
+    pub columns: Vec<String>,
+    #[serde(default)]
+    pub unique: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ForeignKeyDef {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub columns: Vec<String>,
+    pub references: String,
+    pub foreign_columns: Vec<String>,
+    #[serde(default)]
+    pub on_delete: Option<String>,
+    #[serde(default)]
+    pub on_update: Option<String>,
+}
+
+impl DeclaredSchema {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let schema = serde_json::from_str::<DeclaredSchema>(&content)
+            .map_err(|e| format!("{}: {}", path, e))?;
+
+        for table in schema.tables.iter() {
+            let names = std::iter::once(&table.name)
+                .chain(table.columns.iter().map(|c| &c.name))
+                .chain(table.indexes.iter().map(|i| &i.name))
+                .chain(table.indexes.iter().flat_map(|i| i.columns.iter()))
+                .chain(table.foreign_keys.iter().flat_map(|f| {
+                    f.columns
+                        .iter()
+                        .chain(f.foreign_columns.iter())
+                        .chain(std::iter::once(&f.references))
+                }));
+
+            for name in names {
+                if !is_valid_identifier(name) {
+                    return Err(format!("invalid name in {}: {}", table.name, name));
+                }
+            }
+
+            if table.columns.is_empty() {
+                return Err(format!("{} has no columns", table.name));
+            }
+        }
+
+        Ok(schema)
+    }
+}
+
+impl Model {
+    pub async fn get_table_definition(&self, table_name: &str) -> Result<TableDef, String> {
+        let columns = self.get_table_columns(table_name).await?;
+        let conn = self.conn.as_ref().unwrap();
+        let args = vec![ColType::String(Some(table_name.to_string()))];
+
+        let (index_query, foreign_key_query, auto_increment) = match conn.dbtype {
+            DbType::Sqlite => {
+                let table_sql = conn
+                    .query_one_with_type::<SqlText>(
+                        "SELECT sql FROM sqlite_master WHERE type='table' AND name=?",
+                        args.clone(),
+                    )
+                    .await?;
+
+                let auto_increment = match table_sql.sql.to_uppercase().contains("AUTOINCREMENT") {
+                    true => columns
+                        .iter()
+                        .filter(|c| c.pk)
+                        .map(|c| c.name.clone())
+                        .collect(),
+                    false => vec![],
+                };
+
+                (
+                    "SELECT il.name AS name, il.\"unique\" AS is_unique, ii.name AS column_name
+                         FROM pragma_index_list(?) il, pragma_index_info(il.name) ii
+                         WHERE il.origin='c'
+                         ORDER BY il.name, ii.seqno",
+                    "SELECT CAST(id AS TEXT) AS name, \"from\" AS column_name,
+                         \"table\" AS references_table, COALESCE(\"to\", '') AS foreign_column,
+                         on_delete, on_update
+                         FROM pragma_foreign_key_list(?)
+                         ORDER BY id, seq",
+                    auto_increment,
+                )
+            }
+            DbType::Mysql => {
+                let auto_increment = conn
+                    .query_all_with_type::<TableName>(
+                        "SELECT COLUMN_NAME AS name FROM information_schema.columns
+                         WHERE table_schema=DATABASE() AND table_name=?
+                         AND EXTRA LIKE '%auto_increment%'",
+                        args.clone(),
+                    )
+                    .await?
+                    .into_iter()
+                    .map(|c| c.name)
+                    .collect();
+
+                (
+                    "SELECT INDEX_NAME AS name, NON_UNIQUE=0 AS is_unique, COLUMN_NAME AS column_name
+                         FROM information_schema.statistics
+                         WHERE table_schema=DATABASE() AND table_name=? AND INDEX_NAME<>'PRIMARY'
+                         ORDER BY INDEX_NAME, SEQ_IN_INDEX",
+                    "SELECT k.CONSTRAINT_NAME AS name, k.COLUMN_NAME AS column_name,
+                         k.REFERENCED_TABLE_NAME AS references_table,
+                         k.REFERENCED_COLUMN_NAME AS foreign_column,
+                         r.DELETE_RULE AS on_delete, r.UPDATE_RULE AS on_update
+                         FROM information_schema.key_column_usage k
+                         JOIN information_schema.referential_constraints r
+                         ON r.CONSTRAINT_SCHEMA=k.CONSTRAINT_SCHEMA AND r.CONSTRAINT_NAME=k.CONSTRAINT_NAME
+                         WHERE k.TABLE_SCHEMA=DATABASE() AND k.TABLE_NAME=?
+                         ORDER BY k.CONSTRAINT_NAME, k.ORDINAL_POSITION",
+                    auto_increment,
+                )
+            }
+        };
+
+        let mut foreign_keys: Vec<ForeignKeyDef> = vec![];
+        for fk in conn
+            .query_all_with_type::<ForeignKeyColumn>(foreign_key_query, args.clone())
+            .await?
+        {
+            match foreign_keys.last_mut() {
+                Some(last) if last.name.as_deref() == Some(fk.name.as_str()) => {
+                    last.columns.push(fk.column_name);
+                    last.foreign_columns.push(fk.foreign_column);
+                }
+                _ => foreign_keys.push(ForeignKeyDef {
+                    name: Some(fk.name),
+                    columns: vec![fk.column_name],
+                    references: fk.references_table,
+                    foreign_columns: vec![fk.foreign_column],
+                    on_delete: fk.on_delete,
+                    on_update: fk.on_update,
+                }),
+            }
+        }
+
+        let mut indexes: Vec<IndexDef> = vec![];
+        for idx in conn
+            .query_all_with_type::<IndexColumn>(index_query, args.clone())
+            .await?
+        {
+            // mysql backs every foreign key with an index of the same name
+            if foreign_keys
+                .iter()
+                .any(|f| f.name.as_ref() == Some(&idx.name))
+                && conn.dbtype == DbType::Mysql
+            {
+                continue;
+            }
+
+            match indexes.last_mut() {
+                Some(last) if last.name == idx.name => last.columns.push(idx.column_name),
+                _ => indexes.push(IndexDef {
+                    name: idx.name,
+                    columns: vec![idx.column_name],
+                    unique: idx.is_unique,
+                }),
+            }
+        }
+
+        let triggers = match conn.dbtype {
+            DbType::Sqlite => conn
+                .query_all_with_type::<SqlText>(
+                    "SELECT sql FROM sqlite_master WHERE type='trigger' AND tbl_name=?",
+                    args.clone(),
+                )
+                .await?
+                .into_iter()
+                .map(|t| t.sql)
+                .collect(),
+            DbType::Mysql => vec![],
+        };
+
+        Ok(TableDef {
+            name: table_name.to_string(),
+            columns: columns
+                .into_iter()
+                .map(|c| ColumnDef {
+                    auto_increment: auto_increment.contains(&c.name),
+                    default: match conn.dbtype {
+                        DbType::Sqlite => c.dflt_value,
+                        DbType::Mysql => c.dflt_value.map(|d| mysql_default_expression(&d)),
+                    },
+                    name: c.name,
+                    ctype: c.ctype,
+                    primary_key: c.pk,
+                    not_null: c.notnull,
+                })
+                .collect(),
+            indexes,
+            foreign_keys,
+            triggers,
+        })
+    }
+
+    // returns None when the database already matches the declared schema
+    pub async fn generate_schema_migration(&self, path: &str) -> Result<Option<i64>, String> {
+        let declared = DeclaredSchema::from_file(path)?;
+
+        let existing = self
+            .get_all_tables()
+            .await?
+            .into_iter()
+            .map(|t| t.name)
+            .collect::<Vec<String>>();
+
+        let mut live = vec![];
+        for table in declared.tables.iter() {
+            if existing.contains(&table.name) {
+                live.push(self.get_table_definition(&table.name).await?);
+            }
+        }
+
+        let dbtype = &self.conn.as_ref().unwrap().dbtype;
+        let (up_query, down_query) = diff_schema(dbtype, &live, &declared)?;
+
+        if up_query.is_empty() {
+            return Ok(None);
+        }
+
+        let name = format!("schema_{}", Local::now().format("%Y%m%d%H%M%S"));
+        let id = self.add_new_migration(name.clone()).await?;
+
+        let migration = Migration {
+            id,
+            name,
+            up_query,
+            down_query,
+        };
+        self.edit_up_migration(migration.clone()).await?;
+        self.edit_down_migration(migration).await?;
+
+        Ok(Some(id))
+    }
+}
+
+// every step is an (up, down) pair, down runs the steps in reverse
+pub fn diff_schema(
+    dbtype: &DbType,
+    live: &[TableDef],
+    declared: &DeclaredSchema,
+) -> Result<(String, String), String> {
+    let mut steps = vec![];
+
+    for table in declared.tables.iter() {
+        let current = match live.iter().find(|t| t.name == table.name) {
+            Some(current) => current,
+            None => continue,
+        };
+
+        for c in table
+            .columns
+            .iter()
+            .filter(|c| c.not_null && c.default.is_none())
+        {
+            if current
+                .columns
+                .iter()
+                .any(|l| l.name == c.name && !l.not_null && !l.primary_key)
+            {
+                return Err(format!(
+                    "{}.{} can't become NOT NULL without a default, rows holding NULL would not fit",
+                    table.name, c.name
+                ));
+            }
+        }
+    }
+
+    for table in declared.tables.iter() {
+        match live.iter().find(|t| t.name == table.name) {
+            Some(current) => steps.extend(diff_table(dbtype, current, table)),
+            None => {
+                let mut up = vec![create_table(dbtype, table)];
+                up.extend(
+                    table
+                        .indexes
+                        .iter()
+                        .map(|i| create_index(dbtype, &table.name, i)),
+                );
+
+                steps.push((
+                    up.join(";\n"),
+                    format!("DROP TABLE {}", quote_identifier(dbtype, &table.name)),
+                ));
+            }
+        }
+    }
+
+    let up = steps
+        .iter()
+        .map(|(u, _)| u.clone())
+        .collect::<Vec<String>>();
+    let down = steps
+        .iter()
+        .rev()
+        .map(|(_, d)| d.clone())
+        .collect::<Vec<String>>();
+
+    match up.is_empty() {
+        true => Ok(("".to_string(), "".to_string())),
+        false => Ok((up.join(";\n\n") + ";", down.join(";\n\n") + ";")),
+    }
+}
+
+fn diff_table(dbtype: &DbType, current: &TableDef, target: &TableDef) -> Vec<(String, String)> {
+    let added = target
+        .columns
+        .iter()
+        .filter(|c| !current.columns.iter().any(|l| l.name == c.name))
+        .collect::<Vec<&ColumnDef>>();
+    let removed = current
+        .columns
+        .iter()
+        .filter(|c| !target.columns.iter().any(|t| t.name == c.name))
+        .collect::<Vec<&ColumnDef>>();
+    let changed = target
+        .columns
+        .iter()
+        .filter_map(|c| {
+            current
+                .columns
+                .iter()
+                .find(|l| l.name == c.name && !same_column(dbtype, l, c))
+                .map(|l| (l, c))
+        })
+        .collect::<Vec<(&ColumnDef, &ColumnDef)>>();
+
+    let dropped_fks = current
+        .foreign_keys
+        .iter()
+        .filter(|f| {
+            !target
+                .foreign_keys
+                .iter()
+                .any(|t| same_foreign_key(dbtype, f, t))
+        })
+        .collect::<Vec<&ForeignKeyDef>>();
+    let added_fks = target
+        .foreign_keys
+        .iter()
+        .filter(|f| {
+            !current
+                .foreign_keys
+                .iter()
+                .any(|l| same_foreign_key(dbtype, l, f))
+        })
+        .collect::<Vec<&ForeignKeyDef>>();
+
+    let dropped_indexes = current
+        .indexes
+        .iter()
+        .filter(|i| !target.indexes.iter().any(|t| same_index(i, t)))
+        .map(|i| {
+            (
+                drop_index(dbtype, &current.name, i),
+                create_index(dbtype, &current.name, i),
+            )
+        })
+        .collect::<Vec<(String, String)>>();
+    let created_indexes = target
+        .indexes
+        .iter()
+        .filter(|i| !current.indexes.iter().any(|l| same_index(l, i)))
+        .map(|i| {
+            (
+                create_index(dbtype, &target.name, i),
+                drop_index(dbtype, &target.name, i),
+            )
+        })
+        .collect::<Vec<(String, String)>>();
+
+    let table = quote_identifier(dbtype, &target.name);
+    let mut steps = vec![];
+
+    match dbtype {
+        DbType::Sqlite => {
+            let columns_changed = !(removed.is_empty()
+                && changed.is_empty()
+                && dropped_fks.is_empty()
+                && added_fks.is_empty());
+
+            // sqlite can only add plain columns in place, everything else rebuilds the table
+            if columns_changed
+                || added
+                    .iter()
+                    .any(|c| c.primary_key || (c.not_null && c.default.is_none()))
+            {
+                return vec![(
+                    rebuild_table(dbtype, current, target, &current.triggers),
+                    rebuild_table(dbtype, target, current, &current.triggers),
+                )];
+            }
+
+            steps.extend(dropped_indexes);
+            for c in added {
+                steps.push((
+                    format!(
+                        "ALTER TABLE {} ADD COLUMN {}",
+                        table,
+                        column_definition(dbtype, c, false)
+                    ),
+                    format!(
+                        "ALTER TABLE {} DROP COLUMN {}",
+                        table,
+                        quote_identifier(dbtype, &c.name)
+                    ),
+                ));
+            }
+            steps.extend(created_indexes);
+        }
+        DbType::Mysql => {
+            for f in dropped_fks {
+                steps.push((
+                    drop_foreign_key(dbtype, &current.name, f),
+                    format!(
+                        "ALTER TABLE {} ADD {}",
+                        table,
+                        foreign_key_definition(dbtype, &current.name, f)
+                    ),
+                ));
+            }
+            steps.extend(dropped_indexes);
+            for c in removed {
+                steps.push((
+                    format!(
+                        "ALTER TABLE {} DROP COLUMN {}",
+                        table,
+                        quote_identifier(dbtype, &c.name)
+                    ),
+                    format!(
+                        "ALTER TABLE {} ADD COLUMN {}",
+                        table,
+                        column_definition(dbtype, c, false)
+                    ),
+                ));
+            }
+            for (from, to) in changed {
+                let column = quote_identifier(dbtype, &to.name);
+                let value = fill_null(dbtype, from, to);
+
+                let mut up = vec![];
+                if value != column {
+                    up.push(format!("UPDATE {} SET {} = {}", table, column, value));
+                }
+                up.push(format!(
+                    "ALTER TABLE {} MODIFY COLUMN {}",
+                    table,
+                    column_definition(dbtype, to, false)
+                ));
+
+                steps.push((
+                    up.join(";\n"),
+                    format!(
+                        "ALTER TABLE {} MODIFY COLUMN {}",
+                        table,
+                        column_definition(dbtype, from, false)
+                    ),
+                ));
+            }
+            for c in added {
+                steps.push((
+                    format!(
+                        "ALTER TABLE {} ADD COLUMN {}",
+                        table,
+                        column_definition(dbtype, c, false)
+                    ),
+                    format!(
+                        "ALTER TABLE {} DROP COLUMN {}",
+                        table,
+                        quote_identifier(dbtype, &c.name)
+                    ),
+                ));
+            }
+            steps.extend(created_indexes);
+            for f in added_fks {
+                steps.push((
+                    format!(
+                        "ALTER TABLE {} ADD {}",
+                        table,
+                        foreign_key_definition(dbtype, &target.name, f)
+                    ),
+                    drop_foreign_key(dbtype, &target.name, f),
+                ));
+            }
+        }
+    }
+
+    steps
+}
+
+// the usual sqlite dance: create the new shape, copy rows over, swap it in and restore
+// indexes and triggers. foreign keys are switched off while migrations run
+fn rebuild_table(dbtype: &DbType, from: &TableDef, to: &TableDef, triggers: &[String]) -> String {
+    let table = quote_identifier(dbtype, &to.name);
+    let temp_name = format!("{}__new", to.name);
+    let temp = quote_identifier(dbtype, &temp_name);
+
+    let common = to
+        .columns
+        .iter()
+        .filter_map(|c| {
+            from.columns
+                .iter()
+                .find(|f| f.name == c.name)
+                .map(|f| (f, c))
+        })
+        .collect::<Vec<(&ColumnDef, &ColumnDef)>>();
+
+    let columns = common
+        .iter()
+        .map(|(_, c)| quote_identifier(dbtype, &c.name))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let values = common
+        .iter()
+        .map(|(f, c)| fill_null(dbtype, f, c))
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    let mut statements = vec![create_table(
+        dbtype,
+        &TableDef {
+            name: temp_name.clone(),
+            ..to.clone()
+        },
+    )];
+
+    if !common.is_empty() {
+        statements.push(format!(
+            "INSERT INTO {} ({}) SELECT {} FROM {}",
+            temp, columns, values, table
+        ));
+    }
+
+    statements.push(format!("DROP TABLE {}", table));
+    statements.push(format!("ALTER TABLE {} RENAME TO {}", temp, table));
+    statements.extend(to.indexes.iter().map(|i| create_index(dbtype, &to.name, i)));
+    statements.extend(triggers.iter().cloned());
+
+    statements.join(";\n")
+}
+
+// a column turning NOT NULL takes its default where the old rows have NULL
+fn fill_null(dbtype: &DbType, from: &ColumnDef, to: &ColumnDef) -> String {
+    let column = quote_identifier(dbtype, &to.name);
+
+    match &to.default {
+        Some(default) if to.not_null && !from.not_null && !from.primary_key => {
+            format!("COALESCE({}, {})", column, default)
+        }
+        _ => column,
+    }
+}
+
+fn create_table(dbtype: &DbType, table: &TableDef) -> String {
+    let pk = table
+        .columns
+        .iter()
+        .filter(|c| c.primary_key)
+        .map(|c| quote_identifier(dbtype, &c.name))
+        .collect::<Vec<String>>();
+
+    let mut definitions = table
+        .columns
+        .iter()
+        .map(|c| column_definition(dbtype, c, pk.len() == 1))
+        .collect::<Vec<String>>();
+
+    if pk.len() > 1 {
+        definitions.push(format!("PRIMARY KEY ({})", pk.join(", ")));
+    }
+
+    definitions.extend(
+        table
+            .foreign_keys
+            .iter()
+            .map(|f| foreign_key_definition(dbtype, &table.name, f)),
+    );
+
+    format!(
+        "CREATE TABLE {} (\n    {}\n)",
+        quote_identifier(dbtype, &table.name),
+        definitions.join(",\n    ")
+    )
+}
+
+fn column_definition(dbtype: &DbType, column: &ColumnDef, inline_pk: bool) -> String {
+    let mut definition = format!(
+        "{} {}",
+        quote_identifier(dbtype, &column.name),
+        column.ctype
+    );
+
+    if column.not_null {
+        definition += " NOT NULL";
+    }
+    if column.primary_key && inline_pk {
+        definition += " PRIMARY KEY";
+    }
+    if column.auto_increment {
+        definition += match dbtype {
+            DbType::Sqlite if column.primary_key && inline_pk => " AUTOINCREMENT",
+            DbType::Sqlite => "",
+            DbType::Mysql => " AUTO_INCREMENT",
+        };
+    }
+    if let Some(default) = &column.default {
+        definition += &format!(" DEFAULT {}", default);
+    }
+
+    definition
+}
+
+fn foreign_key_definition(dbtype: &DbType, table_name: &str, fk: &ForeignKeyDef) -> String {
+    let columns = |c: &[String]| {
+        c.iter()
+            .map(|n| quote_identifier(dbtype, n))
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+
+    let mut definition = format!(
+        "FOREIGN KEY ({}) REFERENCES {} ({})",
+        columns(&fk.columns),
+        quote_identifier(dbtype, &fk.references),
+        columns(&fk.foreign_columns)
+    );
+
+    if let DbType::Mysql = dbtype {
+        definition = format!(
+            "CONSTRAINT {} {}",
+            quote_identifier(dbtype, &foreign_key_name(table_name, fk)),
+            definition
+        );
+    }
+    if let Some(on_delete) = &fk.on_delete {
+        definition += &format!(" ON DELETE {}", on_delete);
+    }
+    if let Some(on_update) = &fk.on_update {
+        definition += &format!(" ON UPDATE {}", on_update);
+    }
+
+    definition
+}
+
+fn foreign_key_name(table_name: &str, fk: &ForeignKeyDef) -> String {
+    fk.name
+        .clone()
+        .unwrap_or_else(|| format!("fk_{}_{}", table_name, fk.columns.join("_")))
+}
+
+fn drop_foreign_key(dbtype: &DbType, table_name: &str, fk: &ForeignKeyDef) -> String {
+    format!(
+        "ALTER TABLE {} DROP FOREIGN KEY {}",
+        quote_identifier(dbtype, table_name),
+        quote_identifier(dbtype, &foreign_key_name(table_name, fk))
+    )
+}
+
+fn create_index(dbtype: &DbType, table_name: &str, index: &IndexDef) -> String {
+    format!(
+        "CREATE {}INDEX {} ON {} ({})",
+        if index.unique { "UNIQUE " } else { "" },
+        quote_identifier(dbtype, &index.name),
+        quote_identifier(dbtype, table_name),
+        index
+            .columns
+            .iter()
+            .map(|c| quote_identifier(dbtype, c))
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+fn drop_index(dbtype: &DbType, table_name: &str, index: &IndexDef) -> String {
+    match dbtype {
+        DbType::Sqlite => format!("DROP INDEX {}", quote_identifier(dbtype, &index.name)),
+        DbType::Mysql => format!(
+            "DROP INDEX {} ON {}",
+            quote_identifier(dbtype, &index.name),
+            quote_identifier(dbtype, table_name)
+        ),
+    }
+}
+
+fn same_column(dbtype: &DbType, a: &ColumnDef, b: &ColumnDef) -> bool {
+    normalize_type(dbtype, &a.ctype) == normalize_type(dbtype, &b.ctype)
+        && a.primary_key == b.primary_key
+        && a.auto_increment == b.auto_increment
+        // primary keys are implicitly not null
+        && (a.primary_key || a.not_null == b.not_null)
+        && a.default.as_deref().map(normalize_default) == b.default.as_deref().map(normalize_default)
+}
+
+fn same_index(a: &IndexDef, b: &IndexDef) -> bool {
+    a.name == b.name && a.columns == b.columns && a.unique == b.unique
+}
+
+fn same_foreign_key(dbtype: &DbType, a: &ForeignKeyDef, b: &ForeignKeyDef) -> bool {
+    let action = |action: &Option<String>| match action.as_deref().map(|a| a.to_uppercase()) {
+        None => "NO ACTION".to_string(),
+        Some(a) if a == "RESTRICT" && *dbtype == DbType::Mysql => "NO ACTION".to_string(),
+        Some(a) => a,
+    };
+
+    a.columns == b.columns
+        && a.references == b.references
+        && a.foreign_columns == b.foreign_columns
+        && action(&a.on_delete) == action(&b.on_delete)
+        && action(&a.on_update) == action(&b.on_update)
+}
+
+fn normalize_type(dbtype: &DbType, ctype: &str) -> String {
+    let ctype = ctype
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_uppercase();
+
+    // INTEGER and INT differ in sqlite, only INTEGER PRIMARY KEY aliases the rowid
+    if *dbtype == DbType::Sqlite {
+        return ctype;
+    }
+
+    let base = ctype.split('(').next().unwrap_or_default();
+    match base {
+        "BOOL" | "BOOLEAN" => "TINYINT(1)".to_string(),
+        "TINYINT" if ctype == "TINYINT(1)" => ctype,
+        // mysql drops the display width of integer types
+        "INTEGER" | "INT" => ctype.replacen(base, "INT", 1).replace("(11)", ""),
+        "BIGINT" | "SMALLINT" | "MEDIUMINT" | "TINYINT" => match ctype.split_once(')') {
+            Some((_, rest)) => format!("{}{}", base, rest),
+            None => ctype,
+        },
+        _ => ctype,
+    }
+}
+
+fn normalize_default(default: &str) -> String {
+    let default = default.trim();
+
+    match default
+        .strip_prefix('\'')
+        .and_then(|d| d.strip_suffix('\''))
+    {
+        Some(d) => d.replace("''", "'"),
+        None => default.to_string(),
+    }
+}
+
+// information_schema hands back string defaults without their quotes
+fn mysql_default_expression(default: &str) -> String {
+    let upper = default.to_uppercase();
+
+    if default.parse::<f64>().is_ok()
+        || upper == "NULL"
+        || upper.starts_with("CURRENT_TIMESTAMP")
+        || default.starts_with('(')
+    {
+        default.to_string()
+    } else {
+        format!("'{}'", default.replace('\'', "''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::model::DbType;
+
+    use super::{diff_schema, ColumnDef, DeclaredSchema, IndexDef, TableDef};
+
+    fn column(name: &str, ctype: &str) -> ColumnDef {
+        ColumnDef {
+            name: name.to_string(),
+            ctype: ctype.to_string(),
+            primary_key: name == "id",
+            auto_increment: name == "id",
+            not_null: name == "id",
+            default: None,
+        }
+    }
+
+    fn todos(columns: Vec<ColumnDef>, indexes: Vec<IndexDef>) -> TableDef {
+        TableDef {
+            name: "todos".to_string(),
+            columns,
+            indexes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test1() {
+        let live = todos(
+            vec![column("id", "INTEGER"), column("title", "TEXT")],
+            vec![],
+        );
+
+        let unchanged = DeclaredSchema {
+            tables: vec![live.clone()],
+        };
+        assert_eq!(
+            diff_schema(&DbType::Sqlite, std::slice::from_ref(&live), &unchanged).unwrap(),
+            ("".to_string(), "".to_string())
+        );
+
+        let created = diff_schema(&DbType::Sqlite, &[], &unchanged).unwrap();
+        assert_eq!(
+            created,
+            (
+                "CREATE TABLE \"todos\" (\n    \"id\" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,\n    \"title\" TEXT\n);".to_string(),
+                "DROP TABLE \"todos\";".to_string()
+            )
+        );
+
+        let declared = DeclaredSchema {
+            tables: vec![todos(
+                vec![
+                    column("id", "INTEGER"),
+                    column("title", "TEXT"),
+                    column("done", "BOOLEAN"),
+                ],
+                vec![IndexDef {
+                    name: "todos_done".to_string(),
+                    columns: vec!["done".to_string()],
+                    unique: false,
+                }],
+            )],
+        };
+        let (up, down) = diff_schema(&DbType::Sqlite, &[live], &declared).unwrap();
+        assert_eq!(
+            up,
+            "ALTER TABLE \"todos\" ADD COLUMN \"done\" BOOLEAN;\n\nCREATE INDEX \"todos_done\" ON \"todos\" (\"done\");"
+        );
+        assert_eq!(
+            down,
+            "DROP INDEX \"todos_done\";\n\nALTER TABLE \"todos\" DROP COLUMN \"done\";"
+        );
+    }
+
+    #[test]
+    fn test2() {
+        let mut live = todos(
+            vec![column("id", "INTEGER"), column("title", "TEXT")],
+            vec![IndexDef {
+                name: "todos_title".to_string(),
+                columns: vec!["title".to_string()],
+                unique: true,
+            }],
+        );
+        live.triggers =
+            vec!["CREATE TRIGGER todos_ai AFTER INSERT ON todos BEGIN SELECT 1; END".to_string()];
+
+        let mut declared = live.clone();
+        declared.columns[1].not_null = true;
+        declared.columns[1].default = Some("''".to_string());
+        let declared = DeclaredSchema {
+            tables: vec![declared],
+        };
+
+        let (up, down) = diff_schema(&DbType::Sqlite, &[live.clone()], &declared).unwrap();
+        assert_eq!(
+            up,
+            [
+                "CREATE TABLE \"todos__new\" (\n    \"id\" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,\n    \"title\" TEXT NOT NULL DEFAULT ''\n)",
+                "INSERT INTO \"todos__new\" (\"id\", \"title\") SELECT \"id\", COALESCE(\"title\", '') FROM \"todos\"",
+                "DROP TABLE \"todos\"",
+                "ALTER TABLE \"todos__new\" RENAME TO \"todos\"",
+                "CREATE UNIQUE INDEX \"todos_title\" ON \"todos\" (\"title\")",
+                "CREATE TRIGGER todos_ai AFTER INSERT ON todos BEGIN SELECT 1; END;",
+            ]
+            .join(";\n")
+        );
+        assert!(down.starts_with("CREATE TABLE \"todos__new\" (\n    \"id\" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,\n    \"title\" TEXT\n)"));
+
+        let (up, down) = diff_schema(&DbType::Mysql, &[live], &declared).unwrap();
+        assert_eq!(
+            up,
+            "UPDATE `todos` SET `title` = COALESCE(`title`, '');\nALTER TABLE `todos` MODIFY COLUMN `title` TEXT NOT NULL DEFAULT '';"
+        );
+        assert_eq!(down, "ALTER TABLE `todos` MODIFY COLUMN `title` TEXT;");
+    }
+
+    #[test]
+    fn test3() {
+        let live = todos(
+            vec![column("id", "INTEGER"), column("title", "TEXT")],
+            vec![],
+        );
+
+        let mut declared = live.clone();
+        declared.columns[1].not_null = true;
+        let declared = DeclaredSchema {
+            tables: vec![declared],
+        };
+
+        for dbtype in [DbType::Sqlite, DbType::Mysql] {
+            assert_eq!(
+                diff_schema(&dbtype, std::slice::from_ref(&live), &declared),
+                Err("todos.title can't become NOT NULL without a default, rows holding NULL would not fit".to_string())
+            );
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use crate::database::model::ColType;
 
-use super::{model::Storage, Model};
+use super::{
+    model::{Storage, TusUpload},
+    Model,
+};
 
 impl Model {
     pub async fn upload_file(
@@ -9,7 +12,7 @@ impl Model {
         unique_name: String,
         uploaded_by: i64,
     ) -> Result<i64, String> {
-        let query = "INSERT INTO storage(file_name, unique_name, uploaded_by) VALUES (?, ?, ?) returning id";
+        let query = "INSERT INTO storage(file_name, unique_name, uploaded_by) VALUES (?, ?, ?)";
 
         let args = vec![
             ColType::String(Some(file_name)),
@@ -17,11 +20,7 @@ impl Model {
             ColType::Integer(Some(uploaded_by)),
         ];
 
-        let res = self.conn.as_ref().unwrap().query_one(query, args).await;
-        match res {
-            Ok(r) => Ok(r.get::<i64>(0).unwrap()),
-            Err(e) => Err(e),
-        }
+        self.conn.as_ref().unwrap().insert(query, args).await
     }
 
     pub async fn delete_file(&self, file_id: i64) -> Result<u64, String> {
@@ -33,15 +32,71 @@ impl Model {
     }
 
     pub async fn get_file_by_id(&self, file_id: i64) -> Result<Storage, String> {
-        let query = format!(
-            "SELECT id, file_name, unique_name FROM storage WHERE id={}",
-            file_id
-        );
+        let query = "SELECT id, file_name, unique_name FROM storage WHERE id=?";
+        let args = vec![ColType::Integer(Some(file_id))];
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_one_with_type::<Storage>(&query)
+            .query_one_with_type::<Storage>(query, args)
             .await
     }
+
+    pub async fn add_tus_upload(
+        &self,
+        id: String,
+        file_name: String,
+        upload_length: i64,
+        uploaded_by: i64,
+    ) -> Result<u64, String> {
+        let query = "INSERT INTO tus_uploads(id, file_name, upload_length, uploaded_by) VALUES (?, ?, ?, ?)";
+
+        let args = vec![
+            ColType::String(Some(id)),
+            ColType::String(Some(file_name)),
+            ColType::Integer(Some(upload_length)),
+            ColType::Integer(Some(uploaded_by)),
+        ];
+
+        self.conn.as_ref().unwrap().execute(query, args).await
+    }
+
+    pub async fn get_tus_upload_by_id(&self, upload_id: &str) -> Result<TusUpload, String> {
+        let query = "SELECT id, file_name, upload_length, upload_offset, uploaded_by FROM tus_uploads WHERE id=?";
+        let args = vec![ColType::String(Some(upload_id.to_string()))];
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_one_with_type::<TusUpload>(query, args)
+            .await
+    }
+
+    // false when the offset moved since it was read
+    pub async fn update_tus_upload_offset(
+        &self,
+        upload_id: String,
+        current_offset: i64,
+        upload_offset: i64,
+    ) -> Result<bool, String> {
+        let query = "UPDATE tus_uploads SET upload_offset=? WHERE id=? AND upload_offset=?";
+
+        let args = vec![
+            ColType::Integer(Some(upload_offset)),
+            ColType::String(Some(upload_id)),
+            ColType::Integer(Some(current_offset)),
+        ];
+
+        let res = self.conn.as_ref().unwrap().execute(query, args).await?;
+
+        Ok(res == 1)
+    }
+
+    pub async fn delete_tus_upload(&self, upload_id: String) -> Result<u64, String> {
+        let query = "DELETE FROM tus_uploads WHERE id=?";
+
+        let args = vec![ColType::String(Some(upload_id))];
+
+        self.conn.as_ref().unwrap().execute(query, args).await
+    }
 }
@@ -0,0 +1,331 @@
+use std::collections::BTreeMap;
+
+use base64::{engine::general_purpose, Engine};
+use serde_json::Value;
+
+use crate::database::model::{ColInfo, ColType, DbType};
+
+use super::{schema::is_valid_identifier, Model};
+
+impl Model {
+    fn quote_identifier(&self, name: &str) -> String {
+        quote_identifier(&self.conn.as_ref().unwrap().dbtype, name)
+    }
+
+    pub async fn get_table_rows(
+        &self,
+        table_name: &str,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<BTreeMap<String, ColType>>, String> {
+        self.select_table_rows(table_name, vec![], None, limit, offset)
+            .await
+    }
+
+    pub async fn get_table_row(
+        &self,
+        table_name: &str,
+        primary_key: Vec<(String, ColType)>,
+    ) -> Result<Option<BTreeMap<String, ColType>>, String> {
+        if primary_key.is_empty() {
+            return Err(format!("{} has no primary key", table_name));
+        }
+
+        let rows = self
+            .select_table_rows(table_name, primary_key, None, 1, 0)
+            .await?;
+
+        Ok(rows.into_iter().next())
+    }
+
+    // sort is (column, descending)
+    pub async fn select_table_rows(
+        &self,
+        table_name: &str,
+        filters: Vec<(String, ColType)>,
+        sort: Option<(String, bool)>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<BTreeMap<String, ColType>>, String> {
+        if !is_valid_identifier(table_name) {
+            return Err(format!("invalid table name: {}", table_name));
+        }
+
+        let mut query = format!("SELECT * FROM {}", self.quote_identifier(table_name));
+        let mut args = vec![];
+
+        if !filters.is_empty() {
+            let conditions = filters
+                .into_iter()
+                .map(|(c, v)| match is_null(&v) {
+                    true => format!("{} IS NULL", self.quote_identifier(&c)),
+                    false => {
+                        args.push(v);
+                        format!("{}=?", self.quote_identifier(&c))
+                    }
+                })
+                .collect::<Vec<String>>();
+
+            query += &format!(" WHERE {}", conditions.join(" AND "));
+        }
+
+        if let Some((column, descending)) = sort {
+            query += &format!(
+                " ORDER BY {} {}",
+                self.quote_identifier(&column),
+                if descending { "DESC" } else { "ASC" }
+            );
+        }
+
+        query += " LIMIT ? OFFSET ?";
+        args.push(ColType::Integer(Some(limit)));
+        args.push(ColType::Integer(Some(offset)));
+
+        let conn = self.conn.as_ref().unwrap();
+        let rows = conn.query_all(&query, args).await?;
+
+        conn.parse_all(rows)
+    }
+
+    pub async fn insert_table_row(
+        &self,
+        table_name: &str,
+        values: Vec<(String, ColType)>,
+    ) -> Result<Option<BTreeMap<String, ColType>>, String> {
+        if !is_valid_identifier(table_name) {
+            return Err(format!("invalid table name: {}", table_name));
+        }
+
+        let conn = self.conn.as_ref().unwrap();
+        if let DbType::Mysql = conn.dbtype {
+            return self.insert_mysql_table_row(table_name, values).await;
+        }
+
+        let query = match values.is_empty() {
+            true => format!(
+                "INSERT INTO {} DEFAULT VALUES RETURNING *",
+                self.quote_identifier(table_name)
+            ),
+            false => format!(
+                "INSERT INTO {}({}) VALUES ({}) RETURNING *",
+                self.quote_identifier(table_name),
+                self.column_list(&values),
+                vec!["?"; values.len()].join(", ")
+            ),
+        };
+        let args = values.into_iter().map(|(_, v)| v).collect();
+
+        let rows = conn.query_all(&query, args).await?;
+        self.notify_table_change(table_name);
+
+        Ok(conn.parse_all(rows)?.into_iter().next())
+    }
+
+    // mysql has no RETURNING, so the row is read back by its primary key
+    async fn insert_mysql_table_row(
+        &self,
+        table_name: &str,
+        values: Vec<(String, ColType)>,
+    ) -> Result<Option<BTreeMap<String, ColType>>, String> {
+        let columns = self.get_table_columns(table_name).await?;
+
+        let query = format!(
+            "INSERT INTO {}({}) VALUES ({})",
+            self.quote_identifier(table_name),
+            self.column_list(&values),
+            vec!["?"; values.len()].join(", ")
+        );
+        let args = values.iter().map(|(_, v)| v.clone()).collect();
+
+        let id = self.conn.as_ref().unwrap().insert(&query, args).await?;
+        self.notify_table_change(table_name);
+
+        let pk_columns = columns.iter().filter(|c| c.pk).collect::<Vec<_>>();
+        let mut primary_key = vec![];
+        for c in pk_columns.iter() {
+            match values.iter().find(|(name, _)| *name == c.name) {
+                Some((name, v)) => primary_key.push((name.clone(), v.clone())),
+                None if pk_columns.len() == 1 => {
+                    primary_key.push((c.name.clone(), ColType::Integer(Some(id))))
+                }
+                None => return Ok(None),
+            }
+        }
+
+        if primary_key.is_empty() {
+            return Ok(None);
+        }
+
+        self.get_table_row(table_name, primary_key).await
+    }
+
+    fn column_list(&self, values: &[(String, ColType)]) -> String {
+        values
+            .iter()
+            .map(|(c, _)| self.quote_identifier(c))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    pub async fn update_table_row(
+        &self,
+        table_name: &str,
+        primary_key: Vec<(String, ColType)>,
+        values: Vec<(String, ColType)>,
+    ) -> Result<u64, String> {
+        if !is_valid_identifier(table_name) {
+            return Err(format!("invalid table name: {}", table_name));
+        }
+        if primary_key.is_empty() {
+            return Err(format!("{} has no primary key", table_name));
+        }
+
+        let query = format!(
+            "UPDATE {} SET {} WHERE {}",
+            self.quote_identifier(table_name),
+            values
+                .iter()
+                .map(|(c, _)| format!("{}=?", self.quote_identifier(c)))
+                .collect::<Vec<String>>()
+                .join(", "),
+            self.primary_key_filter(&primary_key)
+        );
+        let args = values
+            .into_iter()
+            .chain(primary_key)
+            .map(|(_, v)| v)
+            .collect();
+
+        let res = self.conn.as_ref().unwrap().execute(&query, args).await?;
+        self.notify_table_change(table_name);
+
+        Ok(res)
+    }
+
+    pub async fn delete_table_row(
+        &self,
+        table_name: &str,
+        primary_key: Vec<(String, ColType)>,
+    ) -> Result<u64, String> {
+        if !is_valid_identifier(table_name) {
+            return Err(format!("invalid table name: {}", table_name));
+        }
+        if primary_key.is_empty() {
+            return Err(format!("{} has no primary key", table_name));
+        }
+
+        let query = format!(
+            "DELETE FROM {} WHERE {}",
+            self.quote_identifier(table_name),
+            self.primary_key_filter(&primary_key)
+        );
+        let args = primary_key.into_iter().map(|(_, v)| v).collect();
+
+        let res = self.conn.as_ref().unwrap().execute(&query, args).await?;
+        self.notify_table_change(table_name);
+
+        Ok(res)
+    }
+
+    fn notify_table_change(&self, table_name: &str) {
+        // realtime subscribers also need to see edits made outside the api
+        let _ = self.changes.send(vec![table_name.to_lowercase()]);
+    }
+
+    fn primary_key_filter(&self, primary_key: &[(String, ColType)]) -> String {
+        primary_key
+            .iter()
+            .map(|(c, _)| format!("{}=?", self.quote_identifier(c)))
+            .collect::<Vec<String>>()
+            .join(" AND ")
+    }
+}
+
+pub fn quote_identifier(dbtype: &DbType, name: &str) -> String {
+    match dbtype {
+        DbType::Sqlite => format!("\"{}\"", name.replace('"', "\"\"")),
+        DbType::Mysql => format!("`{}`", name.replace('`', "``")),
+    }
+}
+
+fn is_null(value: &ColType) -> bool {
+    matches!(serde_json::to_value(value), Ok(Value::Null))
+}
+
+pub fn format_col_value(value: &ColType) -> String {
+    match serde_json::to_value(value) {
+        Ok(Value::Null) => "NULL".to_string(),
+        Ok(Value::String(s)) => s,
+        Ok(v) => v.to_string(),
+        Err(_) => "".to_string(),
+    }
+}
+
+// reads text shown by format_col_value back with the column's declared type
+pub fn parse_col_value(column: &ColInfo, content: &str) -> Result<ColType, String> {
+    let ctype = column.ctype.to_uppercase();
+    let invalid = || format!("invalid value for {}: {}", column.name, content);
+
+    let value = if ctype.contains("BOOL") || ctype == "TINYINT(1)" {
+        match content {
+            "true" | "1" => ColType::Bool(Some(true)),
+            "false" | "0" => ColType::Bool(Some(false)),
+            _ => return Err(invalid()),
+        }
+    } else if ctype.contains("INT") {
+        ColType::Integer(Some(content.parse().map_err(|_| invalid())?))
+    } else if ["REAL", "FLOA", "DOUB"].iter().any(|t| ctype.contains(t)) {
+        ColType::Real(Some(content.parse().map_err(|_| invalid())?))
+    } else if ctype.contains("BLOB") || ctype.contains("BINARY") {
+        let bytes = general_purpose::STANDARD
+            .decode(content)
+            .map_err(|_| invalid())?;
+        ColType::Binary(Some(bytes))
+    } else if ctype.contains("JSON") {
+        serde_json::from_str::<Value>(content).map_err(|_| invalid())?;
+        ColType::Json(Some(content.to_string()))
+    } else {
+        ColType::String(Some(content.to_string()))
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::database::model::{ColInfo, ColType};
+
+    use super::{format_col_value, parse_col_value};
+
+    fn column(ctype: &str) -> ColInfo {
+        ColInfo {
+            cid: 0,
+            name: "c".to_string(),
+            ctype: ctype.to_string(),
+            notnull: false,
+            dflt_value: None,
+            pk: false,
+        }
+    }
+
+    #[test]
+    fn test1() {
+        let parsed = |ctype: &str, content: &str| {
+            parse_col_value(&column(ctype), content).map(|v| serde_json::to_value(v).unwrap())
+        };
+
+        let shown = format_col_value(&ColType::Binary(Some(vec![0, 159, 146, 150])));
+        assert!(matches!(
+            parse_col_value(&column("BLOB"), &shown),
+            Ok(ColType::Binary(Some(b))) if b == vec![0, 159, 146, 150]
+        ));
+
+        assert_eq!(parsed("INTEGER", "42"), Ok(json!(42)));
+        assert_eq!(parsed("TINYINT(1)", "true"), Ok(json!(true)));
+        assert_eq!(parsed("TEXT", "42"), Ok(json!("42")));
+        assert!(parsed("INTEGER", "4x").is_err());
+        assert!(parsed("BLOB", "not base64!").is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use crate::database::model::{ColType, DbType};
+
+use super::{
+    model::{ChangeLog, TableTrigger, Webhook},
+    schema::is_valid_identifier,
+    Model,
+};
+
+impl Model {
+    pub async fn get_table_triggers_by_webhook_id(
+        &self,
+        webhook_id: i64,
+    ) -> Result<Vec<TableTrigger>, String> {
+        let query = "SELECT id, webhook_id, table_name, event FROM table_triggers WHERE webhook_id=? ORDER BY id";
+        let args = vec![ColType::Integer(Some(webhook_id))];
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_all_with_type::<TableTrigger>(query, args)
+            .await
+    }
+
+    pub async fn get_table_trigger_by_id(&self, trigger_id: i64) -> Result<TableTrigger, String> {
+        let query = "SELECT id, webhook_id, table_name, event FROM table_triggers WHERE id=?";
+        let args = vec![ColType::Integer(Some(trigger_id))];
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_one_with_type::<TableTrigger>(query, args)
+            .await
+    }
+
+    pub async fn add_table_trigger(
+        &self,
+        webhook_id: i64,
+        table_name: String,
+        event: String,
+    ) -> Result<i64, String> {
+        self.install_table_trigger(&table_name, &event).await?;
+
+        let query = "INSERT INTO table_triggers(webhook_id, table_name, event) VALUES (?, ?, ?)";
+        let args = vec![
+            ColType::Integer(Some(webhook_id)),
+            ColType::String(Some(table_name)),
+            ColType::String(Some(event)),
+        ];
+
+        self.conn.as_ref().unwrap().insert(query, args).await
+    }
+
+    pub async fn delete_table_trigger(&self, trigger_id: i64) -> Result<u64, String> {
+        let trigger = self.get_table_trigger_by_id(trigger_id).await?;
+
+        let query = "DELETE FROM table_triggers WHERE id=?";
+        let args = vec![ColType::Integer(Some(trigger_id))];
+
+        let res = self.conn.as_ref().unwrap().execute(query, args).await?;
+
+        let remaining = self
+            .get_webhooks_by_table_event(&trigger.table_name, &trigger.event)
+            .await?;
+        if remaining.is_empty() {
+            self.drop_table_trigger(&trigger.table_name, &trigger.event)
+                .await?;
+        }
+
+        Ok(res)
+    }
+
+    pub async fn install_table_trigger(
+        &self,
+        table_name: &str,
+        event: &str,
+    ) -> Result<u64, String> {
+        let columns = self.get_table_columns(table_name).await?;
+        let conn = self.conn.as_ref().unwrap();
+
+        let row_json = |prefix: &str| -> String {
+            let fields = columns
+                .iter()
+                .map(|c| {
+                    let col = match conn.dbtype {
+                        DbType::Sqlite if c.ctype.to_uppercase().contains("BLOB") => {
+                            format!("hex({}.\"{}\")", prefix, c.name)
+                        }
+                        DbType::Sqlite => format!("{}.\"{}\"", prefix, c.name),
+                        DbType::Mysql => format!("{}.`{}`", prefix, c.name),
+                    };
+                    format!("'{}', {}", c.name.replace('\'', "''"), col)
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+
+            match conn.dbtype {
+                DbType::Sqlite => format!("json_object({})", fields),
+                DbType::Mysql => format!("JSON_OBJECT({})", fields),
+            }
+        };
+
+        let (old_row, new_row) = match event {
+            "insert" => ("NULL".to_string(), row_json("NEW")),
+            "update" => (row_json("OLD"), row_json("NEW")),
+            "delete" => (row_json("OLD"), "NULL".to_string()),
+            _ => return Err(format!("invalid event: {}", event)),
+        };
+
+        let insert_change = format!(
+            "INSERT INTO change_log(table_name, event, old_row, new_row) VALUES ('{}', '{}', {}, {})",
+            table_name, event, old_row, new_row
+        );
+
+        let query = match conn.dbtype {
+            DbType::Sqlite => format!(
+                "CREATE TRIGGER {} AFTER {} ON \"{}\" BEGIN {}; END",
+                trigger_name(table_name, event),
+                event.to_uppercase(),
+                table_name,
+                insert_change
+            ),
+            DbType::Mysql => format!(
+                "CREATE TRIGGER {} AFTER {} ON `{}` FOR EACH ROW {}",
+                trigger_name(table_name, event),
+                event.to_uppercase(),
+                table_name,
+                insert_change
+            ),
+        };
+
+        self.drop_table_trigger(table_name, event).await?;
+        conn.execute(&query, vec![]).await
+    }
+
+    pub async fn drop_table_trigger(&self, table_name: &str, event: &str) -> Result<u64, String> {
+        if !is_valid_identifier(table_name) {
+            return Err(format!("invalid table name: {}", table_name));
+        }
+
+        let query = format!("DROP TRIGGER IF EXISTS {}", trigger_name(table_name, event));
+
+        self.conn.as_ref().unwrap().execute(&query, vec![]).await
+    }
+
+    pub async fn get_webhooks_by_table_event(
+        &self,
+        table_name: &str,
+        event: &str,
+    ) -> Result<Vec<Webhook>, String> {
+        if !is_valid_identifier(table_name) {
+            return Err(format!("invalid table name: {}", table_name));
+        }
+
+        let query = "SELECT webhooks.id, name, exec_type, action, url, args, is_returned, return_mode, secret FROM webhooks
+            INNER JOIN table_triggers ON webhooks.id = webhook_id
+            WHERE table_name=? AND event=?";
+        let args = vec![
+            ColType::String(Some(table_name.to_string())),
+            ColType::String(Some(event.to_string())),
+        ];
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_all_with_type::<Webhook>(query, args)
+            .await
+    }
+
+    pub async fn get_unprocessed_changes(&self, limit: i64) -> Result<Vec<ChangeLog>, String> {
+        let query = "SELECT id, table_name, event, old_row, new_row FROM change_log WHERE processed=0 ORDER BY id LIMIT ?";
+        let args = vec![ColType::Integer(Some(limit))];
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_all_with_type::<ChangeLog>(query, args)
+            .await
+    }
+
+    pub async fn mark_change_processed(&self, change_id: i64) -> Result<u64, String> {
+        let query = "UPDATE change_log SET processed=1 WHERE id=?";
+        let args = vec![ColType::Integer(Some(change_id))];
+
+        self.conn.as_ref().unwrap().execute(query, args).await
+    }
+}
+
+fn trigger_name(table_name: &str, event: &str) -> String {
+    format!("minibase_{}_{}", table_name, event)
+}
@@ -16,22 +16,27 @@ impl Model {
         self.conn
             .as_ref()
             .unwrap()
-            .query_all_with_type::<User>(query)
+            .query_all_with_type::<User>(query, vec![])
             .await
     }
 
     pub async fn add_default_user(&self, user_email: String) -> Result<i64, String> {
         let query = "UPDATE users 
                     SET role_id=(SELECT id FROM roles WHERE is_default=1)
-                    WHERE role_id IS NULL AND users.email=? RETURNING users.id";
+                    WHERE role_id IS NULL AND users.email=?";
 
-        let args = vec![ColType::String(Some(user_email))];
+        let args = vec![ColType::String(Some(user_email.clone()))];
 
-        let res = self.conn.as_ref().unwrap().query_one(query, args).await;
-        match res {
-            Ok(r) => Ok(r.get::<i64>(0).unwrap()),
-            Err(e) => Err(e),
+        let conn = self.conn.as_ref().unwrap();
+        if conn.execute(query, args).await? == 0 {
+            return Err(format!("{} not found or already has a role", user_email));
         }
+
+        let query = "SELECT id FROM users WHERE email=?";
+        let args = vec![ColType::String(Some(user_email))];
+
+        let row = conn.query_one(query, args).await?;
+        row.get::<i64>(0).map_err(|e| e.to_string())
     }
 
     pub async fn remove_default_user(&self, user_id: i64) -> Result<u64, String> {
@@ -61,27 +66,24 @@ impl Model {
         &self,
         user_id: i64,
     ) -> Result<Vec<UserRoleAccess>, String> {
-        let query = format!(
-            "SELECT roles.id AS role_id, name,
+        let query = "SELECT roles.id AS role_id, name,
              (CASE WHEN users.id IS NULL THEN FALSE ELSE TRUE END) AS is_selected 
              FROM roles
              LEFT JOIN users 
               ON roles.id = users.role_id 
-               AND users.id={}
-            ",
-            user_id
-        );
+               AND users.id=?
+            ";
+        let args = vec![ColType::Integer(Some(user_id))];
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_all_with_type::<UserRoleAccess>(&query)
+            .query_all_with_type::<UserRoleAccess>(query, args)
             .await
     }
 
     pub async fn get_user_by_id(&self, user_id: i64) -> Result<UserId, String> {
-        let query = format!(
-            "SELECT users.id, email, password, 
+        let query = "SELECT users.id, email, password, 
              CASE WHEN role_id IS NULL 
               THEN (SELECT id FROM roles WHERE is_default=1) 
               ELSE roles.id END 
@@ -92,36 +94,33 @@ impl Model {
              AS role_name
              FROM users
              LEFT JOIN roles ON roles.id=role_id
-             WHERE users.id={}
-            ",
-            user_id
-        );
+             WHERE users.id=?
+            ";
+        let args = vec![ColType::Integer(Some(user_id))];
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_one_with_type::<UserId>(&query)
+            .query_one_with_type::<UserId>(query, args)
             .await
     }
 
     pub async fn get_user_by_email(&self, email: &str) -> Result<User, String> {
-        let query = format!(
-            "SELECT users.id, email, password, 
+        let query = "SELECT users.id, email, password, 
              CASE WHEN roles.name IS NULL 
               THEN (SELECT name FROM roles WHERE is_default=1) 
               ELSE roles.name END 
              AS role
              FROM users
              LEFT JOIN roles ON roles.id=role_id
-             WHERE email='{}'
-            ",
-            email
-        );
+             WHERE email=?
+            ";
+        let args = vec![ColType::String(Some(email.to_string()))];
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_one_with_type::<User>(&query)
+            .query_one_with_type::<User>(query, args)
             .await
     }
 
@@ -1,7 +1,7 @@
 use crate::database::model::ColType;
 
 use super::{
-    model::{Webhook, WebhookName},
+    model::{Webhook, WebhookDelivery, WebhookName, WebhookOutbox},
     Model,
 };
 
@@ -12,37 +12,30 @@ impl Model {
         self.conn
             .as_ref()
             .unwrap()
-            .query_all_with_type::<WebhookName>(query)
+            .query_all_with_type::<WebhookName>(query, vec![])
             .await
     }
 
     pub async fn get_webhook_by_id(&self, webhook_id: i64) -> Result<Webhook, String> {
-        let query = format!(
-            "SELECT id, name, exec_type, action, url, args, is_returned FROM webhooks WHERE id={}",
-            webhook_id
-        );
+        let query = "SELECT id, name, exec_type, action, url, args, is_returned, return_mode, secret FROM webhooks WHERE id=?";
+        let args = vec![ColType::Integer(Some(webhook_id))];
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_one_with_type::<Webhook>(&query)
+            .query_one_with_type::<Webhook>(query, args)
             .await
     }
 
     pub async fn add_new_webhook(&self, name: String) -> Result<i64, String> {
-        let query = "INSERT INTO webhooks(name) VALUES (?) RETURNING id";
+        let query = "INSERT INTO webhooks(name) VALUES (?)";
         let args = vec![ColType::String(Some(name))];
 
-        let row = self.conn.as_ref().unwrap().query_one(query, args).await;
-
-        match row {
-            Ok(r) => Ok(r.get::<i64>(0).unwrap()),
-            Err(e) => Err(e),
-        }
+        self.conn.as_ref().unwrap().insert(query, args).await
     }
 
     pub async fn edit_webhook(&self, w: Webhook) -> Result<u64, String> {
-        let query = "UPDATE webhooks SET name=?, exec_type=?, action=?, url=?, args=?, is_returned=? WHERE id=?";
+        let query = "UPDATE webhooks SET name=?, exec_type=?, action=?, url=?, args=?, is_returned=?, return_mode=?, secret=? WHERE id=?";
 
         let args = vec![
             ColType::String(Some(w.name)),
@@ -51,34 +44,135 @@ impl Model {
             ColType::String(Some(w.url)),
             ColType::Json(Some(w.args)),
             ColType::Bool(Some(w.is_returned)),
+            ColType::String(Some(w.return_mode)),
+            ColType::String(Some(w.secret)),
             ColType::Integer(Some(w.id)),
         ];
 
-        self.conn.as_ref().unwrap().execute(query, args).await
+        let res = self.conn.as_ref().unwrap().execute(query, args).await?;
+        self.refresh_routes().await?;
+
+        Ok(res)
     }
 
     pub async fn delete_webhook(&self, webhook_id: i64) -> Result<u64, String> {
         let query = "DELETE FROM webhooks WHERE id=?";
         let args = vec![ColType::Integer(Some(webhook_id))];
 
+        let res = self.conn.as_ref().unwrap().execute(query, args).await?;
+        self.refresh_routes().await?;
+
+        Ok(res)
+    }
+
+    pub async fn add_webhook_outbox(
+        &self,
+        webhook_id: i64,
+        method: String,
+        url: String,
+        payload: String,
+    ) -> Result<u64, String> {
+        let query =
+            "INSERT INTO webhook_outbox(webhook_id, method, url, payload) VALUES (?, ?, ?, ?)";
+
+        let args = vec![
+            ColType::Integer(Some(webhook_id)),
+            ColType::String(Some(method)),
+            ColType::String(Some(url)),
+            ColType::String(Some(payload)),
+        ];
+
+        self.conn.as_ref().unwrap().execute(query, args).await
+    }
+
+    pub async fn get_due_webhook_outbox(
+        &self,
+        now: i64,
+        limit: i64,
+    ) -> Result<Vec<WebhookOutbox>, String> {
+        let query = "SELECT webhook_outbox.id, webhook_id, method, webhook_outbox.url, payload, attempts, secret
+             FROM webhook_outbox
+             INNER JOIN webhooks ON webhooks.id = webhook_id
+             WHERE status='pending' AND next_attempt_at<=?
+             ORDER BY webhook_outbox.id
+             LIMIT ?";
+        let args = vec![ColType::Integer(Some(now)), ColType::Integer(Some(limit))];
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_all_with_type::<WebhookOutbox>(query, args)
+            .await
+    }
+
+    pub async fn update_webhook_outbox(
+        &self,
+        outbox_id: i64,
+        status: &str,
+        attempts: i64,
+        next_attempt_at: i64,
+    ) -> Result<u64, String> {
+        let query = "UPDATE webhook_outbox SET status=?, attempts=?, next_attempt_at=? WHERE id=?";
+
+        let args = vec![
+            ColType::String(Some(status.to_string())),
+            ColType::Integer(Some(attempts)),
+            ColType::Integer(Some(next_attempt_at)),
+            ColType::Integer(Some(outbox_id)),
+        ];
+
+        self.conn.as_ref().unwrap().execute(query, args).await
+    }
+
+    pub async fn add_webhook_delivery(&self, d: WebhookDelivery) -> Result<u64, String> {
+        let query = "INSERT INTO webhook_deliveries(webhook_id, method, url, payload, status_code, latency_ms, response, error, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)";
+
+        let args = vec![
+            ColType::Integer(Some(d.webhook_id)),
+            ColType::String(Some(d.method)),
+            ColType::String(Some(d.url)),
+            ColType::String(Some(d.payload)),
+            ColType::Integer(d.status_code),
+            ColType::Integer(Some(d.latency_ms)),
+            ColType::String(Some(d.response)),
+            ColType::String(Some(d.error)),
+            ColType::Integer(Some(d.created_at)),
+        ];
+
         self.conn.as_ref().unwrap().execute(query, args).await
     }
 
-    pub async fn get_all_webhook_query_by_query_id(
+    pub async fn get_webhook_deliveries_by_webhook_id(
+        &self,
+        webhook_id: i64,
+    ) -> Result<Vec<WebhookDelivery>, String> {
+        let query = "SELECT id, webhook_id, method, url, payload, status_code, latency_ms, response, error, created_at
+             FROM webhook_deliveries
+             WHERE webhook_id=?
+             ORDER BY id DESC
+             LIMIT 100";
+        let args = vec![ColType::Integer(Some(webhook_id))];
+
+        self.conn
+            .as_ref()
+            .unwrap()
+            .query_all_with_type::<WebhookDelivery>(query, args)
+            .await
+    }
+
+    pub async fn get_webhook_delivery_by_id(
         &self,
-        query_id: i64,
-    ) -> Result<Vec<Webhook>, String> {
-        let query = format!(
-            "SELECT id, name, exec_type, action, url, args, is_returned FROM webhooks 
-            INNER JOIN webhook_query ON webhooks.id = webhook_id
-            WHERE query_id={}",
-            query_id
-        );
+        delivery_id: i64,
+    ) -> Result<WebhookDelivery, String> {
+        let query = "SELECT id, webhook_id, method, url, payload, status_code, latency_ms, response, error, created_at
+             FROM webhook_deliveries
+             WHERE id=?";
+        let args = vec![ColType::Integer(Some(delivery_id))];
 
         self.conn
             .as_ref()
             .unwrap()
-            .query_all_with_type::<Webhook>(&query)
+            .query_one_with_type::<WebhookDelivery>(query, args)
             .await
     }
 }
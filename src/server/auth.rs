@@ -1,22 +1,20 @@
 use axum::{
-    body::Body,
-    extract::State,
-    http::{Request, StatusCode},
-    middleware::Next,
-    response::Response,
-    routing::post,
+    body::Body, extract::State, http::Request, middleware::Next, response::Response, routing::post,
     Extension, Json, Router,
 };
 use cookie::time::{Duration, OffsetDateTime};
 use serde_json::Value;
 use tower_cookies::{Cookie, Cookies};
 
-use crate::queries::{
-    model::{User, UserId, UserStorage},
-    Model,
+use crate::{
+    error::AppError,
+    queries::{
+        model::{RoleAccess, User, UserId, UserStorage},
+        Model,
+    },
 };
 
-use super::{model::ResponseUser, utils::hash_password};
+use super::{metrics, model::ResponseUser, utils::hash_password};
 
 pub fn generate_auth_routes(model: Model) -> Router {
     Router::new()
@@ -26,31 +24,38 @@ pub fn generate_auth_routes(model: Model) -> Router {
         .with_state(model)
 }
 
-async fn signup(State(model): State<Model>, Json(body): Json<Value>) -> (StatusCode, String) {
+async fn signup(State(model): State<Model>, Json(body): Json<Value>) -> Result<String, AppError> {
     let email = body.get("email");
     let password = body.get("password");
 
-    if email.is_none() || password.is_none() {
-        return (
-            StatusCode::BAD_REQUEST,
-            "Insufficient parameters".to_string(),
-        );
-    }
-
     match (email, password) {
         (Some(Value::String(email)), Some(Value::String(password))) => {
             let hashed_password = hash_password(password.to_string());
             let res = model.create_user(email.to_string(), hashed_password).await;
 
-            match res {
-                Ok(_) => (StatusCode::OK, "Signup successful".to_string()),
-                Err(_) => (StatusCode::BAD_REQUEST, "Invalid Credentials".to_string()),
+            match res.map_err(AppError::from_db) {
+                Ok(_) => {
+                    model.metrics.record_auth("signup", "success");
+                    Ok("Signup successful".to_string())
+                }
+                Err(AppError::Conflict(_)) => {
+                    model.metrics.record_auth("signup", "conflict");
+                    Err(AppError::Conflict(
+                        "email is already registered".to_string(),
+                    ))
+                }
+                Err(e) => {
+                    model.metrics.record_auth("signup", "error");
+                    Err(e)
+                }
             }
         }
-        (_, _) => (
-            StatusCode::BAD_REQUEST,
-            "Insufficient parameters".to_string(),
-        ),
+        (_, _) => {
+            model.metrics.record_auth("signup", "bad_request");
+            Err(AppError::BadRequest(
+                "email and password are required".to_string(),
+            ))
+        }
     }
 }
 
@@ -58,17 +63,10 @@ async fn login(
     State(model): State<Model>,
     cookies: Cookies,
     Json(body): Json<Value>,
-) -> (StatusCode, String) {
+) -> Result<String, AppError> {
     let email = body.get("email");
     let password = body.get("password");
 
-    if email.is_none() || password.is_none() {
-        return (
-            StatusCode::BAD_REQUEST,
-            "Insufficient parameters".to_string(),
-        );
-    }
-
     match (email, password) {
         (Some(Value::String(email)), Some(Value::String(password))) => {
             let res = model.get_user_by_email(email.as_str()).await;
@@ -78,12 +76,15 @@ async fn login(
                     let hashed_password = hash_password(password.to_string());
 
                     if hashed_password != user.password {
-                        (
-                            StatusCode::UNAUTHORIZED,
-                            "Enter valid email and password".to_string(),
-                        )
+                        model.metrics.record_auth("login", "invalid_credentials");
+                        Err(AppError::Unauthorized(
+                            "invalid email or password".to_string(),
+                        ))
                     } else {
-                        let token = model.utils.generate_auth_token(user.clone()).unwrap();
+                        let token = model
+                            .utils
+                            .generate_auth_token(user.clone())
+                            .map_err(|e| AppError::Internal(e.to_string()))?;
 
                         let mut cookie = Cookie::new("auth", token);
                         cookie.set_http_only(true);
@@ -94,6 +95,7 @@ async fn login(
                         cookie.set_expires(fut);
 
                         cookies.add(cookie);
+                        model.metrics.record_auth("login", "success");
 
                         let res_user = ResponseUser {
                             id: user.id,
@@ -101,58 +103,86 @@ async fn login(
                             role: user.role,
                         };
 
-                        (StatusCode::OK, serde_json::to_string(&res_user).unwrap())
+                        serde_json::to_string(&res_user)
+                            .map_err(|e| AppError::Internal(e.to_string()))
                     }
                 }
-                Err(_) => (StatusCode::BAD_REQUEST, "Invalid Credentials".to_string()),
+                Err(_) => {
+                    model.metrics.record_auth("login", "invalid_credentials");
+                    Err(AppError::Unauthorized(
+                        "invalid email or password".to_string(),
+                    ))
+                }
             }
         }
-        (_, _) => (
-            StatusCode::BAD_REQUEST,
-            "Insufficient parameters".to_string(),
-        ),
+        (_, _) => {
+            model.metrics.record_auth("login", "bad_request");
+            Err(AppError::BadRequest(
+                "email and password are required".to_string(),
+            ))
+        }
     }
 }
 
-async fn logout(cookies: Cookies) -> (StatusCode, String) {
+async fn logout(cookies: Cookies) -> String {
     let mut cookie = Cookie::from("auth");
     cookie.set_path("/");
     cookies.remove(cookie);
 
-    (StatusCode::OK, "logout successfully".to_string())
+    "logout successfully".to_string()
 }
 
 pub async fn auth_middleware(
     Extension(model): Extension<Model>,
-    Extension(query_id): Extension<i64>,
+    Extension(role_access): Extension<Vec<RoleAccess>>,
     cookies: Cookies,
     mut req: Request<Body>,
     next: Next,
-) -> Result<Response, StatusCode> {
-    let optional_role_access = model.get_all_role_access_by_query_id(query_id).await;
-
-    let role_access;
-    match optional_role_access {
-        Ok(ra) => {
-            if ra.is_empty() {
-                req.extensions_mut().insert::<Option<User>>(None);
-                return Ok(next.run(req).await);
-            }
-            role_access = ra;
-        }
-        Err(_) => {
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+) -> Result<Response, AppError> {
+    let token = cookies.get("auth").map(|c| c.value().to_string());
+
+    let user = authorize_roles(&model, role_access, token.as_deref()).await?;
+    if let Some(u) = &user {
+        metrics::set_user_id(u.id);
     }
+    req.extensions_mut().insert(user);
 
-    match cookies.get("auth") {
-        Some(cookie) => {
-            let token = cookie.value();
-            let optional_user = authorize_current_user(&model, token).await;
+    Ok(next.run(req).await)
+}
+
+// no role access means the endpoint is public
+pub async fn authorize_roles(
+    model: &Model,
+    role_access: Vec<RoleAccess>,
+    auth_token: Option<&str>,
+) -> Result<Option<User>, AppError> {
+    if role_access.is_empty() {
+        return Ok(None);
+    }
+
+    let res = authorize_token(model, role_access, auth_token).await;
+    let outcome = match &res {
+        Ok(_) => "success",
+        Err(AppError::Forbidden(_)) => "forbidden",
+        Err(_) => "unauthorized",
+    };
+    model.metrics.record_auth("authorize", outcome);
+
+    res
+}
+
+async fn authorize_token(
+    model: &Model,
+    role_access: Vec<RoleAccess>,
+    auth_token: Option<&str>,
+) -> Result<Option<User>, AppError> {
+    match auth_token {
+        Some(token) => {
+            let optional_user = authorize_current_user(model, token).await;
             match optional_user {
                 Some(user) => {
                     if user.role_id.is_none() {
-                        return Err(StatusCode::UNAUTHORIZED);
+                        return Err(forbidden());
                     }
                     if role_access
                         .into_iter()
@@ -160,23 +190,21 @@ pub async fn auth_middleware(
                         .collect::<Vec<i64>>()
                         .contains(&user.role_id.unwrap())
                     {
-                        req.extensions_mut().insert(Some(User {
+                        Ok(Some(User {
                             id: user.id,
                             email: user.email,
                             password: user.password,
                             role: user.role_name,
-                        }));
+                        }))
                     } else {
-                        return Err(StatusCode::UNAUTHORIZED);
+                        Err(forbidden())
                     }
                 }
-                None => return Err(StatusCode::UNAUTHORIZED),
+                None => Err(unauthorized()),
             }
         }
-        None => return Err(StatusCode::UNAUTHORIZED),
+        None => Err(unauthorized()),
     }
-
-    Ok(next.run(req).await)
 }
 
 pub async fn storage_middleware(
@@ -184,7 +212,7 @@ pub async fn storage_middleware(
     cookies: Cookies,
     mut req: Request<Body>,
     next: Next,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, AppError> {
     match cookies.get("auth") {
         Some(cookie) => {
             let token = cookie.value();
@@ -192,8 +220,9 @@ pub async fn storage_middleware(
             match optional_user {
                 Some(user) => {
                     if user.role_id.is_none() {
-                        return Err(StatusCode::UNAUTHORIZED);
+                        return Err(forbidden());
                     }
+                    metrics::set_user_id(user.id);
 
                     let optional_access = model.get_role_by_id(user.role_id.unwrap()).await;
                     match optional_access {
@@ -207,10 +236,10 @@ pub async fn storage_middleware(
                                 can_delete: role.can_delete,
                             }));
                         }
-                        Err(_) => return Err(StatusCode::UNAUTHORIZED),
+                        Err(_) => return Err(forbidden()),
                     }
                 }
-                None => return Err(StatusCode::UNAUTHORIZED),
+                None => return Err(unauthorized()),
             }
         }
         None => {
@@ -222,6 +251,14 @@ pub async fn storage_middleware(
     Ok(next.run(req).await)
 }
 
+fn unauthorized() -> AppError {
+    AppError::Unauthorized("login required".to_string())
+}
+
+fn forbidden() -> AppError {
+    AppError::Forbidden("not allowed for this role".to_string())
+}
+
 async fn authorize_current_user(model: &Model, auth_token: &str) -> Option<UserId> {
     let token_claim = model.utils.decode_auth_token(auth_token);
 
@@ -0,0 +1,284 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde_json::{json, Value};
+use tokio::sync::broadcast::error::RecvError;
+
+use super::QueryOutput;
+use crate::{
+    parser,
+    queries::{
+        model::{Route, User},
+        Model,
+    },
+};
+
+#[derive(Debug, Default)]
+pub struct ResponseCache {
+    queries: Mutex<HashMap<i64, CachedQuery>>,
+    generation: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct CachedQuery {
+    tables: Vec<String>,
+    entries: HashMap<String, CachedResponse>,
+}
+
+#[derive(Debug)]
+struct CachedResponse {
+    output: QueryOutput,
+    expires_at: Instant,
+}
+
+impl ResponseCache {
+    pub fn get(&self, query_id: i64, key: &str) -> Option<QueryOutput> {
+        let mut queries = self.queries.lock().unwrap();
+        let query = queries.get_mut(&query_id)?;
+
+        match query.entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.output.clone()),
+            Some(_) => {
+                query.entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    // taken before running the query, a write in between makes insert drop the result
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    pub fn insert(&self, route: &Route, key: String, output: QueryOutput, generation: u64) {
+        let mut queries = self.queries.lock().unwrap();
+        if self.generation() != generation {
+            return;
+        }
+
+        let query = queries.entry(route.id).or_default();
+        query.tables = route.tables.clone();
+
+        let now = Instant::now();
+        query.entries.retain(|_, e| e.expires_at > now);
+
+        let max_entries = route.cache.cache_max_entries.max(1) as usize;
+        while query.entries.len() >= max_entries {
+            let oldest = query
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.expires_at)
+                .map(|(k, _)| k.clone());
+
+            match oldest {
+                Some(k) => query.entries.remove(&k),
+                None => break,
+            };
+        }
+
+        query.entries.insert(
+            key,
+            CachedResponse {
+                output,
+                expires_at: now + Duration::from_secs(route.cache.cache_ttl as u64),
+            },
+        );
+    }
+
+    pub fn invalidate(&self, tables: &[String]) {
+        if tables.is_empty() {
+            return;
+        }
+
+        let mut queries = self.queries.lock().unwrap();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+
+        queries.retain(|_, q| {
+            !q.tables
+                .iter()
+                .any(|t| tables.iter().any(|w| w.eq_ignore_ascii_case(t)))
+        });
+    }
+
+    pub fn clear(&self) {
+        let mut queries = self.queries.lock().unwrap();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+
+        queries.clear();
+    }
+}
+
+// every param the query reads is part of the key unless `cache_params` narrows it down,
+// paging params always are and a query reading the user is always cached per user
+pub fn cache_key(route: &Route, params: &HashMap<String, String>, user: Option<&User>) -> String {
+    let variables = route.params.as_deref().unwrap_or_default();
+    let identifiers = parser::get_identifier_placeholders(&route.query).unwrap_or_default();
+
+    let chosen = route
+        .cache
+        .cache_params
+        .split(',')
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<&str>>();
+
+    let mut names = variables
+        .iter()
+        .filter(|v| !v.starts_with('.'))
+        .filter_map(|v| v.split('.').next())
+        .chain(identifiers.iter().map(|(name, _)| name.as_str()))
+        .filter(|name| chosen.is_empty() || chosen.contains(name))
+        .chain(["limit", "offset", "cursor"])
+        .collect::<Vec<&str>>();
+    names.sort();
+    names.dedup();
+
+    let mut key = names
+        .into_iter()
+        .map(|name| json!([name, params.get(name)]))
+        .collect::<Vec<Value>>();
+
+    let reads_user = variables.iter().any(|v| v.starts_with(".USER_"));
+    if route.cache.cache_per_user || reads_user {
+        key.push(json!([".USER_ID", user.map(|u| u.id)]));
+    }
+
+    Value::Array(key).to_string()
+}
+
+// writes made outside of /api queries (rest, tui, triggers) reach the cache through the change feed
+pub async fn run_invalidator(model: Model) {
+    let mut changes = model.changes.subscribe();
+
+    loop {
+        match changes.recv().await {
+            Ok(tables) => model.cache.invalidate(&tables),
+            Err(RecvError::Lagged(_)) => model.cache.clear(),
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::queries::model::{QueryCache, QueryPagination, QueryShape, Route, User};
+
+    use super::{cache_key, QueryOutput, ResponseCache};
+
+    fn output(body: &str) -> QueryOutput {
+        QueryOutput {
+            body: body.to_string(),
+            truncated: false,
+        }
+    }
+
+    fn route(ttl: i64, max_entries: i64) -> Route {
+        Route {
+            id: 1,
+            name: "items".to_string(),
+            exec_type: "get".to_string(),
+            query: "SELECT * FROM items".to_string(),
+            params: Ok(vec![]),
+            tables: vec!["items".to_string()],
+            role_access: vec![],
+            webhooks: vec![],
+            pagination: QueryPagination {
+                pagination: "none".to_string(),
+                page_size: 100,
+                cursor_column: "".to_string(),
+                with_total: false,
+            },
+            shape: QueryShape {
+                shape: "flat".to_string(),
+                group_by: "".to_string(),
+                max_rows: 0,
+            },
+            cache: QueryCache {
+                cache_ttl: ttl,
+                cache_params: "category".to_string(),
+                cache_per_user: true,
+                cache_max_entries: max_entries,
+            },
+        }
+    }
+
+    #[test]
+    fn test1() {
+        let cache = ResponseCache::default();
+        let items = route(60, 2);
+
+        for key in ["a", "b", "c"] {
+            cache.insert(&items, key.to_string(), output(key), cache.generation());
+        }
+        assert_eq!(cache.get(1, "a").map(|o| o.body), None);
+        assert_eq!(cache.get(1, "c").map(|o| o.body), Some("c".to_string()));
+
+        cache.insert(&route(0, 2), "d".to_string(), output("d"), 0);
+        assert_eq!(cache.get(1, "d").map(|o| o.body), None);
+
+        let generation = cache.generation();
+        cache.invalidate(&["ITEMS".to_string()]);
+        assert_eq!(cache.get(1, "c").map(|o| o.body), None);
+
+        cache.insert(&items, "e".to_string(), output("e"), generation);
+        assert_eq!(cache.get(1, "e").map(|o| o.body), None);
+    }
+
+    #[test]
+    fn test2() {
+        let mut items = route(60, 10);
+        items.query = "SELECT * FROM items WHERE category=${category} ORDER BY ${@sort in id,name}"
+            .to_string();
+        items.params = Ok(vec!["category".to_string()]);
+        items.cache.cache_params = "".to_string();
+        items.cache.cache_per_user = false;
+
+        let user = User {
+            id: 7,
+            email: "a@example.com".to_string(),
+            password: "".to_string(),
+            role: None,
+        };
+
+        let mut params = HashMap::new();
+        params.insert("category".to_string(), "books".to_string());
+        params.insert("ignored".to_string(), "x".to_string());
+
+        let key = cache_key(&items, &params, Some(&user));
+
+        params.insert("ignored".to_string(), "y".to_string());
+        assert_eq!(cache_key(&items, &params, None), key);
+
+        params.insert("sort".to_string(), "name".to_string());
+        let sorted = cache_key(&items, &params, None);
+        assert_ne!(sorted, key);
+
+        params.insert("category".to_string(), "games".to_string());
+        assert_ne!(cache_key(&items, &params, None), sorted);
+
+        items.cache.cache_params = "sort".to_string();
+        assert_eq!(
+            cache_key(&items, &params, None),
+            cache_key(
+                &items,
+                &HashMap::from([("sort".to_string(), "name".to_string())]),
+                None
+            )
+        );
+
+        items.params = Ok(vec![".USER_ID".to_string()]);
+        assert_ne!(
+            cache_key(&items, &params, Some(&user)),
+            cache_key(&items, &params, None)
+        );
+    }
+}
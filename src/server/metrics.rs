@@ -0,0 +1,558 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Write as _,
+    future::Future,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::Body,
+    extract::{MatchedPath, State},
+    http::{header::AUTHORIZATION, HeaderMap, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use chrono::Utc;
+use serde_json::json;
+use tokio::{fs, io::AsyncWriteExt, sync::mpsc};
+
+use crate::{error::AppError, queries::Model};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+const ACCESS_LOG_FILE: &str = "logs/access.log";
+const ACCESS_LOG_QUEUE: usize = 4096;
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+const STATS_WINDOW: Duration = Duration::from_secs(60);
+
+tokio::task_local! {
+    static REQUEST: Arc<RequestContext>;
+}
+
+// filled in while the request travels through the middlewares and handlers
+pub struct RequestContext {
+    pub id: String,
+    endpoint: Mutex<Option<String>>,
+    user_id: Mutex<Option<i64>>,
+    db_micros: AtomicU64,
+}
+
+pub fn request_id() -> Option<String> {
+    REQUEST.try_with(|r| r.id.clone()).ok()
+}
+
+pub fn set_endpoint(endpoint: String) {
+    let _ = REQUEST.try_with(|r| *r.endpoint.lock().unwrap() = Some(endpoint));
+}
+
+pub fn set_user_id(user_id: i64) {
+    let _ = REQUEST.try_with(|r| *r.user_id.lock().unwrap() = Some(user_id));
+}
+
+pub async fn time_db<T>(f: impl Future<Output = T>) -> T {
+    let start = Instant::now();
+    let res = f.await;
+
+    let _ = REQUEST.try_with(|r| {
+        r.db_micros
+            .fetch_add(start.elapsed().as_micros() as u64, Ordering::Relaxed)
+    });
+
+    res
+}
+
+#[derive(Debug, Default, Clone)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, secs: f64) {
+        for (i, le) in BUCKETS.iter().enumerate() {
+            if secs <= *le {
+                self.buckets[i] += 1;
+            }
+        }
+        self.sum += secs;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        for (i, le) in BUCKETS.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{},le=\"{}\"}} {}",
+                name, labels, le, self.buckets[i]
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{},le=\"+Inf\"}} {}",
+            name, labels, self.count
+        );
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    requests: BTreeMap<(String, String, u16), u64>,
+    request_latency: BTreeMap<String, Histogram>,
+    db_latency: BTreeMap<String, Histogram>,
+    webhooks: BTreeMap<(i64, &'static str), u64>,
+    webhook_latency: BTreeMap<i64, Histogram>,
+    auth: BTreeMap<(&'static str, &'static str), u64>,
+    cache: BTreeMap<(String, &'static str), u64>,
+    recent: BTreeMap<String, VecDeque<(Instant, bool)>>,
+}
+
+#[derive(Debug, Default)]
+pub struct Metrics {
+    registry: Mutex<Registry>,
+    access_log: Mutex<Option<mpsc::Sender<String>>>,
+}
+
+pub struct EndpointStats {
+    pub endpoint: String,
+    pub requests_per_sec: f64,
+    pub error_rate: f64,
+}
+
+impl Metrics {
+    fn record_request(&self, method: &str, endpoint: &str, status: u16, latency: f64, db: f64) {
+        let mut registry = self.registry.lock().unwrap();
+
+        *registry
+            .requests
+            .entry((endpoint.to_string(), method.to_string(), status))
+            .or_default() += 1;
+        registry
+            .request_latency
+            .entry(endpoint.to_string())
+            .or_default()
+            .observe(latency);
+        registry
+            .db_latency
+            .entry(endpoint.to_string())
+            .or_default()
+            .observe(db);
+
+        let now = Instant::now();
+        let recent = registry.recent.entry(endpoint.to_string()).or_default();
+        recent.push_back((now, status >= 500));
+        while recent
+            .front()
+            .is_some_and(|(t, _)| now.duration_since(*t) > STATS_WINDOW)
+        {
+            recent.pop_front();
+        }
+    }
+
+    pub fn record_webhook(&self, webhook_id: i64, success: bool, latency: Duration) {
+        let mut registry = self.registry.lock().unwrap();
+
+        let outcome = if success { "success" } else { "failure" };
+        *registry.webhooks.entry((webhook_id, outcome)).or_default() += 1;
+        registry
+            .webhook_latency
+            .entry(webhook_id)
+            .or_default()
+            .observe(latency.as_secs_f64());
+    }
+
+    pub fn record_auth(&self, action: &'static str, outcome: &'static str) {
+        let mut registry = self.registry.lock().unwrap();
+        *registry.auth.entry((action, outcome)).or_default() += 1;
+    }
+
+    pub fn record_cache(&self, endpoint: &str, hit: bool) {
+        let mut registry = self.registry.lock().unwrap();
+
+        let outcome = if hit { "hit" } else { "miss" };
+        *registry
+            .cache
+            .entry((endpoint.to_string(), outcome))
+            .or_default() += 1;
+    }
+
+    // hits and misses of every cached endpoint since the server started
+    pub fn cache_stats(&self) -> BTreeMap<String, (u64, u64)> {
+        let registry = self.registry.lock().unwrap();
+        let mut stats: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+
+        for ((endpoint, outcome), count) in registry.cache.iter() {
+            let entry = stats.entry(endpoint.clone()).or_default();
+            match *outcome {
+                "hit" => entry.0 += count,
+                _ => entry.1 += count,
+            }
+        }
+
+        stats
+    }
+
+    // request and error rate of every endpoint over the last minute
+    pub fn endpoint_stats(&self) -> Vec<EndpointStats> {
+        let registry = self.registry.lock().unwrap();
+        let now = Instant::now();
+
+        registry
+            .recent
+            .iter()
+            .map(|(endpoint, recent)| {
+                let (total, errors) = recent
+                    .iter()
+                    .filter(|(t, _)| now.duration_since(*t) <= STATS_WINDOW)
+                    .fold((0, 0), |(total, errors), (_, is_error)| {
+                        (total + 1, errors + *is_error as u64)
+                    });
+
+                EndpointStats {
+                    endpoint: endpoint.clone(),
+                    requests_per_sec: total as f64 / STATS_WINDOW.as_secs_f64(),
+                    error_rate: if total == 0 {
+                        0.0
+                    } else {
+                        errors as f64 / total as f64
+                    },
+                }
+            })
+            .collect()
+    }
+
+    pub fn render(&self) -> String {
+        let registry = self.registry.lock().unwrap();
+        let mut out = String::new();
+
+        out += "# TYPE minibase_requests_total counter\n";
+        for ((endpoint, method, status), count) in registry.requests.iter() {
+            let _ = writeln!(
+                out,
+                "minibase_requests_total{{endpoint=\"{}\",method=\"{}\",status=\"{}\"}} {}",
+                escape_label(endpoint),
+                method,
+                status,
+                count
+            );
+        }
+
+        out += "# TYPE minibase_request_duration_seconds histogram\n";
+        for (endpoint, h) in registry.request_latency.iter() {
+            let labels = format!("endpoint=\"{}\"", escape_label(endpoint));
+            h.render(&mut out, "minibase_request_duration_seconds", &labels);
+        }
+
+        out += "# TYPE minibase_db_duration_seconds histogram\n";
+        for (endpoint, h) in registry.db_latency.iter() {
+            let labels = format!("endpoint=\"{}\"", escape_label(endpoint));
+            h.render(&mut out, "minibase_db_duration_seconds", &labels);
+        }
+
+        out += "# TYPE minibase_webhook_calls_total counter\n";
+        for ((webhook_id, outcome), count) in registry.webhooks.iter() {
+            let _ = writeln!(
+                out,
+                "minibase_webhook_calls_total{{webhook_id=\"{}\",outcome=\"{}\"}} {}",
+                webhook_id, outcome, count
+            );
+        }
+
+        out += "# TYPE minibase_webhook_duration_seconds histogram\n";
+        for (webhook_id, h) in registry.webhook_latency.iter() {
+            let labels = format!("webhook_id=\"{}\"", webhook_id);
+            h.render(&mut out, "minibase_webhook_duration_seconds", &labels);
+        }
+
+        out += "# TYPE minibase_cache_total counter\n";
+        for ((endpoint, outcome), count) in registry.cache.iter() {
+            let _ = writeln!(
+                out,
+                "minibase_cache_total{{endpoint=\"{}\",outcome=\"{}\"}} {}",
+                escape_label(endpoint),
+                outcome,
+                count
+            );
+        }
+
+        out += "# TYPE minibase_auth_total counter\n";
+        for ((action, outcome), count) in registry.auth.iter() {
+            let _ = writeln!(
+                out,
+                "minibase_auth_total{{action=\"{}\",outcome=\"{}\"}} {}",
+                action, outcome, count
+            );
+        }
+
+        out
+    }
+
+    // the file is written by a background task so a slow disk never holds up a response,
+    // lines are dropped while its queue is full
+    fn write_access_log(&self, line: String) {
+        let mut sender = self.access_log.lock().unwrap();
+
+        // the writer stops with the runtime when the server is restarted
+        let closed = match sender.as_ref() {
+            Some(tx) => tx.is_closed(),
+            None => true,
+        };
+        if closed {
+            let (tx, rx) = mpsc::channel(ACCESS_LOG_QUEUE);
+            tokio::spawn(access_log_writer(PathBuf::from(ACCESS_LOG_FILE), rx));
+            *sender = Some(tx);
+        }
+
+        if let Some(tx) = sender.as_ref() {
+            let _ = tx.try_send(line);
+        }
+    }
+}
+
+async fn access_log_writer(path: PathBuf, mut rx: mpsc::Receiver<String>) {
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir).await;
+    }
+
+    let mut file = match fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .await
+    {
+        Ok(file) => file,
+        Err(e) => {
+            log::error!("unable to open access log: {}", e);
+            return;
+        }
+    };
+
+    while let Some(line) = rx.recv().await {
+        let mut lines = line + "\n";
+        // whatever queued up in the meantime goes out in the same write
+        while let Ok(line) = rx.try_recv() {
+            lines += &line;
+            lines += "\n";
+        }
+
+        if let Err(e) = file.write_all(lines.as_bytes()).await {
+            log::error!("unable to write access log: {}", e);
+        }
+        let _ = file.flush().await;
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// a client supplied id is kept so requests can be followed across services
+fn incoming_request_id(req: &Request<Body>) -> Option<String> {
+    req.headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= 64
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+        .map(|id| id.to_string())
+}
+
+pub async fn track_request(State(model): State<Model>, req: Request<Body>, next: Next) -> Response {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let path = req.uri().path().to_string();
+    let matched_path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string());
+
+    let context = Arc::new(RequestContext {
+        id: incoming_request_id(&req).unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+        endpoint: Mutex::new(None),
+        user_id: Mutex::new(None),
+        db_micros: AtomicU64::new(0),
+    });
+
+    let mut res = REQUEST.scope(context.clone(), next.run(req)).await;
+
+    let latency = start.elapsed();
+    let status = res.status().as_u16();
+    let db_time = Duration::from_micros(context.db_micros.load(Ordering::Relaxed));
+
+    // unmatched paths are grouped so random urls can not grow the label set
+    let endpoint = context
+        .endpoint
+        .lock()
+        .unwrap()
+        .clone()
+        .or(matched_path)
+        .unwrap_or("unmatched".to_string());
+
+    model.metrics.record_request(
+        &method,
+        &endpoint,
+        status,
+        latency.as_secs_f64(),
+        db_time.as_secs_f64(),
+    );
+
+    let line = json!({
+        "ts": Utc::now().to_rfc3339(),
+        "request_id": context.id,
+        "method": method,
+        "path": path,
+        "endpoint": endpoint,
+        "user_id": *context.user_id.lock().unwrap(),
+        "status": status,
+        "latency_ms": latency.as_secs_f64() * 1000.0,
+        "db_ms": db_time.as_secs_f64() * 1000.0,
+    });
+    model.metrics.write_access_log(line.to_string());
+
+    if let Ok(id) = HeaderValue::from_str(&context.id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, id);
+    }
+
+    res
+}
+
+// off until a metrics token is set in config, scrapers send it as a bearer token
+pub async fn metrics_handler(
+    State(model): State<Model>,
+    headers: HeaderMap,
+) -> Result<String, AppError> {
+    let token = &model.utils.metrics_token;
+    if token.is_empty() {
+        return Err(AppError::NotFound("metrics are disabled".to_string()));
+    }
+
+    let sent = headers
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    if sent != Some(token.as_str()) {
+        return Err(AppError::Unauthorized("invalid metrics token".to_string()));
+    }
+
+    Ok(model.metrics.render())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use axum::{
+        extract::State,
+        http::{header::AUTHORIZATION, HeaderMap},
+    };
+    use tokio::sync::mpsc;
+
+    use crate::{error::AppError, queries::Model};
+
+    use super::{access_log_writer, metrics_handler, time_db, Metrics, RequestContext, REQUEST};
+
+    #[test]
+    fn test1() {
+        let metrics = Metrics::default();
+
+        metrics.record_request("GET", "/api/todos", 200, 0.02, 0.01);
+        metrics.record_request("GET", "/api/todos", 500, 0.3, 0.0);
+        metrics.record_webhook(4, false, Duration::from_millis(120));
+        metrics.record_auth("login", "invalid_credentials");
+
+        let out = metrics.render();
+        assert!(out.contains(
+            "minibase_requests_total{endpoint=\"/api/todos\",method=\"GET\",status=\"500\"} 1"
+        ));
+        assert!(out.contains(
+            "minibase_request_duration_seconds_bucket{endpoint=\"/api/todos\",le=\"0.025\"} 1"
+        ));
+        assert!(out.contains("minibase_request_duration_seconds_count{endpoint=\"/api/todos\"} 2"));
+        assert!(
+            out.contains("minibase_webhook_calls_total{webhook_id=\"4\",outcome=\"failure\"} 1")
+        );
+        assert!(
+            out.contains("minibase_auth_total{action=\"login\",outcome=\"invalid_credentials\"} 1")
+        );
+
+        let stats = metrics.endpoint_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].error_rate, 0.5);
+    }
+
+    #[tokio::test]
+    async fn test2() {
+        let context = Arc::new(RequestContext {
+            id: "abc".to_string(),
+            endpoint: Default::default(),
+            user_id: Default::default(),
+            db_micros: Default::default(),
+        });
+
+        REQUEST
+            .scope(context.clone(), async {
+                assert_eq!(super::request_id(), Some("abc".to_string()));
+                time_db(tokio::time::sleep(Duration::from_millis(5))).await;
+                super::set_user_id(7);
+            })
+            .await;
+
+        assert!(context.db_micros.load(std::sync::atomic::Ordering::Relaxed) >= 5000);
+        assert_eq!(*context.user_id.lock().unwrap(), Some(7));
+        assert_eq!(super::request_id(), None);
+    }
+
+    #[tokio::test]
+    async fn test3() {
+        let path = std::env::temp_dir()
+            .join(format!("mini-base-{}", uuid::Uuid::new_v4()))
+            .join("access.log");
+
+        let (tx, rx) = mpsc::channel(8);
+        let writer = tokio::spawn(access_log_writer(path.clone(), rx));
+
+        tx.send("{\"status\":200}".to_string()).await.unwrap();
+        tx.send("{\"status\":404}".to_string()).await.unwrap();
+        drop(tx);
+        writer.await.unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "{\"status\":200}\n{\"status\":404}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test4() {
+        let dir = std::env::temp_dir().join(format!("mini-base-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut model = Model::with_config_path(dir.join("config").to_str().unwrap());
+
+        let res = metrics_handler(State(model.clone()), HeaderMap::new()).await;
+        assert!(matches!(res, Err(AppError::NotFound(_))));
+
+        model.utils.metrics_token = "scrape".to_string();
+        let res = metrics_handler(State(model.clone()), HeaderMap::new()).await;
+        assert!(matches!(res, Err(AppError::Unauthorized(_))));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, "Bearer scrape".parse().unwrap());
+        let res = metrics_handler(State(model), headers).await;
+        assert!(res.is_ok());
+    }
+}
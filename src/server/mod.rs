@@ -1,32 +1,56 @@
 use axum::{
     body::Body,
     extract::{Json, Path, Query, State},
-    http::{HeaderValue, Method, Request, StatusCode, Uri},
+    http::{HeaderMap, HeaderValue, Method, Request, Uri},
     middleware::{self, Next},
-    response::Response,
+    response::{IntoResponse, Response},
     routing::{delete, get, post, put},
     Extension, Router, ServiceExt,
 };
-use reqwest::header::HeaderMap;
 use serde_json::{json, Value};
-use std::{collections::HashMap, net::SocketAddr, str::FromStr};
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+    sync::Arc,
+};
 use tower::Layer;
 use tower_cookies::CookieManagerLayer;
 use tower_http::cors::{Any, CorsLayer};
 
 use crate::{
     database::model::ColType,
+    error::AppError,
     parser,
-    queries::{model::User, Model},
+    queries::{
+        model::{Route, User},
+        Model,
+    },
     server::utils::extract_type_from_string,
 };
 
-use self::auth::auth_middleware;
+use self::{
+    auth::auth_middleware,
+    metrics::time_db,
+    pagination::{Page, PageInfo},
+    stream::Format,
+    webhook::{apply_webhook_response, run_webhook},
+};
 
 mod auth;
+pub mod cache;
+pub mod metrics;
 pub mod model;
+mod pagination;
+mod realtime;
+mod rest;
+pub mod scheduler;
+mod shape;
 mod storage;
+mod stream;
+mod transform;
+pub mod tus;
 pub mod utils;
+mod webhook;
 
 #[tokio::main]
 pub async fn start_server(model: Model) {
@@ -49,9 +73,22 @@ pub async fn start_server(model: Model) {
 
     let app = Router::new()
         .route("/health", get(|| async { "Ok" }))
+        .route(
+            "/metrics",
+            get(metrics::metrics_handler).with_state(model.clone()),
+        )
         .nest("/auth", auth::generate_auth_routes(model.clone()))
         .nest("/storage", storage::generate_storage_routes(model.clone()))
         .nest("/api", generate_routes(model.clone()))
+        .nest("/rest", rest::generate_rest_routes(model.clone()))
+        .nest(
+            "/realtime",
+            realtime::generate_realtime_routes(model.clone()),
+        )
+        .layer(middleware::from_fn_with_state(
+            model.clone(),
+            metrics::track_request,
+        ))
         .layer(CookieManagerLayer::new())
         .layer(cors);
 
@@ -76,6 +113,15 @@ pub async fn start_server(model: Model) {
 
     let middleware = tower::util::MapRequestLayer::new(rewrite_request_uri);
 
+    if let Err(e) = model.refresh_routes().await {
+        log::error!("unable to load routes: {}", e);
+    }
+
+    tokio::spawn(webhook::run_outbox_worker(model.clone()));
+    tokio::spawn(webhook::run_change_dispatcher(model.clone()));
+    tokio::spawn(scheduler::run_scheduler(model.clone()));
+    tokio::spawn(cache::run_invalidator(model.clone()));
+
     let addr = SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 0], 3456));
     axum_server::bind(addr)
         .handle(model.handle.unwrap())
@@ -99,31 +145,35 @@ async fn name_middleware(
     Path(name): Path<String>,
     mut req: Request<Body>,
     next: Next,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, AppError> {
     let modified_name = &name.clone().replace('_', "/");
     log::info!("endpoint: {}", &modified_name);
 
-    let optional_query = model.get_query_by_name(modified_name).await;
+    let optional_route = model.get_route_by_name(modified_name);
 
-    match optional_query {
-        Ok(query) => {
-            req.extensions_mut().insert(model);
-            req.extensions_mut().insert(query.id);
+    match optional_route {
+        Some(route) => {
+            metrics::set_endpoint(format!("/api/{}", modified_name));
 
-            match (query.exec_type.as_ref(), req.method()) {
+            match (route.exec_type.as_ref(), req.method()) {
                 ("get", &Method::GET) => {}
                 ("post", &Method::POST) => {}
                 ("put", &Method::PUT) => {}
                 ("delete", &Method::DELETE) => {}
                 _ => {
                     log::error!("invalid endpoint method: {}", req.method());
-                    return Err(StatusCode::NOT_FOUND);
+                    return Err(AppError::NotFound("endpoint not found".to_string()));
                 }
             }
+
+            req.extensions_mut().insert(model);
+            req.extensions_mut().insert(route.id);
+            req.extensions_mut().insert(route.role_access.clone());
+            req.extensions_mut().insert(route);
         }
-        Err(e) => {
-            log::error!("invalid endpoint: {}", e);
-            return Err(StatusCode::NOT_FOUND);
+        None => {
+            log::error!("invalid endpoint: {}", modified_name);
+            return Err(AppError::NotFound("endpoint not found".to_string()));
         }
     }
 
@@ -132,16 +182,62 @@ async fn name_middleware(
 
 async fn get_handler(
     Extension(model): Extension<Model>,
-    Extension(query_id): Extension<i64>,
+    Extension(route): Extension<Arc<Route>>,
     Extension(user): Extension<Option<User>>,
+    headers: HeaderMap,
     Query(query): Query<HashMap<String, String>>,
-) -> (StatusCode, String) {
+) -> Result<Response, AppError> {
+    let format = stream::negotiate(&headers, &query).map_err(AppError::BadRequest)?;
+    let query_id = route.id;
+
+    let page = match format {
+        Format::Json => {
+            pagination::parse_page(&route.pagination, &query).map_err(AppError::BadRequest)?
+        }
+        _ => None,
+    };
+
+    // a page of rows could cut a group in half, so nested and single are never paged
+    if page.is_some() && route.shape.shape != "flat" {
+        return Err(AppError::BadRequest(
+            "pagination only works with the flat shape".to_string(),
+        ));
+    }
+
+    let cache_key = match format {
+        Format::Json if route.cache.cache_ttl > 0 => {
+            Some(cache::cache_key(&route, &query, user.as_ref()))
+        }
+        _ => None,
+    };
+
+    if let Some(key) = &cache_key {
+        let endpoint = format!("/api/{}", route.name);
+        let cached = model.cache.get(query_id, key);
+        model.metrics.record_cache(&endpoint, cached.is_some());
+
+        if let Some(output) = cached {
+            return Ok(output.into_response());
+        }
+    }
+    let generation = model.cache.generation();
+
     let mut json = json!({});
     for (key, val) in query {
         json[key] = extract_type_from_string(&val);
     }
 
-    handler(model, query_id, user, json).await
+    match format {
+        Format::Json => {
+            let output = handler(model.clone(), query_id, user, json, page).await?;
+            if let Some(key) = cache_key {
+                model.cache.insert(&route, key, output.clone(), generation);
+            }
+
+            Ok(output.into_response())
+        }
+        format => stream_handler(model, query_id, user, json, format).await,
+    }
 }
 
 async fn post_handler(
@@ -149,8 +245,8 @@ async fn post_handler(
     Extension(query_id): Extension<i64>,
     Extension(user): Extension<Option<User>>,
     Json(body): Json<Value>,
-) -> (StatusCode, String) {
-    handler(model, query_id, user, body).await
+) -> Result<QueryOutput, AppError> {
+    handler(model, query_id, user, body, None).await
 }
 
 async fn put_handler(
@@ -158,8 +254,8 @@ async fn put_handler(
     Extension(query_id): Extension<i64>,
     Extension(user): Extension<Option<User>>,
     Json(body): Json<Value>,
-) -> (StatusCode, String) {
-    handler(model, query_id, user, body).await
+) -> Result<QueryOutput, AppError> {
+    handler(model, query_id, user, body, None).await
 }
 
 async fn delete_handler(
@@ -167,206 +263,1114 @@ async fn delete_handler(
     Extension(query_id): Extension<i64>,
     Extension(user): Extension<Option<User>>,
     Json(body): Json<Value>,
-) -> (StatusCode, String) {
-    handler(model, query_id, user, body).await
+) -> Result<QueryOutput, AppError> {
+    handler(model, query_id, user, body, None).await
 }
 
-async fn handler(
-    model: Model,
+#[derive(Debug, Clone)]
+pub struct QueryOutput {
+    pub body: String,
+    pub truncated: bool,
+}
+
+// a result cut off at the query's max rows says so in a header
+impl IntoResponse for QueryOutput {
+    fn into_response(self) -> Response {
+        let mut response = self.body.into_response();
+        if self.truncated {
+            response
+                .headers_mut()
+                .insert("x-truncated", HeaderValue::from_static("true"));
+        }
+
+        response
+    }
+}
+
+struct PreparedQuery {
+    route: Arc<Route>,
+    query: String,
+    args: Vec<ColType>,
+    args_map: HashMap<String, Option<ColType>>,
+}
+
+// resolves the stored query against the request and runs the before webhooks
+async fn prepare_query(
+    model: &Model,
     query_id: i64,
     optional_user: Option<User>,
-    data: Value,
-) -> (StatusCode, String) {
-    let optional_query_string = model.get_query_string_by_id(query_id).await;
+    data: &Value,
+    with_webhooks: bool,
+) -> Result<PreparedQuery, AppError> {
+    let route = model
+        .get_route_by_id(query_id)
+        .ok_or(AppError::NotFound("query not found".to_string()))?;
 
-    match optional_query_string {
-        Ok(query_string) => {
-            let (_, params) = parser::parse_query(&query_string.query).unwrap();
+    let parsed_params = route
+        .params
+        .as_ref()
+        .map_err(|e| AppError::Internal(format!("unable to parse query {}: {}", query_id, e)))?;
 
-            let parsed_query =
-                parser::replace_variables_in_query(&query_string.query, params.clone());
+    let mut args_map = HashMap::new();
 
-            let parsed_params = params
-                .into_iter()
-                .map(|p| p.to_string())
-                .collect::<Vec<String>>();
+    for p in parsed_params.iter() {
+        if !p.starts_with('.') && !p.starts_with("wh.") {
+            let d = data
+                .get(p)
+                .map(|p| ColType::get_col_type_from_value(p.clone()));
+
+            args_map.insert(p.clone(), d);
+        }
+    }
 
-            let mut args_map = HashMap::new();
+    if let Some(user) = &optional_user {
+        args_map.insert(
+            ".USER_ID".to_string(),
+            Some(ColType::Integer(Some(user.id))),
+        );
+        args_map.insert(
+            ".USER_EMAIL".to_string(),
+            Some(ColType::String(Some(user.email.clone()))),
+        );
+        args_map.insert(
+            ".USER_ROLE".to_string(),
+            Some(ColType::String(user.role.clone())),
+        );
+    }
+
+    let webhooks = match with_webhooks {
+        true => route.webhooks.as_slice(),
+        false => &[],
+    };
 
-            let args = parsed_params
-                .clone()
+    let wb = run_webhook(model.clone(), args_map.clone(), webhooks, "before").await;
+    match wb {
+        Ok(responses) => {
+            let wh = responses
                 .into_iter()
-                .filter_map(|p| {
-                    if p.starts_with('.') && optional_user.is_some() {
-                        match &optional_user {
-                            Some(user) => {
-                                let map = HashMap::from([
-                                    (String::from(".USER_ID"), ColType::Integer(Some(user.id))),
-                                    (
-                                        String::from(".USER_EMAIL"),
-                                        ColType::String(Some(user.email.clone())),
-                                    ),
-                                    (
-                                        String::from(".USER_ROLE"),
-                                        ColType::String(user.role.clone()),
-                                    ),
-                                ]);
-                                map.get(&p.to_uppercase()).cloned()
-                            }
-                            None => None,
-                        }
-                    } else {
-                        let d = data
-                            .get(&p)
-                            .map(|p| ColType::get_col_type_from_value(p.clone()));
-
-                        args_map.insert(p, d.clone());
-                        d
-                    }
+                .map(|(webhook, body)| {
+                    (
+                        webhook.name,
+                        Box::new(ColType::get_col_type_from_value(body)),
+                    )
                 })
-                .collect::<Vec<ColType>>();
-
-            if let Some(user) = optional_user {
-                args_map.insert(
-                    ".USER_ID".to_string(),
-                    Some(ColType::Integer(Some(user.id))),
-                );
-                args_map.insert(
-                    ".USER_EMAIL".to_string(),
-                    Some(ColType::String(Some(user.email.clone()))),
-                );
-                args_map.insert(
-                    ".USER_ROLE".to_string(),
-                    Some(ColType::String(user.role.clone())),
-                );
-            }
+                .collect();
 
-            let wb = run_webhook(model.clone(), args_map.clone(), query_id, "before").await;
-            if let Err(w) = wb {
-                return w;
-            }
+            args_map.insert("wh".to_string(), Some(ColType::Object(Some(wh))));
+        }
+        Err(e) => return Err(e),
+    }
 
-            let res = run_query(model.clone(), parsed_query, args).await;
-            if res.0 == StatusCode::BAD_REQUEST {
-                return (StatusCode::BAD_REQUEST, res.1);
-            }
+    let lookup = |p: &str| {
+        if p.starts_with('.') && optional_user.is_none() {
+            data.get(p)
+                .map(|p| ColType::get_col_type_from_value(p.clone()))
+        } else if p.starts_with('.') {
+            parser::get_variable_value(&args_map, &p.to_uppercase())
+        } else {
+            parser::get_variable_value(&args_map, p)
+        }
+    };
 
-            let res_args = serde_json::from_str::<Value>(&res.1).unwrap();
+    // only params inside [[ ]] blocks may be left out, a null still has to be sent
+    let required =
+        parser::resolve_optional_blocks(&route.query, |_| false).map_err(AppError::Internal)?;
+    if let Ok((_, required)) = parser::parse_query(&required) {
+        let missing = required
+            .into_iter()
+            .find(|p| !p.starts_with('.') && !p.starts_with("wh.") && lookup(p).is_none());
+
+        if let Some(p) = missing {
+            return Err(AppError::BadRequest(format!("missing param: {}", p)));
+        }
+    }
 
-            let d = ColType::get_col_type_from_value(res_args);
-            args_map.insert("res".to_string(), Some(d));
+    // identifier placeholders aren't bound, so they are only found in the body
+    let query = parser::resolve_optional_blocks(&route.query, |p| {
+        !matches!(lookup(p), None | Some(ColType::Null))
+            || !matches!(data.get(p), None | Some(Value::Null))
+    })
+    .map_err(AppError::Internal)?;
 
-            let wa = run_webhook(model.clone(), args_map, query_id, "after").await;
-            if let Err(w) = wa {
-                return w;
+    let mut identifiers = HashMap::new();
+    for (name, _) in parser::get_identifier_placeholders(&query).map_err(AppError::Internal)? {
+        match data.get(&name) {
+            None | Some(Value::Null) => {}
+            Some(Value::String(v)) => {
+                identifiers.insert(name, v.clone());
             }
+            Some(_) => return Err(AppError::BadRequest(format!("{} must be a string", name))),
+        }
+    }
+    let query = parser::replace_identifier_placeholders(&query, &identifiers)
+        .map_err(AppError::BadRequest)?;
+
+    let params = match parser::parse_query(&query) {
+        Ok((_, params)) => params,
+        Err(e) => {
+            return Err(AppError::Internal(format!(
+                "unable to parse query {}: {}",
+                query_id, e
+            )))
+        }
+    };
+    let in_lists = parser::get_in_list_variables(&query, &params);
+
+    // a param left out of a kept [[ ]] block binds NULL so the rest stay lined up
+    let args = params
+        .iter()
+        .map(|p| lookup(p).unwrap_or(ColType::Null))
+        .collect::<Vec<ColType>>();
+
+    let (args, list_lengths) = expand_in_lists(args, &in_lists, model.utils.max_in_list)?;
+    let query = parser::replace_variables_in_query(&query, params, &list_lengths);
 
-            res
+    Ok(PreparedQuery {
+        route,
+        query,
+        args,
+        args_map,
+    })
+}
+
+async fn handler(
+    model: Model,
+    query_id: i64,
+    optional_user: Option<User>,
+    data: Value,
+    page: Option<Page>,
+) -> Result<QueryOutput, AppError> {
+    let PreparedQuery {
+        route,
+        query,
+        args,
+        mut args_map,
+    } = prepare_query(&model, query_id, optional_user, &data, true).await?;
+
+    let (mut res_json, page_info, truncated) =
+        run_shaped_query(&model, &route, query, args, page).await?;
+
+    let d = ColType::get_col_type_from_value(res_json.clone());
+    args_map.insert("res".to_string(), Some(d));
+
+    let responses = run_webhook(model.clone(), args_map, &route.webhooks, "after").await?;
+    for (webhook, body) in responses {
+        if webhook.is_returned {
+            res_json = apply_webhook_response(res_json, &webhook.return_mode, body);
         }
-        Err(e) => (StatusCode::NOT_FOUND, e),
     }
+
+    let body = match page_info {
+        Some(info) => info.envelope(res_json).to_string(),
+        None => res_json.to_string(),
+    };
+
+    Ok(QueryOutput { body, truncated })
 }
 
-async fn run_webhook(
+// realtime refreshes skip the webhooks, a subscriber must not cause outgoing calls
+async fn refresh_handler(
     model: Model,
-    args_map: HashMap<String, Option<ColType>>,
     query_id: i64,
-    action_type: &str,
-) -> Result<HashMap<String, Value>, (StatusCode, String)> {
-    let optional_webhooks = model.get_all_webhook_query_by_query_id(query_id).await;
-
-    match optional_webhooks {
-        Ok(webhooks) => {
-            let res_map = HashMap::new();
-            for webhook in webhooks {
-                if webhook.action != action_type {
-                    continue;
-                }
+    optional_user: Option<User>,
+    data: Value,
+) -> Result<String, AppError> {
+    let PreparedQuery {
+        route, query, args, ..
+    } = prepare_query(&model, query_id, optional_user, &data, false).await?;
 
-                let client = reqwest::Client::new();
+    let (res_json, _, _) = run_shaped_query(&model, &route, query, args, None).await?;
 
-                let mut builder = match webhook.exec_type.as_str() {
-                    "get" => client.get(webhook.url),
-                    "post" => client.post(webhook.url),
-                    "put" => client.put(webhook.url),
-                    "delete" => client.delete(webhook.url),
-                    _ => return Err((StatusCode::BAD_REQUEST, "invalid type".to_string())),
-                };
+    Ok(res_json.to_string())
+}
 
-                let mut webhook_args = webhook.args.clone();
-                webhook_args =
-                    parser::replace_variables_with_values(&webhook_args, args_map.clone());
+async fn run_shaped_query(
+    model: &Model,
+    route: &Route,
+    query: String,
+    args: Vec<ColType>,
+    page: Option<Page>,
+) -> Result<(Value, Option<PageInfo>, bool), AppError> {
+    let query_shape = &route.shape;
 
-                let args: Value = serde_json::from_str(&webhook_args).unwrap_or(Value::default());
+    let (rows, page_info, truncated) = match page {
+        Some(page) => {
+            let (rows, info) = pagination::run_paged_query(model, &query, args, page)
+                .await
+                .map_err(AppError::from_db)?;
+            (rows, Some(info), false)
+        }
+        None => {
+            let max_rows = query_shape.max_rows as usize;
+            let (rows, truncated) = run_query(model.clone(), query, args, max_rows).await?;
+            (rows, None, truncated)
+        }
+    };
 
-                let header = &args["header"];
-                let query = &args["query"];
-                let body = &args["body"];
+    let written_tables = parser::get_written_tables(&route.query);
+    if !written_tables.is_empty() {
+        // invalidated right away so the writer's next read is fresh
+        model.cache.invalidate(&written_tables);
+        // no receivers just means nobody is subscribed
+        let _ = model.changes.send(written_tables);
+    }
 
-                let mut headermap = HeaderMap::new();
-                if let Some(h) = header.as_object() {
-                    for (k, v) in h.iter() {
-                        let key = reqwest::header::HeaderName::from_str(k);
-                        let val = reqwest::header::HeaderValue::from_str(v.as_str().unwrap_or(""));
+    let res = shape::shape_rows(rows, query_shape).map_err(AppError::Internal)?;
+    if query_shape.shape == "single" && matches!(res, ColType::Null) {
+        return Err(AppError::NotFound("no row found".to_string()));
+    }
 
-                        if let (Ok(k), Ok(v)) = (key, val) {
-                            headermap.insert(k, v);
-                        }
-                    }
-                }
+    let res_json = serde_json::to_value(&res).map_err(|e| AppError::Internal(e.to_string()))?;
 
-                if !headermap.is_empty() {
-                    builder = builder.headers(headermap);
-                }
-                if !query.is_null() {
-                    builder = builder.query(query);
-                }
-                if !body.is_null() {
-                    builder = builder.json(body);
-                }
+    Ok((res_json, page_info, truncated))
+}
 
-                match builder.send().await {
-                    Ok(res) => {
-                        // TODO: might able to get data here and send it to res_map for use in query
-                        if webhook.is_returned {
-                            if let Err(e) = res.error_for_status() {
-                                let mut err_code = StatusCode::BAD_REQUEST;
-                                if let Some(s) = e.status() {
-                                    err_code = StatusCode::from_u16(s.as_u16())
-                                        .unwrap_or(StatusCode::BAD_REQUEST);
-                                }
-                                return Err((err_code, e.without_url().to_string()));
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        if webhook.is_returned {
-                            return Err((StatusCode::BAD_REQUEST, e.to_string()));
-                        }
-                    }
+// ndjson and csv write the flat rows as they are fetched, so shaping, pagination and
+// the `res` of after webhooks only apply to json
+async fn stream_handler(
+    model: Model,
+    query_id: i64,
+    optional_user: Option<User>,
+    data: Value,
+    format: Format,
+) -> Result<Response, AppError> {
+    let PreparedQuery {
+        route,
+        query,
+        args,
+        args_map,
+    } = prepare_query(&model, query_id, optional_user, &data, true).await?;
+    let query_shape = &route.shape;
+
+    let rx = model.conn.as_ref().unwrap().query_stream(query, args);
+    let response = stream::stream_rows(rx, format, query_shape.max_rows as usize).await?;
+
+    let written_tables = parser::get_written_tables(&route.query);
+    if !written_tables.is_empty() {
+        model.cache.invalidate(&written_tables);
+        let _ = model.changes.send(written_tables);
+    }
+
+    run_webhook(model, args_map, &route.webhooks, "after").await?;
+
+    Ok(response)
+}
+
+// arrays used as `IN (${ids})` bind one placeholder per element
+fn expand_in_lists(
+    args: Vec<ColType>,
+    in_lists: &[bool],
+    max_len: usize,
+) -> Result<(Vec<ColType>, Vec<Option<usize>>), AppError> {
+    let mut expanded = vec![];
+    let mut list_lengths = vec![];
+
+    for (arg, in_list) in args.into_iter().zip(in_lists) {
+        match arg {
+            ColType::Array(Some(items)) if *in_list => {
+                if items.len() > max_len {
+                    return Err(AppError::BadRequest(format!(
+                        "list has {} values, at most {} are allowed",
+                        items.len(),
+                        max_len
+                    )));
                 }
+
+                list_lengths.push(Some(items.len()));
+                expanded.extend(items);
+            }
+            arg => {
+                list_lengths.push(None);
+                expanded.push(arg);
             }
-            Ok(res_map)
         }
-        Err(e) => Err((StatusCode::BAD_REQUEST, e)),
     }
+
+    Ok((expanded, list_lengths))
 }
 
 async fn run_query(
     model: Model,
     query: String,
     args: Vec<ColType>,
-) -> (StatusCode, std::string::String) {
-    let optional_rows = model.conn.as_ref().unwrap().query_all(&query, args).await;
+    max_rows: usize,
+) -> Result<(Vec<BTreeMap<String, ColType>>, bool), AppError> {
+    let mut rx = model.conn.as_ref().unwrap().query_stream(query, args);
+
+    // one row past max_rows tells a cut off result from an exact fit, dropping the
+    // receiver then stops the fetch
+    time_db(async {
+        let mut rows = vec![];
+        while let Some(row) = rx.recv().await {
+            let row = row.map_err(AppError::from_db)?;
+            if max_rows > 0 && rows.len() == max_rows {
+                return Ok((rows, true));
+            }
+
+            rows.push(row.into_iter().collect());
+        }
+
+        Ok((rows, false))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Request, StatusCode},
+        Router,
+    };
+    use serde_json::json;
+    use tower::ServiceExt;
+    use tower_cookies::CookieManagerLayer;
+
+    use crate::{
+        database::{
+            model::{ColType, DbType},
+            sqlite::Sqlite,
+            Conn,
+        },
+        queries::{
+            model::{
+                Query, QueryCache, QueryPagination, QueryShape, QueryString, RoleAccess, Webhook,
+                WebhookQuery,
+            },
+            Model,
+        },
+    };
+
+    use super::{auth, generate_routes, handler, refresh_handler, rest, utils::hash_password};
+
+    async fn test_model() -> Model {
+        let dir = std::env::temp_dir().join(format!("mini-base-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut model = Model::with_config_path(dir.join("config").to_str().unwrap());
+        let sqlite = Sqlite::new(dir.join("test.db").to_str().unwrap()).await;
+        model.conn = Some(Conn {
+            dbtype: DbType::Sqlite,
+            err: sqlite.connection.clone().err(),
+            sqlite: Some(sqlite),
+            mysql: None,
+        });
+
+        let id = model.add_new_query("todos".to_string()).await.unwrap();
+        model
+            .edit_query(Query {
+                id,
+                name: "todos".to_string(),
+                exec_type: "get".to_string(),
+            })
+            .await
+            .unwrap();
+        model
+            .edit_query_string(id, "SELECT id, name FROM queries".to_string())
+            .await
+            .unwrap();
+
+        model
+            .create_user(
+                "admin@example.com".to_string(),
+                hash_password("pw".to_string()),
+            )
+            .await
+            .unwrap();
+
+        model
+    }
+
+    fn app(model: Model) -> Router {
+        Router::new()
+            .nest("/auth", auth::generate_auth_routes(model.clone()))
+            .nest("/api", generate_routes(model.clone()))
+            .nest("/rest", rest::generate_rest_routes(model))
+            .layer(CookieManagerLayer::new())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test1() {
+        let model = test_model().await;
 
-    match optional_rows {
-        Ok(rows) => {
-            let out = model.conn.as_ref().unwrap().parse_all(rows);
+        let res = app(model.clone())
+            .oneshot(Request::get("/api/todos").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        for uri in [
+            "/api/x'%20OR%20'1'='1",
+            "/api/x'%20UNION%20SELECT%20id,%20name,%20'get'%20FROM%20queries%20--",
+            "/rest/todos'%20--",
+        ] {
+            let res = app(model.clone())
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::NOT_FOUND, "{}", uri);
+        }
+
+        assert!(model.get_tus_upload_by_id("x' OR '1'='1").await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test2() {
+        let model = test_model().await;
+
+        let login = |email: &str| {
+            Request::post("/auth/login")
+                .header("Content-Type", "application/json")
+                .body(Body::from(
+                    json!({ "email": email, "password": "pw" }).to_string(),
+                ))
+                .unwrap()
+        };
+
+        let res = app(model.clone())
+            .oneshot(login("x' OR '1'='1"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("admin@example.com"));
+
+        let res = app(model)
+            .oneshot(login("admin@example.com"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test3() {
+        let model = test_model().await;
+
+        let id = model.add_new_query("byids".to_string()).await.unwrap();
+        model
+            .edit_query(Query {
+                id,
+                name: "byids".to_string(),
+                exec_type: "post".to_string(),
+            })
+            .await
+            .unwrap();
+        model
+            .edit_query_string(
+                id,
+                "SELECT id FROM queries WHERE id IN (${ids}) ORDER BY id".to_string(),
+            )
+            .await
+            .unwrap();
+
+        let post = |body: serde_json::Value| {
+            Request::post("/api/byids")
+                .header("Content-Type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        for (ids, expected) in [
+            (json!([id, 1, 999]), json!([{"id": 1}, {"id": id}])),
+            (json!([]), json!([])),
+        ] {
+            let res = app(model.clone())
+                .oneshot(post(json!({ "ids": ids })))
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+            let rows: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(rows, expected);
+        }
+
+        let res = app(model.clone())
+            .oneshot(post(json!({ "ids": vec![1; 1001] })))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        model
+            .edit_query_string(
+                id,
+                "SELECT id FROM queries WHERE id NOT IN (${ids}) ORDER BY id".to_string(),
+            )
+            .await
+            .unwrap();
+
+        for (ids, expected) in [
+            (json!([id]), json!([{"id": 1}])),
+            (json!([]), json!([{"id": 1}, {"id": id}])),
+        ] {
+            let res = app(model.clone())
+                .oneshot(post(json!({ "ids": ids })))
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+            let rows: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(rows, expected);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test4() {
+        let model = test_model().await;
+
+        let id = model.add_new_query("search".to_string()).await.unwrap();
+        model
+            .edit_query(Query {
+                id,
+                name: "search".to_string(),
+                exec_type: "post".to_string(),
+            })
+            .await
+            .unwrap();
+        model
+            .edit_query_string(
+                id,
+                "SELECT name FROM queries WHERE 1=1[[ AND name=${name}]][[ AND id=${id}]] ORDER BY id"
+                    .to_string(),
+            )
+            .await
+            .unwrap();
+
+        for (body, expected) in [
+            (json!({}), json!([{"name": "todos"}, {"name": "search"}])),
+            (json!({ "name": "todos" }), json!([{"name": "todos"}])),
             (
-                StatusCode::OK,
-                serde_json::to_string(&out.unwrap()).unwrap(),
+                json!({ "id": id, "name": null }),
+                json!([{"name": "search"}]),
+            ),
+        ] {
+            let res = app(model.clone())
+                .oneshot(
+                    Request::post("/api/search")
+                        .header("Content-Type", "application/json")
+                        .body(Body::from(body.to_string()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+            let rows: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(rows, expected);
+        }
+
+        model
+            .edit_query_string(
+                id,
+                "SELECT name FROM queries WHERE id=${id}[[ AND name=${name}]]".to_string(),
             )
+            .await
+            .unwrap();
+
+        for (body, status) in [
+            (json!({}), StatusCode::BAD_REQUEST),
+            (json!({ "name": "search" }), StatusCode::BAD_REQUEST),
+            (json!({ "id": null }), StatusCode::OK),
+            (json!({ "id": id }), StatusCode::OK),
+        ] {
+            let res = app(model.clone())
+                .oneshot(
+                    Request::post("/api/search")
+                        .header("Content-Type", "application/json")
+                        .body(Body::from(body.to_string()))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(res.status(), status, "{}", body);
         }
-        Err(e) => (StatusCode::BAD_REQUEST, e),
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test5() {
+        let model = test_model().await;
+
+        let id = model.add_new_query("sorted".to_string()).await.unwrap();
+        model
+            .edit_query(Query {
+                id,
+                name: "sorted".to_string(),
+                exec_type: "get".to_string(),
+            })
+            .await
+            .unwrap();
+        model
+            .edit_query_string(
+                id,
+                "SELECT name FROM queries ORDER BY ${@sort in id, name}".to_string(),
+            )
+            .await
+            .unwrap();
+
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        for (uri, expected) in [
+            (
+                "/api/sorted",
+                json!([{"name": "todos"}, {"name": "sorted"}]),
+            ),
+            (
+                "/api/sorted?sort=id%20desc",
+                json!([{"name": "sorted"}, {"name": "todos"}]),
+            ),
+            (
+                "/api/sorted?sort=name",
+                json!([{"name": "sorted"}, {"name": "todos"}]),
+            ),
+        ] {
+            let res = app(model.clone()).oneshot(get(uri)).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK, "{}", uri);
+
+            let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+            let rows: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(rows, expected, "{}", uri);
+        }
+
+        for uri in [
+            "/api/sorted?sort=email",
+            "/api/sorted?sort=(SELECT%20password%20FROM%20users)",
+        ] {
+            let res = app(model.clone()).oneshot(get(uri)).await.unwrap();
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{}", uri);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test6() {
+        let model = test_model().await;
+
+        let id = model.add_new_query("detail".to_string()).await.unwrap();
+        model
+            .edit_query(Query {
+                id,
+                name: "detail".to_string(),
+                exec_type: "get".to_string(),
+            })
+            .await
+            .unwrap();
+        model
+            .edit_query_string(
+                id,
+                "SELECT q.id, q.name AS \"info.name\", o.name AS \"others[].name\" FROM queries q LEFT JOIN queries o ON o.id <> q.id WHERE q.id=${id}"
+                    .to_string(),
+            )
+            .await
+            .unwrap();
+        model
+            .edit_query_shape(
+                id,
+                QueryShape {
+                    shape: "single".to_string(),
+                    group_by: "id".to_string(),
+                    max_rows: 0,
+                },
+            )
+            .await
+            .unwrap();
+
+        let get = |uri: String| Request::get(uri).body(Body::empty()).unwrap();
+
+        let res = app(model.clone())
+            .oneshot(get(format!("/api/detail?id={}", id)))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let row: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(row["id"], id);
+        assert_eq!(row["info"], json!({"name": "detail"}));
+        assert_eq!(row["others"], json!([{"name": "todos"}]));
+
+        let res = app(model.clone())
+            .oneshot(get("/api/detail?id=9999".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        model
+            .edit_query_pagination(
+                id,
+                QueryPagination {
+                    pagination: "offset".to_string(),
+                    page_size: 10,
+                    cursor_column: "".to_string(),
+                    with_total: false,
+                },
+            )
+            .await
+            .unwrap();
+
+        let res = app(model.clone())
+            .oneshot(get(format!("/api/detail?id={}", id)))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test7() {
+        let model = test_model().await;
+
+        let id = model.add_new_query("export".to_string()).await.unwrap();
+        model
+            .edit_query(Query {
+                id,
+                name: "export".to_string(),
+                exec_type: "get".to_string(),
+            })
+            .await
+            .unwrap();
+        model
+            .edit_query_string(
+                id,
+                "SELECT name, id, name FROM queries ORDER BY id".to_string(),
+            )
+            .await
+            .unwrap();
+
+        let res = app(model.clone())
+            .oneshot(
+                Request::get("/api/export?format=ndjson")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()["content-type"], "application/x-ndjson");
+
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let lines = String::from_utf8(body.to_vec()).unwrap();
+        let rows = lines
+            .lines()
+            .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+            .collect::<Vec<serde_json::Value>>();
+        assert_eq!(rows[1], json!({"id": id, "name": "export"}));
+
+        let res = app(model.clone())
+            .oneshot(
+                Request::get("/api/export")
+                    .header("Accept", "text/csv")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            format!("name,id,name\r\ntodos,1,todos\r\nexport,{},export\r\n", id)
+        );
+
+        let res = app(model.clone())
+            .oneshot(Request::get("/api/export").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert!(res.headers().get("x-truncated").is_none());
+
+        model
+            .edit_query_shape(
+                id,
+                QueryShape {
+                    shape: "flat".to_string(),
+                    group_by: "".to_string(),
+                    max_rows: 1,
+                },
+            )
+            .await
+            .unwrap();
+
+        for (uri, expected) in [
+            ("/api/export", "[{\"id\":1,\"name\":\"todos\"}]"),
+            (
+                "/api/export?format=csv",
+                "name,id,name\r\ntodos,1,todos\r\n",
+            ),
+        ] {
+            let res = app(model.clone())
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::OK, "{}", uri);
+            if uri == "/api/export" {
+                assert_eq!(res.headers()["x-truncated"], "true");
+            }
+
+            let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+            assert_eq!(
+                String::from_utf8(body.to_vec()).unwrap(),
+                expected,
+                "{}",
+                uri
+            );
+        }
+
+        let res = app(model.clone())
+            .oneshot(
+                Request::get("/api/export?format=xml")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test8() {
+        let model = test_model().await;
+
+        let id = model.add_new_query("moved".to_string()).await.unwrap();
+        model
+            .edit_query_string(id, "SELECT 1 AS one".to_string())
+            .await
+            .unwrap();
+
+        let get = || Request::get("/api/moved").body(Body::empty()).unwrap();
+
+        let res = app(model.clone()).oneshot(get()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        model
+            .edit_query(Query {
+                id,
+                name: "moved".to_string(),
+                exec_type: "post".to_string(),
+            })
+            .await
+            .unwrap();
+        let res = app(model.clone()).oneshot(get()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        model.delete_query(id).await.unwrap();
+        assert!(model.get_route_by_name("moved").is_none());
+        assert!(model.get_route_by_id(id).is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test9() {
+        let model = test_model().await;
+        let conn = model.conn.as_ref().unwrap();
+        conn.execute(
+            "CREATE TABLE items(id INTEGER PRIMARY KEY, name TEXT)",
+            vec![],
+        )
+        .await
+        .unwrap();
+
+        for (name, exec_type, query) in [
+            ("items", "get", "SELECT name FROM items ORDER BY id"),
+            ("additem", "post", "INSERT INTO items(name) VALUES(${name})"),
+        ] {
+            let id = model.add_new_query(name.to_string()).await.unwrap();
+            model
+                .edit_query(Query {
+                    id,
+                    name: name.to_string(),
+                    exec_type: exec_type.to_string(),
+                })
+                .await
+                .unwrap();
+            model
+                .edit_query_string(id, query.to_string())
+                .await
+                .unwrap();
+        }
+
+        let id = model.get_route_by_name("items").unwrap().id;
+        model
+            .edit_query_cache(
+                id,
+                QueryCache {
+                    cache_ttl: 60,
+                    cache_params: "".to_string(),
+                    cache_per_user: false,
+                    cache_max_entries: 10,
+                },
+            )
+            .await
+            .unwrap();
+
+        let get = |model: Model| async move {
+            let res = app(model)
+                .oneshot(Request::get("/api/items").body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        assert_eq!(get(model.clone()).await, json!([]));
+
+        // written behind the server's back, the cached response is still served
+        conn.execute("INSERT INTO items(name) VALUES('a')", vec![])
+            .await
+            .unwrap();
+        assert_eq!(get(model.clone()).await, json!([]));
+
+        let res = app(model.clone())
+            .oneshot(
+                Request::post("/api/additem")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(json!({"name": "b"}).to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        assert_eq!(
+            get(model.clone()).await,
+            json!([{"name": "a"}, {"name": "b"}])
+        );
+        assert_eq!(model.metrics.cache_stats()["/api/items"], (1, 2));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test10() {
+        let model = test_model().await;
+        let id = model.get_route_by_name("todos").unwrap().id;
+
+        let webhook_id = model.add_new_webhook("notify".to_string()).await.unwrap();
+        model
+            .edit_webhook(Webhook {
+                id: webhook_id,
+                name: "notify".to_string(),
+                exec_type: "post".to_string(),
+                action: "after".to_string(),
+                url: "http://localhost:1".to_string(),
+                args: "{}".to_string(),
+                is_returned: false,
+                return_mode: "replace".to_string(),
+                secret: "".to_string(),
+            })
+            .await
+            .unwrap();
+        model
+            .edit_webhook_query(
+                id,
+                vec![WebhookQuery {
+                    id: webhook_id,
+                    name: "notify".to_string(),
+                    is_connected: true,
+                }],
+            )
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            refresh_handler(model.clone(), id, None, json!({}))
+                .await
+                .unwrap();
+        }
+        let outbox = model.get_due_webhook_outbox(i64::MAX, 10).await.unwrap();
+        assert!(outbox.is_empty());
+
+        handler(model.clone(), id, None, json!({}), None)
+            .await
+            .unwrap();
+        let outbox = model.get_due_webhook_outbox(i64::MAX, 10).await.unwrap();
+        assert_eq!(outbox.len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test11() {
+        let model = test_model().await;
+        let conn = model.conn.as_ref().unwrap();
+
+        for query in [
+            "CREATE TABLE notes(id INTEGER PRIMARY KEY, body TEXT)",
+            "CREATE TABLE tokens(token TEXT PRIMARY KEY, body TEXT)",
+            "INSERT INTO tokens(token, body) VALUES('5abc', 'x')",
+        ] {
+            conn.execute(query, vec![]).await.unwrap();
+        }
+        model.add_rest_table("notes".to_string()).await.unwrap();
+        model.add_rest_table("tokens".to_string()).await.unwrap();
+
+        for (uri, status) in [
+            ("/rest/notes/5abc", StatusCode::BAD_REQUEST),
+            ("/rest/notes?id=1.2.3", StatusCode::BAD_REQUEST),
+            ("/rest/tokens/5abc", StatusCode::OK),
+            ("/api/todos?id=1.2.3", StatusCode::OK),
+        ] {
+            let res = app(model.clone())
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(res.status(), status, "{}", uri);
+        }
+    }
+
+    // cargo test bench_routes -- --ignored --nocapture
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn bench_routes() {
+        let model = test_model().await;
+        let conn = model.conn.as_ref().unwrap();
+        let runs = 1000;
+
+        let start = std::time::Instant::now();
+        for _ in 0..runs {
+            let name = vec![ColType::String(Some("todos".to_string()))];
+            let query = conn
+                .query_one_with_type::<Query>(
+                    "SELECT id, name, exec_type FROM queries WHERE name=?",
+                    name,
+                )
+                .await
+                .unwrap();
+
+            let id = vec![ColType::Integer(Some(query.id))];
+            conn.query_all_with_type::<RoleAccess>(
+                "SELECT role_id FROM role_access WHERE query_id=?",
+                id.clone(),
+            )
+            .await
+            .unwrap();
+            conn.query_one_with_type::<QueryString>(
+                "SELECT query FROM queries WHERE id=?",
+                id.clone(),
+            )
+            .await
+            .unwrap();
+
+            for _ in 0..2 {
+                conn.query_all_with_type::<Webhook>(
+                    "SELECT id, name, exec_type, action, url, args, is_returned, return_mode, secret FROM webhooks 
+                    INNER JOIN webhook_query ON webhooks.id = webhook_id WHERE query_id=?",
+                    id.clone(),
+                )
+                .await
+                .unwrap();
+            }
+        }
+        let lookups = start.elapsed() / runs;
+
+        let start = std::time::Instant::now();
+        for _ in 0..runs {
+            let route = model.get_route_by_name("todos").unwrap();
+            model.get_route_by_id(route.id).unwrap();
+        }
+        let routes = start.elapsed() / runs;
+
+        println!(
+            "system table lookups: {:?}, route table: {:?}",
+            lookups, routes
+        );
+        assert!(routes < lookups);
     }
 }
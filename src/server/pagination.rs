@@ -0,0 +1,281 @@
+use std::collections::{BTreeMap, HashMap};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde_json::{json, Value};
+
+use crate::{
+    database::model::ColType,
+    queries::{
+        model::QueryPagination, schema::is_valid_identifier, table::quote_identifier, Model,
+    },
+};
+
+pub enum PageMode {
+    Offset(i64),
+    Cursor {
+        column: String,
+        after: Option<ColType>,
+    },
+}
+
+pub struct Page {
+    pub mode: PageMode,
+    pub limit: i64,
+    pub with_total: bool,
+}
+
+pub struct PageInfo {
+    pub next_cursor: Option<String>,
+    pub total: Option<i64>,
+}
+
+impl PageInfo {
+    pub fn envelope(self, data: Value) -> Value {
+        json!({
+            "data": data,
+            "next_cursor": self.next_cursor,
+            "total": self.total,
+        })
+    }
+}
+
+// ?limit=20&offset=40 or ?limit=20&cursor=<next_cursor>, limit is capped at the page size
+pub fn parse_page(
+    config: &QueryPagination,
+    query: &HashMap<String, String>,
+) -> Result<Option<Page>, String> {
+    let limit = match query.get("limit") {
+        Some(l) => match l.parse::<i64>() {
+            Ok(l) if l > 0 => l.min(config.page_size),
+            _ => return Err(format!("invalid limit: {}", l)),
+        },
+        None => config.page_size,
+    };
+
+    let mode = match config.pagination.as_str() {
+        "offset" => match query.get("offset") {
+            Some(o) => match o.parse::<i64>() {
+                Ok(o) if o >= 0 => PageMode::Offset(o),
+                _ => return Err(format!("invalid offset: {}", o)),
+            },
+            None => PageMode::Offset(0),
+        },
+        "cursor" => {
+            if !is_valid_identifier(&config.cursor_column) {
+                return Err(format!("invalid cursor column: {}", config.cursor_column));
+            }
+
+            PageMode::Cursor {
+                column: config.cursor_column.clone(),
+                after: query.get("cursor").map(|c| decode_cursor(c)).transpose()?,
+            }
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(Page {
+        mode,
+        limit,
+        with_total: config.with_total,
+    }))
+}
+
+pub fn encode_cursor(value: &Value) -> String {
+    URL_SAFE_NO_PAD.encode(value.to_string())
+}
+
+fn decode_cursor(cursor: &str) -> Result<ColType, String> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|c| serde_json::from_slice::<Value>(&c).ok())
+        .map(ColType::get_col_type_from_value)
+        .ok_or(format!("invalid cursor: {}", cursor))
+}
+
+// returns the rows of the page along with what goes into the envelope
+pub async fn run_paged_query(
+    model: &Model,
+    query: &str,
+    args: Vec<ColType>,
+    page: Page,
+) -> Result<(Vec<BTreeMap<String, ColType>>, PageInfo), String> {
+    let conn = model.conn.as_ref().unwrap();
+    let query = query.trim().trim_end_matches(';');
+
+    let total = match page.with_total {
+        true => {
+            let row = conn
+                .query_one(
+                    &format!("SELECT COUNT(*) FROM ({}) AS page", query),
+                    args.clone(),
+                )
+                .await?;
+
+            Some(row.get::<i64>(0).map_err(|e| e.to_string())?)
+        }
+        false => None,
+    };
+
+    // one extra row tells whether there is a next page
+    let mut args = args;
+    let paged_query = match &page.mode {
+        PageMode::Offset(offset) => {
+            args.push(ColType::Integer(Some(page.limit + 1)));
+            args.push(ColType::Integer(Some(*offset)));
+
+            format!("SELECT * FROM ({}) AS page LIMIT ? OFFSET ?", query)
+        }
+        PageMode::Cursor { column, after } => {
+            let column = quote_identifier(&conn.dbtype, column);
+
+            let condition = match after {
+                Some(after) => {
+                    args.push(after.clone());
+                    format!(" WHERE {} > ?", column)
+                }
+                None => "".to_string(),
+            };
+            args.push(ColType::Integer(Some(page.limit + 1)));
+
+            format!(
+                "SELECT * FROM ({}) AS page{} ORDER BY {} LIMIT ?",
+                query, condition, column
+            )
+        }
+    };
+
+    let rows = conn.query_all(&paged_query, args).await?;
+    let mut rows = conn.parse_all(rows)?;
+
+    let has_more = rows.len() as i64 > page.limit;
+    rows.truncate(page.limit as usize);
+
+    let next_cursor = match (has_more, &page.mode) {
+        (false, _) => None,
+        (true, PageMode::Offset(offset)) => Some((offset + page.limit).to_string()),
+        (true, PageMode::Cursor { column, .. }) => rows
+            .last()
+            .and_then(|r| r.get(column))
+            .and_then(|v| serde_json::to_value(v).ok())
+            .map(|v| encode_cursor(&v)),
+    };
+
+    Ok((rows, PageInfo { next_cursor, total }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use crate::{
+        database::{model::DbType, sqlite::Sqlite, Conn},
+        queries::{model::QueryPagination, Model},
+    };
+
+    use super::{encode_cursor, parse_page, run_paged_query, Page, PageInfo, PageMode};
+
+    fn config(pagination: &str) -> QueryPagination {
+        QueryPagination {
+            pagination: pagination.to_string(),
+            page_size: 50,
+            cursor_column: "id".to_string(),
+            with_total: false,
+        }
+    }
+
+    #[test]
+    fn test1() {
+        let query = HashMap::from([
+            ("limit".to_string(), "500".to_string()),
+            ("offset".to_string(), "20".to_string()),
+        ]);
+
+        let page = parse_page(&config("offset"), &query).unwrap().unwrap();
+        assert_eq!(page.limit, 50);
+        assert!(matches!(page.mode, PageMode::Offset(20)));
+
+        assert!(parse_page(&config("none"), &query).unwrap().is_none());
+
+        let query = HashMap::from([("offset".to_string(), "-1".to_string())]);
+        assert!(parse_page(&config("offset"), &query).is_err());
+    }
+
+    #[test]
+    fn test2() {
+        let query = HashMap::from([("cursor".to_string(), encode_cursor(&json!(42)))]);
+
+        let page = parse_page(&config("cursor"), &query).unwrap().unwrap();
+        match page.mode {
+            PageMode::Cursor { column, after } => {
+                assert_eq!(column, "id");
+                assert_eq!(serde_json::to_value(after).unwrap(), json!(42));
+            }
+            _ => panic!(),
+        }
+
+        let query = HashMap::from([("cursor".to_string(), "%%".to_string())]);
+        assert!(parse_page(&config("cursor"), &query).is_err());
+
+        let info = PageInfo {
+            next_cursor: Some("abc".to_string()),
+            total: None,
+        };
+        assert_eq!(
+            info.envelope(json!([])),
+            json!({ "data": [], "next_cursor": "abc", "total": null })
+        );
+    }
+
+    #[tokio::test]
+    async fn test3() {
+        let dir = std::env::temp_dir().join(format!("mini-base-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut model = Model::with_config_path(dir.join("config").to_str().unwrap());
+        let sqlite = Sqlite::new(dir.join("test.db").to_str().unwrap()).await;
+        model.conn = Some(Conn {
+            dbtype: DbType::Sqlite,
+            err: None,
+            sqlite: Some(sqlite),
+            mysql: None,
+        });
+
+        let conn = model.conn.as_ref().unwrap();
+        conn.execute("CREATE TABLE items (id INTEGER PRIMARY KEY)", vec![])
+            .await
+            .unwrap();
+        conn.execute(
+            "INSERT INTO items (id) VALUES (1), (2), (3), (4), (5)",
+            vec![],
+        )
+        .await
+        .unwrap();
+
+        // the saved query already has its own LIMIT
+        let query = "SELECT id FROM items ORDER BY id LIMIT 3;";
+        let page = |offset| Page {
+            mode: PageMode::Offset(offset),
+            limit: 2,
+            with_total: true,
+        };
+
+        let (rows, info) = run_paged_query(&model, query, vec![], page(0))
+            .await
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&rows).unwrap(),
+            json!([{ "id": 1 }, { "id": 2 }])
+        );
+        assert_eq!(info.next_cursor, Some("2".to_string()));
+        assert_eq!(info.total, Some(3));
+
+        let (rows, info) = run_paged_query(&model, query, vec![], page(2))
+            .await
+            .unwrap();
+        assert_eq!(serde_json::to_value(&rows).unwrap(), json!([{ "id": 3 }]));
+        assert_eq!(info.next_cursor, None);
+    }
+}
@@ -0,0 +1,262 @@
+use std::{collections::HashMap, convert::Infallible, time::Duration};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        Query, State, WebSocketUpgrade,
+    },
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::get,
+    Router,
+};
+use futures::{SinkExt, Stream, StreamExt};
+use serde_json::{json, Value};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+use tower_cookies::Cookies;
+
+use crate::{
+    error::AppError,
+    parser,
+    queries::{model::User, Model},
+    server::utils::extract_type_from_string,
+};
+
+use super::auth::authorize_roles;
+
+pub fn generate_realtime_routes(model: Model) -> Router {
+    Router::new()
+        .route("/", get(ws_handler))
+        .route("/sse", get(sse_handler))
+        .with_state(model)
+}
+
+struct Subscription {
+    query_id: i64,
+    user: Option<User>,
+    params: Value,
+    tables: Vec<String>,
+    last: Option<String>,
+}
+
+impl Subscription {
+    async fn new(
+        model: &Model,
+        name: &str,
+        params: Value,
+        auth_token: Option<&str>,
+    ) -> Result<Self, AppError> {
+        let route = match model.get_route_by_name(name) {
+            Some(r) => r,
+            None => return Err(AppError::NotFound(format!("invalid query: {}", name))),
+        };
+
+        if route.exec_type != "get" {
+            return Err(AppError::BadRequest(
+                "only get queries can be subscribed".to_string(),
+            ));
+        }
+
+        let user = authorize_roles(model, route.role_access.clone(), auth_token).await?;
+
+        Ok(Self {
+            query_id: route.id,
+            user,
+            params,
+            tables: parser::get_tables(&route.query),
+            last: None,
+        })
+    }
+
+    fn is_affected(&self, tables: &[String]) -> bool {
+        tables.iter().any(|t| self.tables.contains(t))
+    }
+
+    // returns the result only when it differs from what the client already has
+    async fn refresh(&mut self, model: &Model) -> Option<Result<Value, AppError>> {
+        let res = super::refresh_handler(
+            model.clone(),
+            self.query_id,
+            self.user.clone(),
+            self.params.clone(),
+        )
+        .await;
+
+        let body = match res {
+            Ok(body) => body,
+            Err(e) => return Some(Err(e)),
+        };
+
+        if self.last.as_ref() == Some(&body) {
+            return None;
+        }
+
+        let data = serde_json::from_str::<Value>(&body).unwrap_or(Value::Null);
+        self.last = Some(body);
+
+        Some(Ok(data))
+    }
+}
+
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(model): State<Model>,
+    cookies: Cookies,
+) -> Response {
+    let token = cookies.get("auth").map(|c| c.value().to_string());
+
+    ws.on_upgrade(move |socket| handle_socket(socket, model, token))
+}
+
+async fn handle_socket(socket: WebSocket, model: Model, auth_token: Option<String>) {
+    let (mut sender, mut receiver) = socket.split();
+    let mut changes = model.changes.subscribe();
+    let mut subscriptions: HashMap<String, Subscription> = HashMap::new();
+
+    loop {
+        let mut messages = vec![];
+
+        tokio::select! {
+            msg = receiver.next() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(t))) => t,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+
+                let request = serde_json::from_str::<Value>(&text).unwrap_or(Value::Null);
+                let id = request["id"].as_str().unwrap_or_default().to_string();
+
+                match request["type"].as_str() {
+                    Some("subscribe") => {
+                        let name = request["query"].as_str().unwrap_or_default();
+                        let params = match &request["params"] {
+                            Value::Object(_) => request["params"].clone(),
+                            _ => json!({}),
+                        };
+
+                        match Subscription::new(&model, name, params, auth_token.as_deref()).await {
+                            Ok(mut sub) => {
+                                if let Some(res) = sub.refresh(&model).await {
+                                    messages.push(result_message(&id, res));
+                                }
+                                subscriptions.insert(id, sub);
+                            }
+                            Err(e) => messages.push(result_message(&id, Err(e))),
+                        }
+                    }
+                    Some("unsubscribe") => {
+                        subscriptions.remove(&id);
+                    }
+                    _ => messages.push(result_message(
+                        &id,
+                        Err(AppError::BadRequest("invalid message".to_string())),
+                    )),
+                }
+            }
+            change = changes.recv() => {
+                let tables = match change {
+                    Ok(t) => Some(t),
+                    // missed notifications, refresh everything
+                    Err(RecvError::Lagged(_)) => None,
+                    Err(RecvError::Closed) => break,
+                };
+
+                for (id, sub) in subscriptions.iter_mut() {
+                    if tables.as_ref().map_or(true, |t| sub.is_affected(t)) {
+                        if let Some(res) = sub.refresh(&model).await {
+                            messages.push(result_message(id, res));
+                        }
+                    }
+                }
+            }
+        }
+
+        for message in messages {
+            if sender
+                .send(Message::Text(message.to_string()))
+                .await
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
+async fn sse_handler(
+    State(model): State<Model>,
+    cookies: Cookies,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let token = cookies.get("auth").map(|c| c.value().to_string());
+
+    let name = match query.get("query") {
+        Some(n) => n.clone(),
+        None => return AppError::BadRequest("missing query".to_string()).into_response(),
+    };
+
+    let mut params = json!({});
+    for (key, val) in query.into_iter().filter(|(k, _)| k != "query") {
+        params[key] = extract_type_from_string(&val);
+    }
+
+    match Subscription::new(&model, &name, params, token.as_deref()).await {
+        Ok(sub) => {
+            let changes = model.changes.subscribe();
+
+            Sse::new(sse_stream(model, sub, changes))
+                .keep_alive(KeepAlive::new().interval(Duration::from_secs(15)))
+                .into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+fn sse_stream(
+    model: Model,
+    sub: Subscription,
+    changes: Receiver<Vec<String>>,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    futures::stream::unfold(
+        (model, sub, changes, true),
+        |(model, mut sub, mut changes, first)| async move {
+            let mut refresh = first;
+
+            loop {
+                if refresh {
+                    if let Some(res) = sub.refresh(&model).await {
+                        let event = match res {
+                            Ok(data) => Event::default().event("result").data(data.to_string()),
+                            Err(e) => Event::default()
+                                .event("error")
+                                .data(e.to_json().to_string()),
+                        };
+
+                        return Some((Ok(event), (model, sub, changes, false)));
+                    }
+                }
+
+                refresh = match changes.recv().await {
+                    Ok(tables) => sub.is_affected(&tables),
+                    Err(RecvError::Lagged(_)) => true,
+                    Err(RecvError::Closed) => return None,
+                };
+            }
+        },
+    )
+}
+
+fn result_message(id: &str, res: Result<Value, AppError>) -> Value {
+    match res {
+        Ok(data) => json!({ "type": "result", "id": id, "data": data }),
+        Err(e) => {
+            let mut message = e.to_json();
+            message["type"] = json!("error");
+            message["id"] = json!(id);
+            message
+        }
+    }
+}
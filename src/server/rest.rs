@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    extract::{Json, Path, Query, State},
+    http::{Method, Request},
+    middleware::{self, Next},
+    response::Response,
+    routing::get,
+    Extension, Router,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    database::model::{ColInfo, ColType},
+    error::AppError,
+    queries::{model::RestTable, Model},
+    server::utils::extract_type_from_string,
+};
+
+use super::{auth::auth_middleware, metrics};
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+pub fn generate_rest_routes(model: Model) -> Router {
+    Router::new()
+        .route("/:table", get(list_handler).post(insert_handler))
+        .route(
+            "/:table/:id",
+            get(get_handler).put(update_handler).delete(delete_handler),
+        )
+        .route_layer(middleware::from_fn(auth_middleware))
+        .route_layer(middleware::from_fn_with_state(model, rest_middleware))
+}
+
+async fn rest_middleware(
+    State(model): State<Model>,
+    Path(params): Path<HashMap<String, String>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    let table_name = params.get("table").cloned().unwrap_or_default();
+
+    let operation = match (req.method(), params.contains_key("id")) {
+        (&Method::GET, false) => "list",
+        (&Method::POST, false) => "insert",
+        (&Method::GET, true) => "get",
+        (&Method::PUT, true) => "update",
+        (&Method::DELETE, true) => "delete",
+        _ => return Err(AppError::NotFound("endpoint not found".to_string())),
+    };
+    log::info!("rest endpoint: {} {}", operation, &table_name);
+
+    let rest_table = match model.get_rest_table_by_name(&table_name).await {
+        Ok(t) => t,
+        Err(e) => {
+            log::error!("invalid rest table: {}", e);
+            return Err(AppError::NotFound(format!(
+                "table not found: {}",
+                table_name
+            )));
+        }
+    };
+
+    metrics::set_endpoint(match params.contains_key("id") {
+        true => format!("/rest/{}/:id", table_name),
+        false => format!("/rest/{}", table_name),
+    });
+
+    let role_access = model
+        .get_all_role_access_by_rest_table(rest_table.id, operation)
+        .await
+        .map_err(AppError::Database)?;
+
+    let columns = match model.get_table_columns(&table_name).await {
+        Ok(c) if !c.is_empty() => c,
+        _ => {
+            log::error!("table does not exist: {}", &table_name);
+            return Err(AppError::NotFound(format!(
+                "table not found: {}",
+                table_name
+            )));
+        }
+    };
+
+    req.extensions_mut().insert(model);
+    req.extensions_mut().insert(rest_table);
+    req.extensions_mut().insert(columns);
+    req.extensions_mut().insert(role_access);
+
+    Ok(next.run(req).await)
+}
+
+async fn list_handler(
+    Extension(model): Extension<Model>,
+    Extension(rest_table): Extension<RestTable>,
+    Extension(columns): Extension<Vec<ColInfo>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Result<String, AppError> {
+    let params = parse_list_params(&columns, query).map_err(AppError::BadRequest)?;
+
+    let rows = model
+        .select_table_rows(
+            &rest_table.table_name,
+            params.filters,
+            params.sort,
+            params.limit,
+            params.offset,
+        )
+        .await
+        .map_err(AppError::from_db)?;
+
+    to_json(&rows)
+}
+
+async fn get_handler(
+    Extension(model): Extension<Model>,
+    Extension(rest_table): Extension<RestTable>,
+    Extension(columns): Extension<Vec<ColInfo>>,
+    Path((_, id)): Path<(String, String)>,
+) -> Result<String, AppError> {
+    let primary_key = primary_key(&columns, &id).map_err(AppError::BadRequest)?;
+
+    match model
+        .get_table_row(&rest_table.table_name, primary_key)
+        .await
+        .map_err(AppError::from_db)?
+    {
+        Some(row) => to_json(&row),
+        None => Err(row_not_found()),
+    }
+}
+
+async fn insert_handler(
+    Extension(model): Extension<Model>,
+    Extension(rest_table): Extension<RestTable>,
+    Extension(columns): Extension<Vec<ColInfo>>,
+    Json(body): Json<Value>,
+) -> Result<String, AppError> {
+    let values = body_values(&columns, body).map_err(AppError::BadRequest)?;
+
+    let row = model
+        .insert_table_row(&rest_table.table_name, values)
+        .await
+        .map_err(AppError::from_db)?;
+
+    to_json(&row)
+}
+
+async fn update_handler(
+    Extension(model): Extension<Model>,
+    Extension(rest_table): Extension<RestTable>,
+    Extension(columns): Extension<Vec<ColInfo>>,
+    Path((_, id)): Path<(String, String)>,
+    Json(body): Json<Value>,
+) -> Result<String, AppError> {
+    let primary_key = primary_key(&columns, &id).map_err(AppError::BadRequest)?;
+
+    let values = body_values(&columns, body).map_err(AppError::BadRequest)?;
+    if values.is_empty() {
+        return Err(AppError::BadRequest("nothing to update".to_string()));
+    }
+
+    let res = model
+        .update_table_row(&rest_table.table_name, primary_key.clone(), values)
+        .await
+        .map_err(AppError::from_db)?;
+
+    if res == 0 {
+        return Err(row_not_found());
+    }
+
+    let row = model
+        .get_table_row(&rest_table.table_name, primary_key)
+        .await
+        .map_err(AppError::from_db)?;
+
+    to_json(&row)
+}
+
+async fn delete_handler(
+    Extension(model): Extension<Model>,
+    Extension(rest_table): Extension<RestTable>,
+    Extension(columns): Extension<Vec<ColInfo>>,
+    Path((_, id)): Path<(String, String)>,
+) -> Result<String, AppError> {
+    let primary_key = primary_key(&columns, &id).map_err(AppError::BadRequest)?;
+
+    match model
+        .delete_table_row(&rest_table.table_name, primary_key)
+        .await
+        .map_err(AppError::from_db)?
+    {
+        0 => Err(row_not_found()),
+        n => Ok(json!({ "deleted": n }).to_string()),
+    }
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, AppError> {
+    serde_json::to_string(value).map_err(|e| AppError::Internal(e.to_string()))
+}
+
+fn row_not_found() -> AppError {
+    AppError::NotFound("row not found".to_string())
+}
+
+struct ListParams {
+    filters: Vec<(String, ColType)>,
+    sort: Option<(String, bool)>,
+    limit: i64,
+    offset: i64,
+}
+
+// ?limit=10&offset=20&sort=-created_at&done=true, every other key is an equality filter
+fn parse_list_params(
+    columns: &[ColInfo],
+    query: HashMap<String, String>,
+) -> Result<ListParams, String> {
+    let mut params = ListParams {
+        filters: vec![],
+        sort: None,
+        limit: DEFAULT_LIMIT,
+        offset: 0,
+    };
+
+    let mut query = query.into_iter().collect::<Vec<(String, String)>>();
+    query.sort();
+
+    for (key, val) in query {
+        match key.as_str() {
+            "limit" => match val.parse::<i64>() {
+                Ok(l) if l > 0 => params.limit = l.min(MAX_LIMIT),
+                _ => return Err(format!("invalid limit: {}", val)),
+            },
+            "offset" => match val.parse::<i64>() {
+                Ok(o) if o >= 0 => params.offset = o,
+                _ => return Err(format!("invalid offset: {}", val)),
+            },
+            "sort" => {
+                let (column, descending) = match val.strip_prefix('-') {
+                    Some(c) => (c.to_string(), true),
+                    None => (val.clone(), false),
+                };
+
+                if !columns.iter().any(|c| c.name == column) {
+                    return Err(format!("invalid sort column: {}", column));
+                }
+                params.sort = Some((column, descending));
+            }
+            _ => {
+                let column = columns.iter().find(|c| c.name == key);
+                let value = match column {
+                    Some(c) => column_value(c, &val)?,
+                    None => return Err(format!("invalid filter column: {}", key)),
+                };
+                params.filters.push((key, value));
+            }
+        }
+    }
+
+    Ok(params)
+}
+
+fn primary_key(columns: &[ColInfo], id: &str) -> Result<Vec<(String, ColType)>, String> {
+    let pk = columns.iter().filter(|c| c.pk).collect::<Vec<&ColInfo>>();
+
+    if pk.len() != 1 {
+        return Err("table needs a single column primary key".to_string());
+    }
+
+    Ok(vec![(pk[0].name.clone(), column_value(pk[0], id)?)])
+}
+
+// text is only bound as is for non numeric columns, so `5abc` can't reach an integer key
+fn column_value(column: &ColInfo, val: &str) -> Result<ColType, String> {
+    let ctype = column.ctype.to_uppercase();
+    let numeric = ["INT", "REAL", "FLOA", "DOUB", "DEC", "NUM"]
+        .iter()
+        .any(|t| ctype.contains(t));
+
+    match ColType::get_col_type_from_value(extract_type_from_string(val)) {
+        ColType::String(_) if numeric => Err(format!("invalid value for {}: {}", column.name, val)),
+        value => Ok(value),
+    }
+}
+
+fn body_values(columns: &[ColInfo], body: Value) -> Result<Vec<(String, ColType)>, String> {
+    let body = match body {
+        Value::Object(b) => b,
+        _ => return Err("body must be a json object".to_string()),
+    };
+
+    let mut values = vec![];
+
+    for (key, val) in body {
+        if !columns.iter().any(|c| c.name == key) {
+            return Err(format!("invalid column: {}", key));
+        }
+
+        values.push((key, ColType::get_col_type_from_value(val)));
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use crate::database::model::ColInfo;
+
+    use super::{body_values, parse_list_params, primary_key, MAX_LIMIT};
+
+    fn columns() -> Vec<ColInfo> {
+        ["id", "title", "done"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| ColInfo {
+                cid: i as i64,
+                name: name.to_string(),
+                ctype: "TEXT".to_string(),
+                notnull: false,
+                dflt_value: None,
+                pk: name == "id",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test1() {
+        let query = HashMap::from([
+            ("limit".to_string(), "5000".to_string()),
+            ("offset".to_string(), "20".to_string()),
+            ("sort".to_string(), "-title".to_string()),
+            ("done".to_string(), "true".to_string()),
+        ]);
+
+        let params = parse_list_params(&columns(), query).unwrap();
+        assert_eq!(params.limit, MAX_LIMIT);
+        assert_eq!(params.offset, 20);
+        assert_eq!(params.sort, Some(("title".to_string(), true)));
+        assert_eq!(
+            serde_json::to_value(&params.filters).unwrap(),
+            json!([["done", true]])
+        );
+
+        let query = HashMap::from([("password".to_string(), "x".to_string())]);
+        assert!(parse_list_params(&columns(), query).is_err());
+
+        let query = HashMap::from([("limit".to_string(), "-1".to_string())]);
+        assert!(parse_list_params(&columns(), query).is_err());
+    }
+
+    #[test]
+    fn test2() {
+        assert_eq!(
+            serde_json::to_value(primary_key(&columns(), "7").unwrap()).unwrap(),
+            json!([["id", 7]])
+        );
+        assert!(primary_key(&columns()[1..], "7").is_err());
+
+        assert_eq!(
+            serde_json::to_value(primary_key(&columns(), "5abc").unwrap()).unwrap(),
+            json!([["id", "5abc"]])
+        );
+        let mut typed = columns();
+        typed[0].ctype = "INTEGER".to_string();
+        assert!(primary_key(&typed, "5abc").is_err());
+        assert!(primary_key(&typed, "1.2.3").is_err());
+
+        let query = HashMap::from([("id".to_string(), "x".to_string())]);
+        assert!(parse_list_params(&typed, query).is_err());
+
+        assert!(body_values(&columns(), json!({ "title": "a" })).is_ok());
+        assert!(body_values(&columns(), json!({ "owner": "a" })).is_err());
+        assert!(body_values(&columns(), json!([1])).is_err());
+    }
+}
@@ -0,0 +1,255 @@
+use std::{collections::HashMap, str::FromStr, time::Instant};
+
+use chrono::{DateTime, Utc};
+use cron::Schedule as CronSchedule;
+use serde_json::Value;
+
+use crate::{
+    database::model::ColType,
+    parser,
+    queries::{
+        model::{Schedule, ScheduleRun},
+        Model,
+    },
+};
+
+use super::webhook::{deliver, WebhookRequest};
+
+const OUTPUT_SNIPPET_LEN: usize = 512;
+
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+pub fn parse_cron(cron: &str) -> Result<CronSchedule, String> {
+    let fields = cron.split_whitespace().collect::<Vec<&str>>();
+
+    // plain 5 field expressions run at the start of the minute
+    let expression = match fields.as_slice() {
+        [minute, hour, day, month, weekday] => format!(
+            "0 {} {} {} {} {}",
+            minute,
+            hour,
+            day,
+            month,
+            weekday_names(weekday).map_err(|e| format!("invalid cron {}: {}", cron, e))?
+        ),
+        _ => cron.trim().to_string(),
+    };
+
+    CronSchedule::from_str(&expression).map_err(|e| format!("invalid cron {}: {}", cron, e))
+}
+
+// 5 field cron counts weekdays 0-7 from sunday while the cron crate counts 1-7,
+// so numbers are written out as names
+fn weekday_names(field: &str) -> Result<String, String> {
+    let mut days: Vec<String> = vec![];
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<usize>() {
+                Ok(s) if s > 0 => (range, s),
+                _ => return Err(format!("invalid day of week: {}", part)),
+            },
+            None => (part, 1),
+        };
+
+        let bounds = match range.split_once('-') {
+            _ if range == "*" && step > 1 => Some((0, 6)),
+            Some((from, to)) => from.parse::<usize>().ok().zip(to.parse::<usize>().ok()),
+            None => range.parse::<usize>().ok().map(|d| (d, d)),
+        };
+
+        match bounds {
+            Some((from, to)) if from <= to && to <= 7 => {
+                for day in (from..=to).step_by(step) {
+                    let name = WEEKDAYS[day % 7].to_string();
+                    if !days.contains(&name) {
+                        days.push(name);
+                    }
+                }
+            }
+            Some(_) => return Err(format!("invalid day of week: {}", part)),
+            None => days.push(part.to_string()),
+        }
+    }
+
+    Ok(days.join(","))
+}
+
+pub fn next_run_at(cron: &str, after: i64) -> Result<i64, String> {
+    let after = match DateTime::from_timestamp(after, 0) {
+        Some(t) => t,
+        None => return Err(format!("invalid timestamp: {}", after)),
+    };
+
+    match parse_cron(cron)?.after(&after).next() {
+        Some(t) => Ok(t.timestamp()),
+        None => Err(format!("cron {} never runs again", cron)),
+    }
+}
+
+pub async fn run_scheduler(model: Model) {
+    loop {
+        let now = Utc::now().timestamp();
+
+        match model.get_due_schedules(now).await {
+            Ok(schedules) => {
+                for schedule in schedules {
+                    tokio::spawn(run_schedule(model.clone(), schedule, now));
+                }
+            }
+            Err(e) => log::error!("unable to read schedules: {}", e),
+        }
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+}
+
+async fn run_schedule(model: Model, schedule: Schedule, now: i64) {
+    let next = match next_run_at(&schedule.cron, now) {
+        Ok(n) => n,
+        Err(e) => {
+            log::error!("disabling schedule {}: {}", schedule.name, e);
+            let _ = model.disable_schedule(schedule.id).await;
+            record_run(&model, schedule.id, now, 0, Err(e)).await;
+            return;
+        }
+    };
+
+    // missed runs are not replayed, the job runs once and moves on to the next slot
+    match model
+        .claim_schedule(schedule.id, schedule.next_run_at, next)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(e) => {
+            log::error!("unable to claim schedule {}: {}", schedule.name, e);
+            return;
+        }
+    }
+
+    let start = Instant::now();
+    let started_at = Utc::now().timestamp();
+
+    let args = serde_json::from_str::<Value>(&schedule.args).unwrap_or(Value::Null);
+    let args = match args {
+        Value::Object(_) => args,
+        _ => Value::Object(serde_json::Map::new()),
+    };
+
+    let res = match schedule.target_type.as_str() {
+        "query" => run_query_target(&model, schedule.target_id, args).await,
+        "webhook" => run_webhook_target(&model, schedule.target_id, args).await,
+        t => Err(format!("invalid target type: {}", t)),
+    };
+
+    if let Err(e) = &res {
+        log::error!("schedule {} failed: {}", schedule.name, e);
+    }
+
+    record_run(
+        &model,
+        schedule.id,
+        started_at,
+        start.elapsed().as_millis() as i64,
+        res,
+    )
+    .await;
+}
+
+async fn run_query_target(model: &Model, query_id: i64, args: Value) -> Result<String, String> {
+    super::handler(model.clone(), query_id, None, args, None)
+        .await
+        .map(|output| output.body)
+        .map_err(|e| e.to_string())
+}
+
+async fn run_webhook_target(model: &Model, webhook_id: i64, args: Value) -> Result<String, String> {
+    let webhook = model.get_webhook_by_id(webhook_id).await?;
+
+    let args_map = match args {
+        Value::Object(o) => o
+            .into_iter()
+            .map(|(k, v)| (k, Some(ColType::get_col_type_from_value(v))))
+            .collect::<HashMap<String, Option<ColType>>>(),
+        _ => HashMap::new(),
+    };
+
+    let request = WebhookRequest {
+        webhook_id: webhook.id,
+        method: webhook.exec_type,
+        url: webhook.url,
+        payload: parser::replace_variables_with_values(&webhook.args, args_map),
+        secret: webhook.secret,
+    };
+
+    let delivery = deliver(model, &request).await;
+
+    match (delivery.is_success(), delivery.status_code) {
+        (true, _) => Ok(delivery.body),
+        (false, Some(code)) => Err(format!("webhook responded with status {}", code)),
+        (false, None) => Err(delivery.error),
+    }
+}
+
+async fn record_run(
+    model: &Model,
+    schedule_id: i64,
+    started_at: i64,
+    duration_ms: i64,
+    res: Result<String, String>,
+) {
+    let (status, output) = match res {
+        Ok(o) => ("success", o),
+        Err(e) => ("failed", e),
+    };
+
+    let res = model
+        .add_schedule_run(ScheduleRun {
+            id: 0,
+            schedule_id,
+            started_at,
+            duration_ms,
+            status: status.to_string(),
+            output: output.chars().take(OUTPUT_SNIPPET_LEN).collect(),
+        })
+        .await;
+
+    if let Err(e) = res {
+        log::error!("unable to record schedule run: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{next_run_at, parse_cron};
+
+    #[test]
+    fn test1() {
+        // 2024-01-01 00:00:30 UTC
+        assert_eq!(next_run_at("*/5 * * * *", 1704067230), Ok(1704067500));
+        assert_eq!(next_run_at("0 0 * * * *", 1704067230), Ok(1704070800));
+    }
+
+    #[test]
+    fn test2() {
+        assert!(parse_cron("every minute").is_err());
+        assert!(parse_cron("61 * * * *").is_err());
+        assert!(parse_cron("* * * * 8").is_err());
+        assert!(parse_cron("* * * * 0").is_ok());
+    }
+
+    #[test]
+    fn test3() {
+        // monday 2024-01-01 00:00:30 UTC
+        assert_eq!(next_run_at("0 9 * * 1", 1704067230), Ok(1704099600));
+        assert_eq!(next_run_at("0 9 * * 1", 1704099600), Ok(1704704400));
+        assert_eq!(next_run_at("0 9 * * MON", 1704099600), Ok(1704704400));
+
+        // sunday is both 0 and 7
+        assert_eq!(next_run_at("0 0 * * 0", 1704067230), Ok(1704585600));
+        assert_eq!(next_run_at("0 0 * * 7", 1704067230), Ok(1704585600));
+        assert_eq!(next_run_at("0 0 * * 5-7", 1704067230), Ok(1704412800));
+        assert_eq!(next_run_at("0 0 * * */2", 1704067230), Ok(1704153600));
+    }
+}
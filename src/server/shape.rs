@@ -0,0 +1,252 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{database::model::ColType, queries::model::QueryShape};
+
+type Object = HashMap<String, Box<ColType>>;
+
+struct Group {
+    parent: Object,
+    children: BTreeMap<String, Vec<ColType>>,
+    seen: HashSet<String>,
+}
+
+// `author.name` becomes {"author": {"name": ..}} and `comments[].body` collects into a
+// "comments" array, rows with the same group_by values collapse into one object
+pub fn shape_rows(
+    rows: Vec<BTreeMap<String, ColType>>,
+    shape: &QueryShape,
+) -> Result<ColType, String> {
+    if shape.shape == "flat" {
+        return Ok(ColType::Array(Some(
+            rows.into_iter()
+                .map(|row| ColType::Object(Some(flat_object(row))))
+                .collect(),
+        )));
+    }
+
+    let keys = shape
+        .group_by
+        .split(',')
+        .map(|k| k.trim())
+        .filter(|k| !k.is_empty())
+        .collect::<Vec<&str>>();
+
+    let mut order: Vec<String> = vec![];
+    let mut groups: HashMap<String, Group> = HashMap::new();
+
+    for (i, row) in rows.into_iter().enumerate() {
+        let group_key = match keys.is_empty() {
+            true => i.to_string(),
+            false => {
+                let mut values = vec![];
+                for key in keys.iter() {
+                    let value = row
+                        .get(*key)
+                        .ok_or(format!("group by column {} is not in the result", key))?;
+                    values.push(serde_json::to_string(value).map_err(|e| e.to_string())?);
+                }
+                values.join("\u{0}")
+            }
+        };
+
+        let mut parent = vec![];
+        let mut children: BTreeMap<String, Vec<(String, ColType)>> = BTreeMap::new();
+
+        for (column, value) in row {
+            match column.split_once("[].") {
+                Some((array, path)) => children
+                    .entry(array.to_string())
+                    .or_default()
+                    .push((path.to_string(), value)),
+                None => parent.push((column, value)),
+            }
+        }
+
+        let group = match groups.get_mut(&group_key) {
+            Some(group) => group,
+            None => {
+                order.push(group_key.clone());
+                groups.entry(group_key).or_insert(Group {
+                    parent: nest(parent)?,
+                    children: BTreeMap::new(),
+                    seen: HashSet::new(),
+                })
+            }
+        };
+
+        for (array, columns) in children {
+            let items = group.children.entry(array.clone()).or_default();
+
+            // a left join without a match gives a child that is all nulls
+            if columns.iter().all(|(_, v)| is_null(v)) {
+                continue;
+            }
+
+            let child = ColType::Object(Some(nest(columns)?));
+            let child_key = format!(
+                "{}\u{0}{}",
+                array,
+                serde_json::to_string(&child).map_err(|e| e.to_string())?
+            );
+            if group.seen.insert(child_key) {
+                items.push(child);
+            }
+        }
+    }
+
+    let objects = order
+        .into_iter()
+        .filter_map(|key| groups.remove(&key))
+        .map(|group| {
+            let mut object = group.parent;
+            for (array, items) in group.children {
+                object.insert(array, Box::new(ColType::Array(Some(items))));
+            }
+            ColType::Object(Some(object))
+        })
+        .collect::<Vec<ColType>>();
+
+    match shape.shape.as_str() {
+        "single" => Ok(objects.into_iter().next().unwrap_or(ColType::Null)),
+        _ => Ok(ColType::Array(Some(objects))),
+    }
+}
+
+fn flat_object(row: BTreeMap<String, ColType>) -> Object {
+    row.into_iter().map(|(k, v)| (k, Box::new(v))).collect()
+}
+
+fn nest(columns: Vec<(String, ColType)>) -> Result<Object, String> {
+    let mut object = HashMap::new();
+
+    for (column, value) in columns {
+        let path = column.split('.').collect::<Vec<&str>>();
+        insert_path(&mut object, &path, value).map_err(|_| {
+            format!(
+                "column {} clashes with another column of the same path",
+                column
+            )
+        })?;
+    }
+
+    Ok(object)
+}
+
+fn insert_path(object: &mut Object, path: &[&str], value: ColType) -> Result<(), ()> {
+    match path {
+        [] => Err(()),
+        [key] => match object.contains_key(*key) {
+            true => Err(()),
+            false => {
+                object.insert(key.to_string(), Box::new(value));
+                Ok(())
+            }
+        },
+        [key, rest @ ..] => {
+            let child = object
+                .entry(key.to_string())
+                .or_insert_with(|| Box::new(ColType::Object(Some(HashMap::new()))));
+
+            match child.as_mut() {
+                ColType::Object(Some(child)) => insert_path(child, rest, value),
+                _ => Err(()),
+            }
+        }
+    }
+}
+
+fn is_null(value: &ColType) -> bool {
+    matches!(serde_json::to_value(value), Ok(serde_json::Value::Null))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde_json::json;
+
+    use crate::{database::model::ColType, queries::model::QueryShape};
+
+    use super::shape_rows;
+
+    fn row(values: &[(&str, ColType)]) -> BTreeMap<String, ColType> {
+        values
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    fn shape(shape: &str, group_by: &str) -> QueryShape {
+        QueryShape {
+            shape: shape.to_string(),
+            group_by: group_by.to_string(),
+            max_rows: 0,
+        }
+    }
+
+    fn rows() -> Vec<BTreeMap<String, ColType>> {
+        let text = |t: &str| ColType::String(Some(t.to_string()));
+
+        vec![
+            row(&[
+                ("id", ColType::Integer(Some(1))),
+                ("author.name", text("ann")),
+                ("comments[].id", ColType::Integer(Some(10))),
+                ("comments[].body", text("first")),
+            ]),
+            row(&[
+                ("id", ColType::Integer(Some(1))),
+                ("author.name", text("ann")),
+                ("comments[].id", ColType::Integer(Some(11))),
+                ("comments[].body", text("second")),
+            ]),
+            row(&[
+                ("id", ColType::Integer(Some(2))),
+                ("author.name", text("bob")),
+                ("comments[].id", ColType::Null),
+                ("comments[].body", ColType::Null),
+            ]),
+        ]
+    }
+
+    #[test]
+    fn test1() {
+        let shaped = shape_rows(rows(), &shape("nested", "id")).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(shaped).unwrap(),
+            json!([
+                {
+                    "id": 1,
+                    "author": {"name": "ann"},
+                    "comments": [{"id": 10, "body": "first"}, {"id": 11, "body": "second"}]
+                },
+                {"id": 2, "author": {"name": "bob"}, "comments": []}
+            ])
+        );
+
+        let shaped = shape_rows(rows(), &shape("flat", "")).unwrap();
+        assert_eq!(
+            serde_json::to_value(shaped).unwrap()[0]["author.name"],
+            "ann"
+        );
+    }
+
+    #[test]
+    fn test2() {
+        let shaped = shape_rows(rows(), &shape("single", "id")).unwrap();
+        assert_eq!(serde_json::to_value(shaped).unwrap()["id"], 1);
+
+        assert!(matches!(
+            shape_rows(vec![], &shape("single", "id")).unwrap(),
+            ColType::Null
+        ));
+        assert!(shape_rows(rows(), &shape("nested", "missing")).is_err());
+
+        let clash = vec![row(&[
+            ("a", ColType::Integer(Some(1))),
+            ("a.b", ColType::Integer(Some(2))),
+        ])];
+        assert!(shape_rows(clash, &shape("nested", "")).is_err());
+    }
+}
@@ -1,13 +1,15 @@
 use std::collections::HashMap;
 
-use crate::queries::{model::UserStorage, Model};
+use crate::{
+    error::AppError,
+    queries::{model::UserStorage, Model},
+};
 use axum::{
     body::Body,
     extract::Query,
-    http::StatusCode,
     middleware,
-    response::{IntoResponse, Response},
-    routing::{get, post},
+    response::Response,
+    routing::{get, head, post},
     Extension, Json, Router,
 };
 use axum_extra::extract::{multipart::Field, Multipart};
@@ -21,6 +23,8 @@ use tokio::{
 use super::{
     auth::{self},
     model::TokenFile,
+    transform::{Format, ImageTransform},
+    tus,
 };
 
 pub fn generate_storage_routes(model: Model) -> Router {
@@ -29,6 +33,11 @@ pub fn generate_storage_routes(model: Model) -> Router {
         .route("/delete", post(delete))
         .route("/generate-token", post(generate_token))
         .route("/get", get(get_file))
+        .route("/tus", post(tus::create).options(tus::options))
+        .route(
+            "/tus/:id",
+            head(tus::head).patch(tus::patch).delete(tus::terminate),
+        )
         .route_layer(middleware::from_fn_with_state(
             model,
             auth::storage_middleware,
@@ -39,31 +48,30 @@ async fn upload(
     Extension(model): Extension<Model>,
     Extension(user_storage): Extension<Option<UserStorage>>,
     mut multipart: Multipart,
-) -> (StatusCode, String) {
+) -> Result<String, AppError> {
     let user_id = match user_storage {
         Some(user) => {
             if !user.can_write {
-                return (
-                    StatusCode::UNAUTHORIZED,
-                    "Unauthorized to upload file".to_string(),
-                );
+                return Err(AppError::Forbidden(
+                    "not allowed to upload files".to_string(),
+                ));
             }
             user.id
         }
-        None => return (StatusCode::UNAUTHORIZED, "please login first".to_string()),
+        None => return Err(login_required()),
     };
 
     let mut ids = vec![];
     while let Some(field) = match multipart.next_field().await {
         Ok(field) => field,
         Err(_) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                "error retrieving field".to_string(),
-            );
+            return Err(AppError::BadRequest("error retrieving field".to_string()));
         }
     } {
-        let file_name = field.file_name().unwrap().to_string();
+        let file_name = match field.file_name() {
+            Some(f) => f.to_string(),
+            None => return Err(AppError::BadRequest("file name is required".to_string())),
+        };
 
         let filename_arr = file_name.split('.').collect::<Vec<&str>>();
         let random_id = uuid::Uuid::new_v4().to_string();
@@ -71,11 +79,12 @@ async fn upload(
 
         let save_path = format!("uploads/{}", &generated_name);
 
-        if save_file(field, &save_path).await.is_err() {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "error saving file".to_string(),
-            );
+        if let Err(e) = save_file(field, &save_path)
+            .await
+            .map_err(|e| e.to_string())
+        {
+            let _ = fs::remove_file(&save_path).await;
+            return Err(AppError::Internal(format!("error saving file: {}", e)));
         }
 
         let res = model.upload_file(file_name, generated_name, user_id).await;
@@ -83,12 +92,7 @@ async fn upload(
             Ok(id) => {
                 ids.push(id);
             }
-            Err(_) => {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "error saving file".to_string(),
-                )
-            }
+            Err(e) => return Err(AppError::Database(e)),
         }
     }
 
@@ -98,103 +102,69 @@ async fn upload(
         .collect::<Vec<String>>()
         .join(", ");
 
-    (StatusCode::OK, ids_str)
+    Ok(ids_str)
 }
 
 async fn delete(
     Extension(model): Extension<Model>,
     Extension(user_storage): Extension<Option<UserStorage>>,
     Json(body): Json<Value>,
-) -> (StatusCode, String) {
+) -> Result<String, AppError> {
     match user_storage {
         Some(user) => {
             if !user.can_delete {
-                return (
-                    StatusCode::UNAUTHORIZED,
-                    "Unauthorized to delete file".to_string(),
-                );
+                return Err(AppError::Forbidden(
+                    "not allowed to delete files".to_string(),
+                ));
             }
         }
-        None => return (StatusCode::UNAUTHORIZED, "please login first".to_string()),
+        None => return Err(login_required()),
     }
 
-    let file_id: i64;
-    match body.get("file_id") {
-        Some(Value::Number(f_id)) => {
-            file_id = f_id.as_i64().unwrap();
+    let file_id = match body.get("file_id").and_then(|f| f.as_i64()) {
+        Some(f_id) => f_id,
+        None => return Err(AppError::BadRequest("file_id is required".to_string())),
+    };
 
-            let res = model.get_file_by_id(file_id).await;
-            match res {
-                Ok(s) => {
-                    let _ = fs::remove_file(format!("uploads/{}", &s.unique_name)).await;
-                }
-                Err(_) => {
-                    return (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        "error deleting file".to_string(),
-                    );
-                }
-            }
-        }
-        _ => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "error deleting file".to_string(),
-            );
+    match model.get_file_by_id(file_id).await {
+        Ok(s) => {
+            let _ = fs::remove_file(format!("uploads/{}", &s.unique_name)).await;
         }
+        Err(_) => return Err(file_not_found()),
     }
 
     match model.delete_file(file_id).await {
-        Ok(r) => {
-            if r == 0 {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "error deleting file".to_string(),
-                );
-            }
-        }
-        Err(_) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "error deleting file".to_string(),
-            );
-        }
+        Ok(0) => Err(file_not_found()),
+        Ok(_) => Ok("File deleted successfully".to_string()),
+        Err(e) => Err(AppError::Database(e)),
     }
-
-    (StatusCode::OK, "File deleted successfully".to_string())
 }
 
 async fn generate_token(
     Extension(model): Extension<Model>,
     Extension(user_storage): Extension<Option<UserStorage>>,
     Json(body): Json<Value>,
-) -> (StatusCode, String) {
+) -> Result<String, AppError> {
     match user_storage {
         Some(user) => {
             if !user.can_read {
-                return (
-                    StatusCode::UNAUTHORIZED,
-                    "Unauthorized to generate token".to_string(),
-                );
+                return Err(AppError::Forbidden("not allowed to read files".to_string()));
             }
         }
-        None => return (StatusCode::UNAUTHORIZED, "please login first".to_string()),
+        None => return Err(login_required()),
     }
 
-    let file_id: i64;
-    let exp_time: i64;
-    match (body.get("file_id"), body.get("exp_time")) {
-        (Some(Value::Number(f_id)), Some(Value::Number(e_time))) => {
-            file_id = f_id.as_i64().unwrap();
-            exp_time = e_time.as_i64().unwrap();
-        }
+    let (file_id, exp_time) = match (
+        body.get("file_id").and_then(|f| f.as_i64()),
+        body.get("exp_time").and_then(|e| e.as_i64()),
+    ) {
+        (Some(f_id), Some(e_time)) => (f_id, e_time),
         (_, _) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "error generating token".to_string(),
-            );
+            return Err(AppError::BadRequest(
+                "file_id and exp_time are required".to_string(),
+            ));
         }
-    }
+    };
 
     match model.get_file_by_id(file_id).await {
         Ok(s) => {
@@ -205,31 +175,22 @@ async fn generate_token(
                 if exp_time == -1 { 9999999999 } else { exp_time },
             );
             match optional_token {
-                Ok(token) => {
-                    let url = format!("http://localhost:3456/storage/get?token={}", token);
-                    (StatusCode::OK, url)
-                }
-                Err(_) => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "error generating token".to_string(),
-                ),
+                Ok(token) => Ok(format!("http://localhost:3456/storage/get?token={}", token)),
+                Err(e) => Err(AppError::Internal(e.to_string())),
             }
         }
-        Err(_) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "error generating token".to_string(),
-        ),
+        Err(_) => Err(file_not_found()),
     }
 }
 
 async fn get_file(
     Extension(model): Extension<Model>,
     Query(query): Query<HashMap<String, String>>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<Response, AppError> {
     let token = match query.get("token") {
         Some(t) => t,
         None => {
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            return Err(AppError::BadRequest("token is required".to_string()));
         }
     };
 
@@ -240,30 +201,94 @@ async fn get_file(
             let mut file = match File::open(&file_path).await {
                 Ok(file) => file,
                 Err(_) => {
-                    return Err(StatusCode::NOT_FOUND);
+                    return Err(file_not_found());
                 }
             };
 
             let mut content = Vec::new();
-            if file.read_to_end(&mut content).await.is_err() {
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            if let Err(e) = file.read_to_end(&mut content).await {
+                return Err(AppError::Internal(e.to_string()));
             }
 
-            let mime_type = mime_guess::from_path(file_path)
+            let mut mime_type = mime_guess::from_path(file_path)
                 .first_raw()
-                .unwrap_or("application/octet-stream");
+                .unwrap_or("application/octet-stream")
+                .to_string();
 
-            let response = Response::builder()
-                .header("Content-Type", mime_type)
-                .body(Body::from(content));
+            let optional_transform =
+                ImageTransform::from_query(&query, model.utils.max_image_dimension);
+
+            match optional_transform {
+                Ok(Some(transform)) if mime_type.starts_with("image/") => {
+                    let format = transform.output_format(&mime_type);
+                    mime_type = format.mime_type().to_string();
 
-            match response {
-                Ok(r) => Ok(r),
-                Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+                    content = transform_file(
+                        transform,
+                        format,
+                        &token_file.claims.file.unique_name,
+                        content,
+                    )
+                    .await?;
+                }
+                Ok(_) => {}
+                Err(e) => return Err(AppError::BadRequest(e)),
             }
+
+            Response::builder()
+                .header("Content-Type", mime_type)
+                .body(Body::from(content))
+                .map_err(|e| AppError::Internal(e.to_string()))
+        }
+        Err(_) => Err(AppError::Unauthorized(
+            "invalid or expired token".to_string(),
+        )),
+    }
+}
+
+async fn transform_file(
+    transform: ImageTransform,
+    format: Format,
+    unique_name: &str,
+    content: Vec<u8>,
+) -> Result<Vec<u8>, AppError> {
+    let cache_path = format!(
+        "./uploads/cache/{}",
+        transform.cache_name(unique_name, &format)
+    );
+
+    if let Ok(cached) = fs::read(&cache_path).await {
+        return Ok(cached);
+    }
+
+    let res = tokio::task::spawn_blocking(move || transform.apply(&content, &format)).await;
+
+    let transformed = match res {
+        Ok(Ok(t)) => t,
+        Ok(Err(e)) => {
+            log::error!("image transform failed: {}", e);
+            return Err(AppError::Unprocessable(
+                "image could not be transformed".to_string(),
+            ));
+        }
+        Err(e) => return Err(AppError::Internal(e.to_string())),
+    };
+
+    if fs::create_dir_all("./uploads/cache").await.is_ok() {
+        if let Err(e) = fs::write(&cache_path, &transformed).await {
+            log::error!("unable to cache transformed image: {}", e);
         }
-        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     }
+
+    Ok(transformed)
+}
+
+fn login_required() -> AppError {
+    AppError::Unauthorized("login required".to_string())
+}
+
+fn file_not_found() -> AppError {
+    AppError::NotFound("file not found".to_string())
 }
 
 async fn save_file(mut field: Field, save_path: &str) -> Result<(), Box<dyn std::error::Error>> {
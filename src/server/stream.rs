@@ -0,0 +1,196 @@
+use std::collections::{BTreeMap, HashMap};
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+use tokio::sync::mpsc::Receiver;
+
+use crate::{
+    database::model::{ColType, StreamRow},
+    error::AppError,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Ndjson,
+    Csv,
+}
+
+struct StreamState {
+    rx: Receiver<Result<StreamRow, String>>,
+    first: Option<StreamRow>,
+    columns: Vec<String>,
+    format: Format,
+    sent: usize,
+    max_rows: usize,
+}
+
+// the format param wins over the Accept header, anything unknown in Accept falls back to json
+pub fn negotiate(headers: &HeaderMap, query: &HashMap<String, String>) -> Result<Format, String> {
+    if let Some(format) = query.get("format") {
+        return match format.as_str() {
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            "csv" => Ok(Format::Csv),
+            f => Err(format!("unknown format: {}", f)),
+        };
+    }
+
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|a| a.to_str().ok())
+        .unwrap_or("");
+
+    let format = accept
+        .split(',')
+        .filter_map(|m| m.split(';').next())
+        .find_map(|m| match m.trim() {
+            "application/json" => Some(Format::Json),
+            "application/x-ndjson" | "application/ndjson" => Some(Format::Ndjson),
+            "text/csv" => Some(Format::Csv),
+            _ => None,
+        })
+        .unwrap_or(Format::Json);
+
+    Ok(format)
+}
+
+// waits for the first row so a failing query still answers with an error status,
+// the rest is written as it arrives
+pub async fn stream_rows(
+    mut rx: Receiver<Result<StreamRow, String>>,
+    format: Format,
+    max_rows: usize,
+) -> Result<Response, AppError> {
+    let first = match rx.recv().await {
+        Some(Ok(row)) => Some(row),
+        Some(Err(e)) => return Err(AppError::from_db(e)),
+        None => None,
+    };
+
+    let content_type = match format {
+        Format::Csv => "text/csv",
+        _ => "application/x-ndjson",
+    };
+
+    let state = StreamState {
+        rx,
+        columns: first
+            .as_ref()
+            .map(|row| row.iter().map(|(name, _)| name.clone()).collect())
+            .unwrap_or_default(),
+        first,
+        format,
+        sent: 0,
+        max_rows,
+    };
+
+    let body = futures::stream::unfold(state, |mut state| async move {
+        if state.max_rows > 0 && state.sent >= state.max_rows {
+            return None;
+        }
+
+        let row = match state.first.take() {
+            Some(row) => row,
+            None => match state.rx.recv().await? {
+                Ok(row) => row,
+                Err(e) => return Some((Err(e), state)),
+            },
+        };
+
+        let line = match state.format {
+            Format::Csv if state.sent == 0 => {
+                let header = state.columns.iter().map(|c| Value::String(c.clone()));
+                csv_line(header) + &csv_line(csv_values(&row))
+            }
+            Format::Csv => csv_line(csv_values(&row)),
+            _ => {
+                let object = row.into_iter().collect::<BTreeMap<String, ColType>>();
+                serde_json::to_string(&object).unwrap_or_default() + "\n"
+            }
+        };
+
+        state.sent += 1;
+        Some((Ok::<String, String>(line), state))
+    });
+
+    Ok((
+        [(header::CONTENT_TYPE, content_type)],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
+
+// every row of a query has the same columns in the same order as the header
+fn csv_values(row: &StreamRow) -> impl Iterator<Item = Value> + '_ {
+    row.iter()
+        .map(|(_, v)| serde_json::to_value(v).unwrap_or(Value::Null))
+}
+
+fn csv_line(values: impl Iterator<Item = Value>) -> String {
+    let cells = values
+        .map(|v| {
+            let cell = match v {
+                Value::Null => "".to_string(),
+                Value::String(t) => t,
+                t => t.to_string(),
+            };
+
+            match cell.contains([',', '"', '\n', '\r']) {
+                true => format!("\"{}\"", cell.replace('"', "\"\"")),
+                false => cell,
+            }
+        })
+        .collect::<Vec<String>>();
+
+    cells.join(",") + "\r\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::http::{header, HeaderMap};
+    use serde_json::json;
+
+    use super::{csv_line, negotiate, Format};
+
+    #[test]
+    fn test1() {
+        let mut headers = HeaderMap::new();
+        let mut query = HashMap::new();
+        assert_eq!(negotiate(&headers, &query), Ok(Format::Json));
+
+        headers.insert(
+            header::ACCEPT,
+            "text/html, application/x-ndjson;q=0.9".parse().unwrap(),
+        );
+        assert_eq!(negotiate(&headers, &query), Ok(Format::Ndjson));
+
+        query.insert("format".to_string(), "csv".to_string());
+        assert_eq!(negotiate(&headers, &query), Ok(Format::Csv));
+
+        query.insert("format".to_string(), "xml".to_string());
+        assert!(negotiate(&headers, &query).is_err());
+    }
+
+    #[test]
+    fn test2() {
+        let values = vec![
+            json!(1),
+            json!("plain"),
+            json!("a, \"b\""),
+            json!(null),
+            json!({"x": 1}),
+        ];
+
+        assert_eq!(
+            csv_line(values.into_iter()),
+            "1,plain,\"a, \"\"b\"\"\",,\"{\"\"x\"\":1}\"\r\n"
+        );
+    }
+}
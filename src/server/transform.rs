@@ -0,0 +1,285 @@
+use std::{collections::HashMap, io::Cursor};
+
+use image::{
+    codecs::{jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    DynamicImage, ImageEncoder,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fit {
+    Contain,
+    Cover,
+    Fill,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Format {
+    Webp,
+    Png,
+    Jpeg,
+}
+
+impl Format {
+    pub fn extension(&self) -> &str {
+        match self {
+            Format::Webp => "webp",
+            Format::Png => "png",
+            Format::Jpeg => "jpeg",
+        }
+    }
+
+    pub fn mime_type(&self) -> &str {
+        match self {
+            Format::Webp => "image/webp",
+            Format::Png => "image/png",
+            Format::Jpeg => "image/jpeg",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageTransform {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fit: Fit,
+    pub quality: u8,
+    pub format: Option<Format>,
+}
+
+impl ImageTransform {
+    pub fn from_query(
+        query: &HashMap<String, String>,
+        max_dimension: u32,
+    ) -> Result<Option<Self>, String> {
+        if !["w", "h", "fit", "q", "format"]
+            .iter()
+            .any(|k| query.contains_key(*k))
+        {
+            return Ok(None);
+        }
+
+        let width = parse_dimension(query.get("w"), max_dimension)?;
+        let height = parse_dimension(query.get("h"), max_dimension)?;
+
+        let fit = match query.get("fit").map(|f| f.as_str()) {
+            None | Some("contain") => Fit::Contain,
+            Some("cover") => Fit::Cover,
+            Some("fill") => Fit::Fill,
+            Some(f) => return Err(format!("invalid fit: {}", f)),
+        };
+
+        let format = match query.get("format").map(|f| f.as_str()) {
+            None => None,
+            Some("webp") => Some(Format::Webp),
+            Some("png") => Some(Format::Png),
+            Some("jpeg") | Some("jpg") => Some(Format::Jpeg),
+            Some(f) => return Err(format!("invalid format: {}", f)),
+        };
+
+        let quality = match query.get("q") {
+            Some(_) if matches!(format, Some(Format::Png) | Some(Format::Webp)) => {
+                return Err("quality only applies to jpeg".to_string())
+            }
+            Some(q) => match q.parse::<u8>() {
+                Ok(q) if (1..=100).contains(&q) => q,
+                _ => return Err(format!("invalid quality: {}", q)),
+            },
+            None => 80,
+        };
+
+        Ok(Some(Self {
+            width,
+            height,
+            fit,
+            quality,
+            format,
+        }))
+    }
+
+    pub fn output_format(&self, mime_type: &str) -> Format {
+        match &self.format {
+            Some(f) => f.clone(),
+            None => match mime_type {
+                "image/png" => Format::Png,
+                "image/webp" => Format::Webp,
+                _ => Format::Jpeg,
+            },
+        }
+    }
+
+    pub fn cache_name(&self, unique_name: &str, format: &Format) -> String {
+        let fit = match self.fit {
+            Fit::Contain => "contain",
+            Fit::Cover => "cover",
+            Fit::Fill => "fill",
+        };
+
+        // png and webp are lossless, the quality doesn't change them
+        let quality = match format {
+            Format::Jpeg => format!("_q{}", self.quality),
+            _ => "".to_string(),
+        };
+
+        format!(
+            "{}_{}x{}_{}{}.{}",
+            unique_name,
+            self.width.map(|w| w.to_string()).unwrap_or_default(),
+            self.height.map(|h| h.to_string()).unwrap_or_default(),
+            fit,
+            quality,
+            format.extension()
+        )
+    }
+
+    pub fn apply(&self, content: &[u8], format: &Format) -> Result<Vec<u8>, String> {
+        let img = image::load_from_memory(content).map_err(|e| e.to_string())?;
+
+        let img = match (self.width, self.height) {
+            (None, None) => img,
+            (w, h) => {
+                let w = w.unwrap_or_else(|| scale(img.width(), img.height(), h.unwrap()));
+                let h = h.unwrap_or_else(|| scale(img.height(), img.width(), w));
+
+                match self.fit {
+                    Fit::Contain => img.resize(w, h, FilterType::Lanczos3),
+                    Fit::Cover => img.resize_to_fill(w, h, FilterType::Lanczos3),
+                    Fit::Fill => img.resize_exact(w, h, FilterType::Lanczos3),
+                }
+            }
+        };
+
+        encode(img, format, self.quality)
+    }
+}
+
+fn parse_dimension(value: Option<&String>, max_dimension: u32) -> Result<Option<u32>, String> {
+    match value {
+        Some(v) => match v.parse::<u32>() {
+            Ok(d) if d > 0 && d <= max_dimension => Ok(Some(d)),
+            Ok(d) if d > max_dimension => Err(format!(
+                "dimension {} exceeds maximum of {}",
+                d, max_dimension
+            )),
+            _ => Err(format!("invalid dimension: {}", v)),
+        },
+        None => Ok(None),
+    }
+}
+
+fn scale(side: u32, other_side: u32, other_target: u32) -> u32 {
+    let scaled = side as u64 * other_target as u64 / other_side.max(1) as u64;
+    scaled.max(1) as u32
+}
+
+fn encode(img: DynamicImage, format: &Format, quality: u8) -> Result<Vec<u8>, String> {
+    let mut out = Cursor::new(Vec::new());
+
+    let res = match format {
+        Format::Jpeg => {
+            let rgb = img.to_rgb8();
+            JpegEncoder::new_with_quality(&mut out, quality).write_image(
+                rgb.as_raw(),
+                rgb.width(),
+                rgb.height(),
+                image::ColorType::Rgb8,
+            )
+        }
+        Format::Png => {
+            let rgba = img.to_rgba8();
+            PngEncoder::new(&mut out).write_image(
+                rgba.as_raw(),
+                rgba.width(),
+                rgba.height(),
+                image::ColorType::Rgba8,
+            )
+        }
+        Format::Webp => {
+            let rgba = img.to_rgba8();
+            WebPEncoder::new_lossless(&mut out).write_image(
+                rgba.as_raw(),
+                rgba.width(),
+                rgba.height(),
+                image::ColorType::Rgba8,
+            )
+        }
+    };
+
+    match res {
+        Ok(_) => Ok(out.into_inner()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{Fit, Format, ImageTransform};
+
+    #[test]
+    fn test1() {
+        let query = HashMap::from([("token".to_string(), "abc".to_string())]);
+
+        assert_eq!(ImageTransform::from_query(&query, 2048), Ok(None));
+    }
+
+    #[test]
+    fn test2() {
+        let query = HashMap::from([
+            ("w".to_string(), "200".to_string()),
+            ("fit".to_string(), "cover".to_string()),
+            ("format".to_string(), "webp".to_string()),
+        ]);
+
+        let transform = ImageTransform::from_query(&query, 2048).unwrap().unwrap();
+
+        assert_eq!(transform.width, Some(200));
+        assert_eq!(transform.height, None);
+        assert_eq!(transform.fit, Fit::Cover);
+        assert_eq!(transform.format, Some(Format::Webp));
+        assert_eq!(
+            transform.cache_name("abc.png", &Format::Webp),
+            "abc.png_200x_cover.webp"
+        );
+        assert_eq!(
+            transform.cache_name("abc.png", &Format::Jpeg),
+            "abc.png_200x_cover_q80.jpeg"
+        );
+    }
+
+    #[test]
+    fn test3() {
+        let query = HashMap::from([("w".to_string(), "5000".to_string())]);
+
+        assert!(ImageTransform::from_query(&query, 2048).is_err());
+    }
+
+    #[test]
+    fn test4() {
+        let query = HashMap::from([
+            ("q".to_string(), "50".to_string()),
+            ("format".to_string(), "png".to_string()),
+        ]);
+        assert_eq!(
+            ImageTransform::from_query(&query, 2048),
+            Err("quality only applies to jpeg".to_string())
+        );
+
+        let query = HashMap::from([
+            ("q".to_string(), "50".to_string()),
+            ("format".to_string(), "jpg".to_string()),
+        ]);
+        let transform = ImageTransform::from_query(&query, 2048).unwrap().unwrap();
+        assert_eq!(transform.quality, 50);
+
+        // without a format the output follows the file, a png source ignores q
+        let query = HashMap::from([("q".to_string(), "50".to_string())]);
+        let transform = ImageTransform::from_query(&query, 2048).unwrap().unwrap();
+        let format = transform.output_format("image/png");
+        assert_eq!(
+            transform.cache_name("abc.png", &format),
+            "abc.png_x_contain.png"
+        );
+    }
+}
@@ -0,0 +1,431 @@
+use std::{
+    collections::HashSet,
+    path::Path as FsPath,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    body::Body,
+    extract::Path,
+    http::{HeaderMap, StatusCode},
+    response::Response,
+    Extension,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::StreamExt;
+use tokio::{
+    fs::{self, OpenOptions},
+    io::{AsyncSeekExt, AsyncWriteExt},
+};
+
+use crate::queries::{
+    model::{TusUpload, UserStorage},
+    Model,
+};
+
+const TUS_VERSION: &str = "1.0.0";
+const UPLOAD_DIR: &str = "uploads";
+const TUS_DIR: &str = "uploads/tus";
+
+// uploads with a PATCH in flight, a second one for the same upload gets a 409
+#[derive(Debug, Default)]
+pub struct UploadLocks {
+    uploads: Mutex<HashSet<String>>,
+}
+
+struct UploadLock {
+    locks: Arc<UploadLocks>,
+    upload_id: String,
+}
+
+impl UploadLocks {
+    fn lock(self: &Arc<Self>, upload_id: &str) -> Option<UploadLock> {
+        if !self.uploads.lock().unwrap().insert(upload_id.to_string()) {
+            return None;
+        }
+
+        Some(UploadLock {
+            locks: self.clone(),
+            upload_id: upload_id.to_string(),
+        })
+    }
+}
+
+impl Drop for UploadLock {
+    fn drop(&mut self) {
+        self.locks.uploads.lock().unwrap().remove(&self.upload_id);
+    }
+}
+
+pub async fn options() -> Response {
+    tus_response(StatusCode::NO_CONTENT)
+        .header("Tus-Version", TUS_VERSION)
+        .header("Tus-Extension", "creation,termination")
+        .body(Body::empty())
+        .unwrap()
+}
+
+pub async fn create(
+    Extension(model): Extension<Model>,
+    Extension(user_storage): Extension<Option<UserStorage>>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let user_id = writable_user(user_storage)?;
+
+    let upload_length = match header_i64(&headers, "Upload-Length") {
+        Some(l) if l >= 0 => l,
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let file_name = match headers
+        .get("Upload-Metadata")
+        .and_then(|m| m.to_str().ok())
+        .and_then(|m| metadata_value(m, "filename"))
+    {
+        Some(f) => f,
+        None => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let upload_id = uuid::Uuid::new_v4().to_string();
+
+    if fs::create_dir_all(TUS_DIR).await.is_err()
+        || fs::File::create(tus_path(&upload_id)).await.is_err()
+    {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let res = model
+        .add_tus_upload(upload_id.clone(), file_name, upload_length, user_id)
+        .await;
+
+    if let Err(e) = res {
+        log::error!("unable to create upload: {}", e);
+        let _ = fs::remove_file(tus_path(&upload_id)).await;
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    Ok(tus_response(StatusCode::CREATED)
+        .header("Location", format!("/storage/tus/{}", upload_id))
+        .body(Body::empty())
+        .unwrap())
+}
+
+pub async fn head(
+    Extension(model): Extension<Model>,
+    Extension(user_storage): Extension<Option<UserStorage>>,
+    Path(upload_id): Path<String>,
+) -> Result<Response, StatusCode> {
+    let user_id = writable_user(user_storage)?;
+    let upload = get_upload(&model, &upload_id, user_id).await?;
+
+    Ok(tus_response(StatusCode::OK)
+        .header("Upload-Offset", upload.upload_offset)
+        .header("Upload-Length", upload.upload_length)
+        .header("Cache-Control", "no-store")
+        .body(Body::empty())
+        .unwrap())
+}
+
+pub async fn patch(
+    Extension(model): Extension<Model>,
+    Extension(user_storage): Extension<Option<UserStorage>>,
+    Path(upload_id): Path<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, StatusCode> {
+    let user_id = writable_user(user_storage)?;
+
+    let content_type = headers.get("Content-Type").and_then(|c| c.to_str().ok());
+    if content_type != Some("application/offset+octet-stream") {
+        return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    let upload = get_upload(&model, &upload_id, user_id).await?;
+
+    let _lock = match model.tus_locks.lock(&upload.id) {
+        Some(lock) => lock,
+        None => return Err(StatusCode::CONFLICT),
+    };
+
+    // read again under the lock, a chunk may have landed since the first read
+    let upload = get_upload(&model, &upload_id, user_id).await?;
+    if header_i64(&headers, "Upload-Offset") != Some(upload.upload_offset) {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let mut file = match OpenOptions::new()
+        .write(true)
+        .open(tus_path(&upload.id))
+        .await
+    {
+        Ok(f) => f,
+        Err(_) => return Err(StatusCode::NOT_FOUND),
+    };
+
+    // bytes written after the last persisted offset are not trusted
+    if file.set_len(upload.upload_offset as u64).await.is_err()
+        || file
+            .seek(std::io::SeekFrom::Start(upload.upload_offset as u64))
+            .await
+            .is_err()
+    {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let mut offset = upload.upload_offset;
+    let mut stream = body.into_data_stream();
+    let mut failed = false;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(c) => c,
+            Err(_) => {
+                failed = true;
+                break;
+            }
+        };
+
+        if offset + chunk.len() as i64 > upload.upload_length {
+            failed = true;
+            break;
+        }
+
+        if file.write_all(&chunk).await.is_err() {
+            failed = true;
+            break;
+        }
+        offset += chunk.len() as i64;
+    }
+
+    if file.flush().await.is_err() {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    // only moves forward from the offset this request started at, another instance may share the db
+    if offset != upload.upload_offset {
+        match model
+            .update_tus_upload_offset(upload.id.clone(), upload.upload_offset, offset)
+            .await
+        {
+            Ok(true) => {}
+            Ok(false) => return Err(StatusCode::CONFLICT),
+            Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        }
+    }
+
+    if failed {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut builder = tus_response(StatusCode::NO_CONTENT).header("Upload-Offset", offset);
+
+    if offset == upload.upload_length {
+        let file_id = complete_upload(&model, upload, UPLOAD_DIR).await?;
+        builder = builder.header("Upload-File-Id", file_id);
+    }
+
+    Ok(builder.body(Body::empty()).unwrap())
+}
+
+pub async fn terminate(
+    Extension(model): Extension<Model>,
+    Extension(user_storage): Extension<Option<UserStorage>>,
+    Path(upload_id): Path<String>,
+) -> Result<Response, StatusCode> {
+    let user_id = writable_user(user_storage)?;
+    let upload = get_upload(&model, &upload_id, user_id).await?;
+
+    if model.delete_tus_upload(upload.id.clone()).await.is_err() {
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    let _ = fs::remove_file(tus_path(&upload.id)).await;
+
+    Ok(tus_response(StatusCode::NO_CONTENT)
+        .body(Body::empty())
+        .unwrap())
+}
+
+// the file is registered before it is moved, on failure the upload stays as it is and an
+// empty PATCH at the final offset tries again
+async fn complete_upload(
+    model: &Model,
+    upload: TusUpload,
+    upload_dir: &str,
+) -> Result<i64, StatusCode> {
+    let filename_arr = upload.file_name.split('.').collect::<Vec<&str>>();
+    let generated_name = format!("{}.{}", upload.id, filename_arr.last().unwrap());
+
+    let res = model
+        .upload_file(upload.file_name, generated_name.clone(), upload.uploaded_by)
+        .await;
+
+    let file_id = match res {
+        Ok(id) => id,
+        Err(e) => {
+            log::error!("unable to register upload: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let from = FsPath::new(upload_dir).join("tus").join(&upload.id);
+    if let Err(e) = fs::rename(from, FsPath::new(upload_dir).join(&generated_name)).await {
+        log::error!("unable to move upload: {}", e);
+        let _ = model.delete_file(file_id).await;
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let _ = model.delete_tus_upload(upload.id).await;
+
+    Ok(file_id)
+}
+
+async fn get_upload(model: &Model, upload_id: &str, user_id: i64) -> Result<TusUpload, StatusCode> {
+    if uuid::Uuid::parse_str(upload_id).is_err() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    match model.get_tus_upload_by_id(upload_id).await {
+        Ok(upload) if upload.uploaded_by == user_id => Ok(upload),
+        Ok(_) => Err(StatusCode::FORBIDDEN),
+        Err(_) => Err(StatusCode::NOT_FOUND),
+    }
+}
+
+fn writable_user(user_storage: Option<UserStorage>) -> Result<i64, StatusCode> {
+    match user_storage {
+        Some(user) if user.can_write => Ok(user.id),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+fn tus_response(status: StatusCode) -> axum::http::response::Builder {
+    Response::builder()
+        .status(status)
+        .header("Tus-Resumable", TUS_VERSION)
+}
+
+fn tus_path(upload_id: &str) -> String {
+    format!("{}/{}", TUS_DIR, upload_id)
+}
+
+fn header_i64(headers: &HeaderMap, name: &str) -> Option<i64> {
+    headers
+        .get(name)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.parse::<i64>().ok())
+}
+
+fn metadata_value(metadata: &str, key: &str) -> Option<String> {
+    metadata.split(',').find_map(|pair| {
+        let mut it = pair.trim().splitn(2, ' ');
+        match (it.next(), it.next()) {
+            (Some(k), Some(v)) if k == key => STANDARD
+                .decode(v)
+                .ok()
+                .and_then(|d| String::from_utf8(d).ok()),
+            _ => None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use crate::{
+        database::{model::DbType, sqlite::Sqlite, Conn},
+        queries::Model,
+    };
+
+    use std::sync::Arc;
+
+    use super::{complete_upload, metadata_value, UploadLocks};
+
+    #[test]
+    fn test1() {
+        let metadata = "filename d29ybGRfZG9taW5hdGlvbl9wbGFuLnBkZg==,is_confidential";
+
+        assert_eq!(
+            metadata_value(metadata, "filename"),
+            Some("world_domination_plan.pdf".to_string())
+        );
+        assert_eq!(metadata_value(metadata, "is_confidential"), None);
+    }
+
+    #[tokio::test]
+    async fn test2() {
+        let dir = std::env::temp_dir().join(format!("mini-base-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("tus")).unwrap();
+        let upload_dir = dir.to_str().unwrap();
+
+        let mut model = Model::with_config_path(dir.join("config").to_str().unwrap());
+        let dbpath = dir.join("test.db");
+        let mut sqlite = Sqlite::new(dbpath.to_str().unwrap()).await;
+        // one connection, so the retry can't land on one still reading before the delete
+        sqlite.connection = Ok(SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect(dbpath.to_str().unwrap())
+            .await
+            .unwrap());
+        model.conn = Some(Conn {
+            dbtype: DbType::Sqlite,
+            err: None,
+            sqlite: Some(sqlite),
+            mysql: None,
+        });
+
+        model
+            .create_user("a@example.com".to_string(), "pw".to_string())
+            .await
+            .unwrap();
+        let user_id = model.get_user_by_email("a@example.com").await.unwrap().id;
+
+        let upload_id = uuid::Uuid::new_v4().to_string();
+        model
+            .add_tus_upload(upload_id.clone(), "a.txt".to_string(), 2, user_id)
+            .await
+            .unwrap();
+        std::fs::write(dir.join("tus").join(&upload_id), "hi").unwrap();
+
+        // an existing row with the same unique name makes registering fail
+        let generated_name = format!("{}.txt", upload_id);
+        let taken = model
+            .upload_file("b.txt".to_string(), generated_name.clone(), user_id)
+            .await
+            .unwrap();
+
+        let upload = model.get_tus_upload_by_id(&upload_id).await.unwrap();
+        let res = complete_upload(&model, upload, upload_dir).await;
+        assert_eq!(res, Err(StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(dir.join("tus").join(&upload_id).exists());
+        assert!(!dir.join(&generated_name).exists());
+
+        model.delete_file(taken).await.unwrap();
+
+        let upload = model.get_tus_upload_by_id(&upload_id).await.unwrap();
+        let file_id = complete_upload(&model, upload, upload_dir).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join(&generated_name)).unwrap(),
+            "hi"
+        );
+        assert_eq!(
+            model.get_file_by_id(file_id).await.unwrap().unique_name,
+            generated_name
+        );
+        assert!(model.get_tus_upload_by_id(&upload_id).await.is_err());
+    }
+
+    #[test]
+    fn test3() {
+        let locks = Arc::new(UploadLocks::default());
+
+        let lock = locks.lock("a");
+        assert!(lock.is_some());
+        assert!(locks.lock("a").is_none());
+        assert!(locks.lock("b").is_some());
+
+        drop(lock);
+        assert!(locks.lock("a").is_some());
+    }
+}
@@ -1,7 +1,7 @@
 use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Number, Value};
 use sha2::{Digest, Sha256};
 
 use crate::{parser::parse_type, queries::model::User};
@@ -27,6 +27,9 @@ pub struct Utils {
     pub ips: Vec<String>,
     pub auth_secret: String,
     pub storage_secret: String,
+    pub max_image_dimension: u32,
+    pub max_in_list: usize,
+    pub metrics_token: String,
 }
 
 impl Utils {
@@ -116,20 +119,35 @@ pub fn hash_password(password: String) -> String {
     format!("{:x}", hash)
 }
 
+// anything that doesn't parse as what it looks like stays text
 pub fn extract_type_from_string(val: &str) -> Value {
-    let val_type = parse_type(val);
-
-    match val_type {
-        "string" => Value::String(val.to_string()),
-        "number" => Value::Number(val.parse().unwrap()),
-        "bool" => {
-            let mut t = false;
-            if val == "true" {
-                t = true;
-            }
-            Value::Bool(t)
-        }
-        "null" => Value::Null,
-        _ => panic!(),
+    match parse_type(val) {
+        "number" => match val.parse::<Number>() {
+            Ok(n) => Value::Number(n),
+            Err(_) => Value::String(val.to_string()),
+        },
+        "bool" if val == "true" => Value::Bool(true),
+        "bool" if val == "false" => Value::Bool(false),
+        "null" if val.is_empty() || val == "null" => Value::Null,
+        _ => Value::String(val.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::extract_type_from_string;
+
+    #[test]
+    fn test1() {
+        assert_eq!(extract_type_from_string("12"), json!(12));
+        assert_eq!(extract_type_from_string("1.5"), json!(1.5));
+        assert_eq!(extract_type_from_string("true"), json!(true));
+        assert_eq!(extract_type_from_string(""), json!(null));
+        assert_eq!(extract_type_from_string("1.2.3"), json!("1.2.3"));
+        assert_eq!(extract_type_from_string("5abc"), json!("5abc"));
+        assert_eq!(extract_type_from_string("trueish"), json!("trueish"));
+        assert_eq!(extract_type_from_string("-"), json!("-"));
     }
 }
@@ -0,0 +1,503 @@
+use std::{collections::HashMap, str::FromStr, time::Instant};
+
+use axum::http::StatusCode;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::header::HeaderMap;
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::{
+    database::model::ColType,
+    error::AppError,
+    parser,
+    queries::{
+        model::{ChangeLog, Webhook, WebhookDelivery, WebhookOutbox},
+        Model,
+    },
+};
+
+const OUTBOX_BATCH_SIZE: i64 = 10;
+const CHANGE_BATCH_SIZE: i64 = 50;
+const MAX_ATTEMPTS: i64 = 8;
+const BASE_BACKOFF_SECS: i64 = 5;
+const MAX_BACKOFF_SECS: i64 = 3600;
+const RESPONSE_SNIPPET_LEN: usize = 512;
+
+pub struct WebhookRequest {
+    pub webhook_id: i64,
+    pub method: String,
+    pub url: String,
+    pub payload: String,
+    pub secret: String,
+}
+
+pub struct Delivery {
+    pub status_code: Option<u16>,
+    pub body: String,
+    pub error: String,
+}
+
+impl Delivery {
+    pub fn is_success(&self) -> bool {
+        self.error.is_empty() && matches!(self.status_code, Some(200..=299))
+    }
+}
+
+pub async fn run_webhook(
+    model: Model,
+    args_map: HashMap<String, Option<ColType>>,
+    webhooks: &[Webhook],
+    action_type: &str,
+) -> Result<Vec<(Webhook, Value)>, AppError> {
+    let mut responses = vec![];
+    for webhook in webhooks {
+        if webhook.action != action_type {
+            continue;
+        }
+
+        if !["get", "post", "put", "delete"].contains(&webhook.exec_type.as_str()) {
+            return Err(AppError::Internal(format!(
+                "webhook {} has an invalid type: {}",
+                webhook.name, webhook.exec_type
+            )));
+        }
+
+        let payload = parser::replace_variables_with_values(&webhook.args, args_map.clone());
+
+        // only webhooks whose result is needed by the request are sent inline
+        if action_type == "after" && !webhook.is_returned {
+            let res = model
+                .add_webhook_outbox(
+                    webhook.id,
+                    webhook.exec_type.clone(),
+                    webhook.url.clone(),
+                    payload,
+                )
+                .await;
+
+            if let Err(e) = res {
+                log::error!("unable to queue webhook {}: {}", webhook.name, e);
+            }
+            continue;
+        }
+
+        let request = WebhookRequest {
+            webhook_id: webhook.id,
+            method: webhook.exec_type.clone(),
+            url: webhook.url.clone(),
+            payload,
+            secret: webhook.secret.clone(),
+        };
+
+        let delivery = deliver(&model, &request).await;
+
+        if delivery.is_success() {
+            let body = serde_json::from_str::<Value>(&delivery.body).unwrap_or(Value::Null);
+            responses.push((webhook.clone(), body));
+        } else if webhook.is_returned {
+            return match delivery.status_code {
+                Some(code) => Err(AppError::Webhook(
+                    StatusCode::from_u16(code).unwrap_or(StatusCode::BAD_GATEWAY),
+                    format!("webhook {} responded with status {}", webhook.name, code),
+                )),
+                None => {
+                    log::error!("webhook {} failed: {}", webhook.name, delivery.error);
+                    Err(AppError::Webhook(
+                        StatusCode::BAD_GATEWAY,
+                        format!("webhook {} could not be reached", webhook.name),
+                    ))
+                }
+            };
+        }
+    }
+    Ok(responses)
+}
+
+// `keep` only waits for the webhook, the response stays the query result
+pub fn apply_webhook_response(res: Value, return_mode: &str, body: Value) -> Value {
+    match (return_mode, body) {
+        ("replace", body) => body,
+        ("merge", Value::Object(fields)) => {
+            let mut out = match res {
+                Value::Object(o) => o,
+                res => serde_json::Map::from_iter([("data".to_string(), res)]),
+            };
+            out.extend(fields);
+
+            Value::Object(out)
+        }
+        (_, _) => res,
+    }
+}
+
+pub async fn deliver(model: &Model, request: &WebhookRequest) -> Delivery {
+    let start = Instant::now();
+
+    let delivery = match build_request(model, request) {
+        Ok(builder) => match builder.send().await {
+            Ok(res) => {
+                let status_code = res.status().as_u16();
+                let body = res.text().await.unwrap_or_default();
+
+                Delivery {
+                    status_code: Some(status_code),
+                    body,
+                    error: "".to_string(),
+                }
+            }
+            Err(e) => Delivery {
+                status_code: None,
+                body: "".to_string(),
+                error: e.without_url().to_string(),
+            },
+        },
+        Err(e) => Delivery {
+            status_code: None,
+            body: "".to_string(),
+            error: e,
+        },
+    };
+
+    let latency = start.elapsed();
+
+    let res = model
+        .add_webhook_delivery(WebhookDelivery {
+            id: 0,
+            webhook_id: request.webhook_id,
+            method: request.method.clone(),
+            url: request.url.clone(),
+            payload: request.payload.clone(),
+            status_code: delivery.status_code.map(|c| c as i64),
+            latency_ms: latency.as_millis() as i64,
+            response: delivery.body.chars().take(RESPONSE_SNIPPET_LEN).collect(),
+            error: delivery.error.clone(),
+            created_at: Utc::now().timestamp(),
+        })
+        .await;
+
+    if let Err(e) = res {
+        log::error!("unable to record webhook delivery: {}", e);
+    }
+
+    model
+        .metrics
+        .record_webhook(request.webhook_id, delivery.is_success(), latency);
+
+    delivery
+}
+
+fn build_request(
+    model: &Model,
+    request: &WebhookRequest,
+) -> Result<reqwest::RequestBuilder, String> {
+    let client = &model.http;
+
+    let mut builder = match request.method.as_str() {
+        "get" => client.get(&request.url),
+        "post" => client.post(&request.url),
+        "put" => client.put(&request.url),
+        "delete" => client.delete(&request.url),
+        _ => return Err("invalid type".to_string()),
+    };
+
+    let args: Value = serde_json::from_str(&request.payload)
+        .map_err(|e| format!("invalid webhook payload: {}", e))?;
+
+    let header = &args["header"];
+    let query = &args["query"];
+    let body = &args["body"];
+
+    let mut headermap = HeaderMap::new();
+    if let Some(h) = header.as_object() {
+        for (k, v) in h.iter() {
+            let key = reqwest::header::HeaderName::from_str(k);
+            let val = reqwest::header::HeaderValue::from_str(v.as_str().unwrap_or(""));
+
+            if let (Ok(k), Ok(v)) = (key, val) {
+                headermap.insert(k, v);
+            }
+        }
+    }
+
+    if !headermap.is_empty() {
+        builder = builder.headers(headermap);
+    }
+    if !query.is_null() {
+        builder = builder.query(query);
+    }
+
+    let mut body_bytes = vec![];
+    if !body.is_null() {
+        body_bytes = serde_json::to_vec(body).map_err(|e| e.to_string())?;
+        builder = builder
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body_bytes.clone());
+    }
+
+    if !request.secret.is_empty() {
+        builder = builder.header(
+            "X-Minibase-Signature",
+            format!("sha256={}", sign_payload(&request.secret, &body_bytes)),
+        );
+    }
+
+    Ok(builder)
+}
+
+pub fn sign_payload(secret: &str, payload: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any size");
+    mac.update(payload);
+
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
+pub async fn run_outbox_worker(model: Model) {
+    loop {
+        let now = Utc::now().timestamp();
+
+        match model.get_due_webhook_outbox(now, OUTBOX_BATCH_SIZE).await {
+            Ok(entries) => {
+                futures::future::join_all(
+                    entries
+                        .into_iter()
+                        .map(|entry| deliver_outbox(&model, entry)),
+                )
+                .await;
+            }
+            Err(e) => log::error!("unable to read webhook outbox: {}", e),
+        }
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+}
+
+async fn deliver_outbox(model: &Model, entry: WebhookOutbox) {
+    let request = WebhookRequest {
+        webhook_id: entry.webhook_id,
+        method: entry.method,
+        url: entry.url,
+        payload: entry.payload,
+        secret: entry.secret,
+    };
+
+    let delivery = deliver(model, &request).await;
+    let attempts = entry.attempts + 1;
+
+    let res = if delivery.is_success() {
+        model
+            .update_webhook_outbox(entry.id, "delivered", attempts, 0)
+            .await
+    } else if attempts >= MAX_ATTEMPTS {
+        log::error!(
+            "webhook delivery {} failed after {} attempts",
+            entry.id,
+            attempts
+        );
+        model
+            .update_webhook_outbox(entry.id, "failed", attempts, 0)
+            .await
+    } else {
+        let next_attempt_at = Utc::now().timestamp() + backoff_secs(attempts);
+        model
+            .update_webhook_outbox(entry.id, "pending", attempts, next_attempt_at)
+            .await
+    };
+
+    if let Err(e) = res {
+        log::error!("unable to update webhook outbox: {}", e);
+    }
+}
+
+pub async fn run_change_dispatcher(model: Model) {
+    loop {
+        match model.get_unprocessed_changes(CHANGE_BATCH_SIZE).await {
+            Ok(changes) => {
+                for change in changes {
+                    dispatch_change(&model, change).await;
+                }
+            }
+            Err(e) => log::error!("unable to read change log: {}", e),
+        }
+
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+}
+
+async fn dispatch_change(model: &Model, change: ChangeLog) {
+    let webhooks = match model
+        .get_webhooks_by_table_event(&change.table_name, &change.event)
+        .await
+    {
+        Ok(w) => w,
+        Err(e) => {
+            log::error!("unable to read triggers for {}: {}", change.table_name, e);
+            return;
+        }
+    };
+
+    let args_map = change_args(&change);
+
+    for webhook in webhooks {
+        let payload = parser::replace_variables_with_values(&webhook.args, args_map.clone());
+
+        let res = model
+            .add_webhook_outbox(webhook.id, webhook.exec_type, webhook.url, payload)
+            .await;
+
+        if let Err(e) = res {
+            log::error!("unable to queue webhook {}: {}", webhook.name, e);
+        }
+    }
+
+    let _ = model.changes.send(vec![change.table_name.clone()]);
+
+    if let Err(e) = model.mark_change_processed(change.id).await {
+        log::error!("unable to mark change {} processed: {}", change.id, e);
+    }
+}
+
+fn change_args(change: &ChangeLog) -> HashMap<String, Option<ColType>> {
+    let row = |r: &Option<String>| {
+        r.as_ref()
+            .and_then(|r| serde_json::from_str::<Value>(r).ok())
+            .map(ColType::get_col_type_from_value)
+    };
+
+    HashMap::from([
+        (
+            "table".to_string(),
+            Some(ColType::String(Some(change.table_name.clone()))),
+        ),
+        (
+            "event".to_string(),
+            Some(ColType::String(Some(change.event.clone()))),
+        ),
+        ("old".to_string(), row(&change.old_row)),
+        ("new".to_string(), row(&change.new_row)),
+    ])
+}
+
+fn backoff_secs(attempts: i64) -> i64 {
+    let exp = (attempts - 1).clamp(0, 20) as u32;
+
+    (BASE_BACKOFF_SECS * 2_i64.pow(exp)).min(MAX_BACKOFF_SECS)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        parser,
+        queries::{model::ChangeLog, Model},
+    };
+
+    use super::{
+        apply_webhook_response, backoff_secs, build_request, change_args, sign_payload,
+        WebhookRequest,
+    };
+
+    #[test]
+    fn test1() {
+        let res = json!([{"id": 1}]);
+        let body = json!({"plan": "pro"});
+
+        assert_eq!(
+            apply_webhook_response(res, "merge", body),
+            json!({"data": [{"id": 1}], "plan": "pro"})
+        );
+    }
+
+    #[test]
+    fn test2() {
+        let res = json!([{"id": 1}]);
+        let body = json!({"plan": "pro"});
+
+        assert_eq!(
+            apply_webhook_response(res, "replace", body),
+            json!({"plan": "pro"})
+        );
+
+        let res = json!([{"id": 1}]);
+        assert_eq!(
+            apply_webhook_response(res.clone(), "keep", json!({"plan": "pro"})),
+            res
+        );
+    }
+
+    #[test]
+    fn test3() {
+        assert_eq!(backoff_secs(1), 5);
+        assert_eq!(backoff_secs(2), 10);
+        assert_eq!(backoff_secs(4), 40);
+        assert_eq!(backoff_secs(30), 3600);
+    }
+
+    #[test]
+    fn test4() {
+        assert_eq!(
+            sign_payload("key", b"The quick brown fox jumps over the lazy dog"),
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn test5() {
+        let change = ChangeLog {
+            id: 1,
+            table_name: "todos".to_string(),
+            event: "update".to_string(),
+            old_row: Some("{\"id\": 3, \"title\": \"old\"}".to_string()),
+            new_row: Some("{\"id\": 3, \"title\": \"new\"}".to_string()),
+        };
+
+        assert_eq!(
+            parser::replace_variables_with_values(
+                "{ \"body\": { \"id\": ${new.id}, \"from\": ${old.title}, \"to\": ${new.title}, \"event\": ${event} } }",
+                change_args(&change)
+            ),
+            "{ \"body\": { \"id\": 3, \"from\": \"old\", \"to\": \"new\", \"event\": \"update\" } }"
+        );
+    }
+
+    #[test]
+    fn test6() {
+        let change = ChangeLog {
+            id: 1,
+            table_name: "todos".to_string(),
+            event: "insert".to_string(),
+            old_row: None,
+            new_row: Some(json!({"title": "a\\\", \"admin\": true, \"x\": \""}).to_string()),
+        };
+
+        let payload = parser::replace_variables_with_values(
+            "{ \"body\": { \"title\": ${new.title} } }",
+            change_args(&change),
+        );
+        let payload: serde_json::Value = serde_json::from_str(&payload).unwrap();
+        assert_eq!(
+            payload,
+            json!({"body": {"title": "a\\\", \"admin\": true, \"x\": \""}})
+        );
+    }
+
+    #[test]
+    fn test7() {
+        let dir = std::env::temp_dir().join(format!("mini-base-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let model = Model::with_config_path(dir.join("config").to_str().unwrap());
+
+        let request = WebhookRequest {
+            webhook_id: 1,
+            method: "post".to_string(),
+            url: "http://localhost".to_string(),
+            payload: "{ \"body\": { \"title\": \"a\" \"b\" } }".to_string(),
+            secret: "".to_string(),
+        };
+
+        let err = build_request(&model, &request).err().unwrap();
+        assert!(err.starts_with("invalid webhook payload"), "{}", err);
+    }
+}
@@ -1,7 +1,8 @@
 use cursive::{
     align::Align,
+    direction::Orientation,
     view::{Nameable, Scrollable},
-    views::{Dialog, ListView, NamedView, TextView},
+    views::{Dialog, LinearLayout, ListView, NamedView, TextView},
     Cursive,
 };
 
@@ -12,10 +13,23 @@ pub fn api_dashboard(s: &mut Cursive) -> NamedView<Dialog> {
 
     let apis = update_apis(s, apis);
 
+    let rest_apis = ListView::new();
+
+    let rest_apis = update_rest_apis(s, rest_apis);
+
     Dialog::new()
         .title("Api")
-        .content(apis.with_name("server_apis").scrollable())
+        .content(
+            LinearLayout::new(Orientation::Vertical)
+                .child(apis.with_name("server_apis"))
+                .child(rest_apis.with_name("rest_apis"))
+                .scrollable(),
+        )
         .padding_lrtb(1, 1, 1, 0)
+        .button("refresh", |s: &mut Cursive| {
+            let apis = update_apis(s, ListView::new());
+            s.call_on_name("server_apis", |list: &mut ListView| *list = apis);
+        })
         .with_name(Sidebar::Api.to_string())
 }
 
@@ -44,16 +58,59 @@ fn update_apis(s: &mut Cursive, mut apis: ListView) -> ListView {
         "/storage/generate-token",
         TextView::new("post").align(Align::center_right()),
     );
+    apis.add_child(
+        "/storage/tus",
+        TextView::new("post").align(Align::center_right()),
+    );
+    apis.add_child(
+        "/realtime",
+        TextView::new("ws").align(Align::center_right()),
+    );
+    apis.add_child(
+        "/realtime/sse",
+        TextView::new("get").align(Align::center_right()),
+    );
 
     let model = get_current_mut_model(s);
     let optional_queries = futures::executor::block_on(model.get_all_apis());
+    let cache_stats = model.metrics.cache_stats();
 
     match optional_queries {
         Ok(queries) => {
             for q in queries {
+                let endpoint = format!("/api/{}", q.name);
+                let label = match cache_stats.get(&endpoint) {
+                    Some((hits, misses)) => {
+                        format!("{}  {} hits / {} misses", q.exec_type, hits, misses)
+                    }
+                    None => q.exec_type,
+                };
+
+                apis.add_child(endpoint, TextView::new(label).align(Align::center_right()));
+            }
+        }
+        Err(e) => {
+            s.add_layer(Dialog::info(e));
+        }
+    }
+
+    apis
+}
+
+pub fn update_rest_apis(s: &mut Cursive, mut apis: ListView) -> ListView {
+    let model = get_current_mut_model(s);
+    let optional_rest_tables = futures::executor::block_on(model.get_all_rest_tables());
+
+    match optional_rest_tables {
+        Ok(rest_tables) => {
+            for t in rest_tables {
+                apis.add_child(
+                    format!("/rest/{}", t.table_name),
+                    TextView::new("get, post").align(Align::center_right()),
+                );
                 apis.add_child(
-                    format!("/api/{}", q.name),
-                    TextView::new(q.exec_type).align(Align::center_right()),
+                    format!("/rest/{}/:id", t.table_name),
+                    TextView::new("get, put, delete").align(Align::center_right()),
                 );
             }
         }
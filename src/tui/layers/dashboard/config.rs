@@ -28,6 +28,9 @@ pub fn config_dashboard(s: &mut Cursive) -> NamedView<Dialog> {
                 ips: "".to_string(),
                 auth_secret: "".to_string(),
                 storage_secret: "".to_string(),
+                max_image_dimension: "".to_string(),
+                max_in_list: "".to_string(),
+                metrics_token: "".to_string(),
             }
         }
     };
@@ -53,6 +56,27 @@ pub fn config_dashboard(s: &mut Cursive) -> NamedView<Dialog> {
             .content(config_data.storage_secret)
             .with_name("storage_secret"),
     );
+    list.add_child(
+        "Max Image Dimension",
+        EditView::new()
+            .on_edit(on_data_changes)
+            .content(config_data.max_image_dimension)
+            .with_name("max_image_dimension"),
+    );
+    list.add_child(
+        "Max IN List Length",
+        EditView::new()
+            .on_edit(on_data_changes)
+            .content(config_data.max_in_list)
+            .with_name("max_in_list"),
+    );
+    list.add_child(
+        "Metrics Token",
+        EditView::new()
+            .on_edit(on_data_changes)
+            .content(config_data.metrics_token)
+            .with_name("metrics_token"),
+    );
 
     Dialog::new()
         .title("Config")
@@ -89,10 +113,25 @@ fn on_save(s: &mut Cursive) {
         .get_content()
         .to_string();
 
+    let max_image_dimension = get_data_from_refname::<EditView>(s, "max_image_dimension")
+        .get_content()
+        .to_string();
+
+    let max_in_list = get_data_from_refname::<EditView>(s, "max_in_list")
+        .get_content()
+        .to_string();
+
+    let metrics_token = get_data_from_refname::<EditView>(s, "metrics_token")
+        .get_content()
+        .to_string();
+
     let config = Config {
         ips: ips.clone(),
         auth_secret: auth_secret.clone(),
         storage_secret: storage_secret.clone(),
+        max_image_dimension: max_image_dimension.clone(),
+        max_in_list: max_in_list.clone(),
+        metrics_token: metrics_token.clone(),
     };
 
     let model = get_current_mut_model(s);
@@ -107,6 +146,9 @@ fn on_save(s: &mut Cursive) {
     model.utils.ips = ips.split(',').map(|ip| ip.to_string()).collect();
     model.utils.auth_secret = auth_secret;
     model.utils.storage_secret = storage_secret;
+    model.utils.max_image_dimension = max_image_dimension.parse().unwrap_or(2048);
+    model.utils.max_in_list = max_in_list.parse().unwrap_or(1000);
+    model.utils.metrics_token = metrics_token;
 
     let model = get_current_model(s);
     thread::spawn(|| {
@@ -5,14 +5,16 @@ use cursive::{
 };
 
 use crate::{
-    queries::model::Migration,
+    queries::{migration_file::MIGRATION_DIR, model::Migration, schema_diff::SCHEMA_FILE},
     tui::{
         components::{
             self,
             selector::{add_select_item, get_select_item, remove_select_item, update_select_item},
         },
         model::Sidebar,
-        utils::{get_current_model, get_current_mut_model, get_data_from_refname},
+        utils::{
+            format_timestamp, get_current_model, get_current_mut_model, get_data_from_refname,
+        },
     },
 };
 
@@ -20,37 +22,47 @@ pub fn migration_dashboard(s: &mut Cursive) -> NamedView<Dialog> {
     let on_up = |s: &mut Cursive| {
         let model = get_current_model(s);
 
+        match futures::executor::block_on(model.sync_migration_files(MIGRATION_DIR)) {
+            Ok(report) => {
+                for (id, name) in report.added {
+                    add_select_item(s, "migration_list", name, id as usize);
+                }
+
+                if !report.warnings.is_empty() {
+                    s.add_layer(Dialog::info(report.warnings.join("\n")));
+                }
+            }
+            Err(e) => {
+                s.add_layer(Dialog::info(e));
+                return;
+            }
+        }
+
         let optional_migrations = futures::executor::block_on(model.get_up_migrations());
 
         match optional_migrations {
             Ok(migrations) => {
                 for migration in migrations {
-                    let res = futures::executor::block_on(model.run_migration(migration.up_query));
+                    let res = futures::executor::block_on(model.apply_migration(&migration));
 
                     match res {
                         Ok(_) => {
-                            if futures::executor::block_on(
-                                model.update_executed_migration(migration.id, true),
-                            )
-                            .is_ok()
-                            {
-                                let mut item_string =
-                                    get_select_item(s, "migration_list", migration.id as usize);
-
-                                if !item_string.contains("(up)") {
-                                    item_string += "(up)";
-                                }
-
-                                update_select_item(
-                                    s,
-                                    "migration_list",
-                                    item_string,
-                                    migration.id as usize,
-                                );
+                            let mut item_string =
+                                get_select_item(s, "migration_list", migration.id as usize);
+
+                            if !item_string.contains("(up)") {
+                                item_string += "(up)";
                             }
+
+                            update_select_item(
+                                s,
+                                "migration_list",
+                                item_string,
+                                migration.id as usize,
+                            );
                         }
                         Err(e) => {
-                            s.add_layer(Dialog::info(e));
+                            s.add_layer(Dialog::info(format!("{}: {}", migration.name, e)));
                             return;
                         }
                     }
@@ -68,31 +80,24 @@ pub fn migration_dashboard(s: &mut Cursive) -> NamedView<Dialog> {
         match optional_migrations {
             Ok(migrations) => {
                 for migration in migrations {
-                    let res =
-                        futures::executor::block_on(model.run_migration(migration.down_query));
+                    let res = futures::executor::block_on(model.revert_migration(&migration));
 
                     match res {
                         Ok(_) => {
-                            if futures::executor::block_on(
-                                model.update_executed_migration(migration.id, false),
-                            )
-                            .is_ok()
-                            {
-                                let mut item_string =
-                                    get_select_item(s, "migration_list", migration.id as usize);
-
-                                item_string = item_string.replace("(up)", "");
-
-                                update_select_item(
-                                    s,
-                                    "migration_list",
-                                    item_string,
-                                    migration.id as usize,
-                                );
-                            }
+                            let mut item_string =
+                                get_select_item(s, "migration_list", migration.id as usize);
+
+                            item_string = item_string.replace("(up)", "").replace("(drift)", "");
+
+                            update_select_item(
+                                s,
+                                "migration_list",
+                                item_string,
+                                migration.id as usize,
+                            );
                         }
                         Err(e) => {
-                            s.add_layer(Dialog::info(e));
+                            s.add_layer(Dialog::info(format!("{}: {}", migration.name, e)));
                             return;
                         }
                     }
@@ -109,6 +114,7 @@ pub fn migration_dashboard(s: &mut Cursive) -> NamedView<Dialog> {
     let model = get_current_mut_model(s);
 
     let optional_migrations = futures::executor::block_on(model.get_all_migrations());
+    let drifted = futures::executor::block_on(model.get_drifted_migrations()).unwrap_or_default();
 
     let mut migrations = vec![];
 
@@ -123,7 +129,9 @@ pub fn migration_dashboard(s: &mut Cursive) -> NamedView<Dialog> {
         migrations
             .into_iter()
             .map(|m| {
-                if m.executed {
+                if drifted.contains(&m.id) {
+                    (m.id as usize, m.name + "(up)(drift)")
+                } else if m.executed {
                     (m.id as usize, m.name + "(up)")
                 } else {
                     (m.id as usize, m.name)
@@ -141,6 +149,8 @@ pub fn migration_dashboard(s: &mut Cursive) -> NamedView<Dialog> {
         .button("up", on_up)
         .button("down", on_down)
         .button("add migration", add_migration)
+        .button("generate", generate_migration)
+        .button("export", export_migrations)
         .with_name(Sidebar::Migration.to_string())
 }
 
@@ -157,8 +167,25 @@ fn edit_migration(s: &mut Cursive, idx: usize) {
         }
     };
 
+    let drifted = futures::executor::block_on(model.get_drifted_migrations()).unwrap_or_default();
+
     let mut list = ListView::new();
 
+    list.add_child(
+        "Status",
+        TextView::new(
+            match (migration.executed, drifted.contains(&migration.id)) {
+                (true, true) => "applied, changed since it was applied",
+                (true, false) => "applied",
+                (false, _) => "pending",
+            },
+        ),
+    );
+
+    if let Some(applied_at) = migration.applied_at {
+        list.add_child("Applied At", TextView::new(format_timestamp(applied_at)));
+    }
+
     list.add_child(
         "Up Migration",
         Button::new("", move |s: &mut Cursive| {
@@ -375,3 +402,54 @@ fn add_migration(s: &mut Cursive) {
             .button("cancel", on_cancel),
     );
 }
+
+fn generate_migration(s: &mut Cursive) {
+    let model = get_current_model(s);
+
+    match futures::executor::block_on(model.generate_schema_migration(SCHEMA_FILE)) {
+        Ok(Some(id)) => {
+            match futures::executor::block_on(model.get_migration_name_by_id(id)) {
+                Ok(m) => add_select_item(s, "migration_list", m.name, id as usize),
+                Err(e) => {
+                    s.add_layer(Dialog::info(e));
+                    return;
+                }
+            }
+
+            edit_migration(s, id as usize);
+        }
+        Ok(None) => s.add_layer(Dialog::info(format!(
+            "database already matches {}",
+            SCHEMA_FILE
+        ))),
+        Err(e) => s.add_layer(Dialog::info(e)),
+    }
+}
+
+fn export_migrations(s: &mut Cursive) {
+    let model = get_current_model(s);
+
+    match futures::executor::block_on(model.export_migration_files(MIGRATION_DIR)) {
+        Ok(exported) => {
+            let optional_migrations = futures::executor::block_on(model.get_all_migrations());
+
+            if let Ok(migrations) = optional_migrations {
+                for m in migrations {
+                    let mut item_string = m.name;
+                    if m.executed {
+                        item_string += "(up)";
+                    }
+
+                    update_select_item(s, "migration_list", item_string, m.id as usize);
+                }
+            }
+
+            s.add_layer(Dialog::info(format!(
+                "exported {} migrations to {}/",
+                exported.len(),
+                MIGRATION_DIR
+            )));
+        }
+        Err(e) => s.add_layer(Dialog::info(e)),
+    }
+}
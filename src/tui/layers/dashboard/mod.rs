@@ -19,6 +19,9 @@ pub mod config;
 pub mod migration;
 pub mod query;
 pub mod role;
+pub mod scheduler;
+pub mod stats;
+pub mod table;
 pub mod user;
 pub mod webhook;
 
@@ -80,8 +83,11 @@ pub fn display_dashboard(s: &mut Cursive) {
     dashboards.add_screen(user::user_dashboard(s).full_screen());
     dashboards.add_screen(query::query_dashboard(s).full_screen());
     dashboards.add_screen(webhook::webhook_dashboard(s).full_screen());
+    dashboards.add_screen(scheduler::scheduler_dashboard(s).full_screen());
     dashboards.add_screen(migration::migration_dashboard(s).full_screen());
+    dashboards.add_screen(table::table_dashboard(s).full_screen());
     dashboards.add_screen(api::api_dashboard(s).full_screen());
+    dashboards.add_screen(stats::stats_dashboard(s).full_screen());
 
     s.add_layer(
         LinearLayout::new(Orientation::Horizontal)
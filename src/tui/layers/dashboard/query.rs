@@ -1,14 +1,17 @@
 use cursive::{
     align::Align,
+    direction::Orientation,
     view::{Nameable, Scrollable},
     views::{
-        Button, Dialog, EditView, ListChild, ListView, NamedView, SelectView, TextArea, TextView,
+        Button, Dialog, EditView, LinearLayout, ListChild, ListView, NamedView, RadioGroup,
+        SelectView, TextArea, TextView,
     },
-    Cursive,
+    Cursive, With,
 };
 
 use crate::{
-    queries::model::{Query, QueryAccess, WebhookQuery},
+    parser,
+    queries::model::{Query, QueryAccess, QueryCache, QueryPagination, QueryShape, WebhookQuery},
     tui::{
         components::{
             self,
@@ -201,6 +204,53 @@ fn edit_query(s: &mut Cursive, idx: usize) {
         }),
     );
 
+    list.add_child(
+        "Pagination",
+        Button::new("", move |s: &mut Cursive| {
+            // both dialogs check the other one, pagination only works with the flat shape
+            if let Err(e) = load_pagination_and_shape(s, idx as i64) {
+                s.add_layer(Dialog::info(e));
+                return;
+            }
+
+            edit_query_pagination(s);
+        }),
+    );
+
+    list.add_child(
+        "Response",
+        Button::new("", move |s: &mut Cursive| {
+            if let Err(e) = load_pagination_and_shape(s, idx as i64) {
+                s.add_layer(Dialog::info(e));
+                return;
+            }
+
+            edit_query_shape(s);
+        }),
+    );
+
+    list.add_child(
+        "Cache",
+        Button::new("", move |s: &mut Cursive| {
+            let model = get_current_mut_model(s);
+
+            if model.temp.query_cache.is_none() {
+                let optional_cache =
+                    futures::executor::block_on(model.get_query_cache_by_id(idx as i64));
+
+                match optional_cache {
+                    Ok(c) => model.temp.query_cache = Some(c),
+                    Err(e) => {
+                        s.add_layer(Dialog::info(e));
+                        return;
+                    }
+                }
+            }
+
+            edit_query_cache(s);
+        }),
+    );
+
     list.add_child(
         "Editor",
         Button::new("", move |s: &mut Cursive| {
@@ -324,6 +374,13 @@ fn edit_query(s: &mut Cursive, idx: usize) {
 
         let model = get_current_mut_model(s);
 
+        if model.temp.query_written {
+            if let Err(e) = parser::validate_query(&model.temp.query_string) {
+                s.add_layer(Dialog::info(e));
+                return;
+            }
+        }
+
         let res1 = futures::executor::block_on(model.edit_query(Query {
             id: idx as i64,
             name: label.clone(),
@@ -335,6 +392,36 @@ fn edit_query(s: &mut Cursive, idx: usize) {
             return;
         }
 
+        if let Some(pagination) = model.temp.query_pagination.take() {
+            let res: Result<u64, String> =
+                futures::executor::block_on(model.edit_query_pagination(idx as i64, pagination));
+
+            if let Err(e) = res {
+                s.add_layer(Dialog::info(e));
+                return;
+            }
+        }
+
+        if let Some(shape) = model.temp.query_shape.take() {
+            let res: Result<u64, String> =
+                futures::executor::block_on(model.edit_query_shape(idx as i64, shape));
+
+            if let Err(e) = res {
+                s.add_layer(Dialog::info(e));
+                return;
+            }
+        }
+
+        if let Some(cache) = model.temp.query_cache.take() {
+            let res: Result<u64, String> =
+                futures::executor::block_on(model.edit_query_cache(idx as i64, cache));
+
+            if let Err(e) = res {
+                s.add_layer(Dialog::info(e));
+                return;
+            }
+        }
+
         let query_access = model.temp.query_access.clone();
         model.temp.query_access.clear();
 
@@ -383,7 +470,7 @@ fn edit_query(s: &mut Cursive, idx: usize) {
         let res = update_select_item(s, "query_list", label.clone(), idx);
         if let Some(i) = res {
             s.call_on_name("server_apis", move |list: &mut ListView| {
-                let l = list.row_mut(i + 6);
+                let l = list.row_mut(i + 9);
                 if let ListChild::Row(_, _) = l {
                     *l = ListChild::Row(
                         format!("/api/{}", label.clone()),
@@ -407,6 +494,9 @@ fn edit_query(s: &mut Cursive, idx: usize) {
                     let model = get_current_mut_model(s);
                     model.temp.query_written = false;
                     model.temp.query_access_update = false;
+                    model.temp.query_pagination = None;
+                    model.temp.query_shape = None;
+                    model.temp.query_cache = None;
 
                     let res = futures::executor::block_on(model.delete_query(idx as i64));
 
@@ -421,7 +511,7 @@ fn edit_query(s: &mut Cursive, idx: usize) {
                     let res = remove_select_item(s, "query_list", idx);
                     if let Some(i) = res {
                         s.call_on_name("server_apis", |list: &mut ListView| {
-                            list.remove_child(i + 6);
+                            list.remove_child(i + 9);
                         });
                     }
 
@@ -435,6 +525,9 @@ fn edit_query(s: &mut Cursive, idx: usize) {
         let model = get_current_mut_model(s);
         model.temp.query_written = false;
         model.temp.query_access_update = false;
+        model.temp.query_pagination = None;
+        model.temp.query_shape = None;
+        model.temp.query_cache = None;
 
         s.pop_layer();
     };
@@ -450,6 +543,281 @@ fn edit_query(s: &mut Cursive, idx: usize) {
     );
 }
 
+fn load_pagination_and_shape(s: &mut Cursive, query_id: i64) -> Result<(), String> {
+    let model = get_current_mut_model(s);
+
+    if model.temp.query_pagination.is_none() {
+        let pagination = futures::executor::block_on(model.get_query_pagination_by_id(query_id))?;
+        model.temp.query_pagination = Some(pagination);
+    }
+
+    if model.temp.query_shape.is_none() {
+        let shape = futures::executor::block_on(model.get_query_shape_by_id(query_id))?;
+        model.temp.query_shape = Some(shape);
+    }
+
+    Ok(())
+}
+
+fn edit_query_pagination(s: &mut Cursive) {
+    let model = get_current_model(s);
+    let pagination = model.temp.query_pagination.unwrap();
+
+    let mut list = ListView::new();
+
+    let mut mode_group: RadioGroup<String> = RadioGroup::new();
+    let mut modes = LinearLayout::new(Orientation::Horizontal);
+    for mode in ["none", "offset", "cursor"] {
+        modes.add_child(mode_group.button(mode.to_string(), mode).with_if(
+            pagination.pagination == mode,
+            |b| {
+                b.select();
+            },
+        ));
+    }
+    list.add_child("Mode", modes);
+
+    list.add_child(
+        "Max Page Size",
+        EditView::new()
+            .content(pagination.page_size.to_string())
+            .with_name("edit_query_page_size"),
+    );
+
+    list.add_child(
+        "Cursor Column",
+        EditView::new()
+            .content(pagination.cursor_column)
+            .with_name("edit_query_cursor_column"),
+    );
+
+    let mut total_group: RadioGroup<bool> = RadioGroup::new();
+    list.add_child(
+        "Total",
+        LinearLayout::new(Orientation::Horizontal)
+            .child(total_group.button(false, "False"))
+            .child(
+                total_group
+                    .button(true, "True")
+                    .with_if(pagination.with_total, |b| {
+                        b.select();
+                    }),
+            ),
+    );
+
+    let on_submit = move |s: &mut Cursive| {
+        let page_size_ref = get_data_from_refname::<EditView>(s, "edit_query_page_size");
+        let page_size = match page_size_ref.get_content().parse::<i64>() {
+            Ok(p) if p > 0 => p,
+            _ => {
+                s.add_layer(Dialog::info("max page size must be a positive number"));
+                return;
+            }
+        };
+
+        let cursor_column_ref = get_data_from_refname::<EditView>(s, "edit_query_cursor_column");
+        let cursor_column = cursor_column_ref.get_content().to_string();
+
+        let pagination = mode_group.selection().to_string();
+        if pagination == "cursor" && cursor_column.is_empty() {
+            s.add_layer(Dialog::info("cursor pagination needs a cursor column"));
+            return;
+        }
+
+        let model = get_current_mut_model(s);
+        let shape = model.temp.query_shape.as_ref().map(|q| q.shape.as_str());
+        if pagination != "none" && shape.is_some_and(|shape| shape != "flat") {
+            s.add_layer(Dialog::info("pagination only works with the flat shape"));
+            return;
+        }
+
+        model.temp.query_pagination = Some(QueryPagination {
+            pagination,
+            page_size,
+            cursor_column,
+            with_total: *total_group.selection(),
+        });
+
+        s.pop_layer();
+    };
+
+    s.add_layer(
+        Dialog::new()
+            .title("Pagination")
+            .content(list)
+            .padding_lrtb(1, 1, 1, 0)
+            .button("submit", on_submit)
+            .button("cancel", |s: &mut Cursive| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn edit_query_shape(s: &mut Cursive) {
+    let model = get_current_model(s);
+    let shape = model.temp.query_shape.unwrap();
+
+    let mut list = ListView::new();
+
+    let mut shape_group: RadioGroup<String> = RadioGroup::new();
+    let mut shapes = LinearLayout::new(Orientation::Horizontal);
+    for mode in ["flat", "nested", "single"] {
+        shapes.add_child(shape_group.button(mode.to_string(), mode).with_if(
+            shape.shape == mode,
+            |b| {
+                b.select();
+            },
+        ));
+    }
+    list.add_child("Shape", shapes);
+
+    list.add_child(
+        "Group By",
+        EditView::new()
+            .content(shape.group_by)
+            .with_name("edit_query_group_by"),
+    );
+
+    list.add_child(
+        "Max Rows",
+        EditView::new()
+            .content(shape.max_rows.to_string())
+            .with_name("edit_query_max_rows"),
+    );
+
+    let on_submit = move |s: &mut Cursive| {
+        let group_by_ref = get_data_from_refname::<EditView>(s, "edit_query_group_by");
+        let group_by = group_by_ref.get_content().to_string();
+
+        let max_rows_ref = get_data_from_refname::<EditView>(s, "edit_query_max_rows");
+        let max_rows = match max_rows_ref.get_content().parse::<i64>() {
+            Ok(m) if m >= 0 => m,
+            _ => {
+                s.add_layer(Dialog::info("max rows must be 0 or a positive number"));
+                return;
+            }
+        };
+
+        let shape = shape_group.selection().to_string();
+
+        let model = get_current_mut_model(s);
+        let pagination = model
+            .temp
+            .query_pagination
+            .as_ref()
+            .map(|p| p.pagination.as_str());
+        if shape != "flat" && pagination.is_some_and(|pagination| pagination != "none") {
+            s.add_layer(Dialog::info("pagination only works with the flat shape"));
+            return;
+        }
+
+        model.temp.query_shape = Some(QueryShape {
+            shape,
+            group_by,
+            max_rows,
+        });
+
+        s.pop_layer();
+    };
+
+    s.add_layer(
+        Dialog::new()
+            .title("Response")
+            .content(list)
+            .padding_lrtb(1, 1, 1, 0)
+            .button("submit", on_submit)
+            .button("cancel", |s: &mut Cursive| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn edit_query_cache(s: &mut Cursive) {
+    let model = get_current_model(s);
+    let cache = model.temp.query_cache.unwrap();
+
+    let mut list = ListView::new();
+
+    list.add_child(
+        "TTL (seconds)",
+        EditView::new()
+            .content(cache.cache_ttl.to_string())
+            .with_name("edit_query_cache_ttl"),
+    );
+
+    list.add_child(
+        "Key Params",
+        EditView::new()
+            .content(cache.cache_params)
+            .with_name("edit_query_cache_params"),
+    );
+
+    let mut per_user_group: RadioGroup<bool> = RadioGroup::new();
+    list.add_child(
+        "Per User",
+        LinearLayout::new(Orientation::Horizontal)
+            .child(per_user_group.button(false, "False"))
+            .child(
+                per_user_group
+                    .button(true, "True")
+                    .with_if(cache.cache_per_user, |b| {
+                        b.select();
+                    }),
+            ),
+    );
+
+    list.add_child(
+        "Max Entries",
+        EditView::new()
+            .content(cache.cache_max_entries.to_string())
+            .with_name("edit_query_cache_max_entries"),
+    );
+
+    let on_submit = move |s: &mut Cursive| {
+        let ttl_ref = get_data_from_refname::<EditView>(s, "edit_query_cache_ttl");
+        let cache_ttl = match ttl_ref.get_content().parse::<i64>() {
+            Ok(t) if t >= 0 => t,
+            _ => {
+                s.add_layer(Dialog::info("ttl must be 0 or a positive number"));
+                return;
+            }
+        };
+
+        let params_ref = get_data_from_refname::<EditView>(s, "edit_query_cache_params");
+        let cache_params = params_ref.get_content().to_string();
+
+        let max_entries_ref = get_data_from_refname::<EditView>(s, "edit_query_cache_max_entries");
+        let cache_max_entries = match max_entries_ref.get_content().parse::<i64>() {
+            Ok(m) if m > 0 => m,
+            _ => {
+                s.add_layer(Dialog::info("max entries must be a positive number"));
+                return;
+            }
+        };
+
+        let model = get_current_mut_model(s);
+        model.temp.query_cache = Some(QueryCache {
+            cache_ttl,
+            cache_params,
+            cache_per_user: *per_user_group.selection(),
+            cache_max_entries,
+        });
+
+        s.pop_layer();
+    };
+
+    s.add_layer(
+        Dialog::new()
+            .title("Cache")
+            .content(list)
+            .padding_lrtb(1, 1, 1, 0)
+            .button("submit", on_submit)
+            .button("cancel", |s: &mut Cursive| {
+                s.pop_layer();
+            }),
+    );
+}
+
 fn add_query(s: &mut Cursive) {
     let on_submit = |s: &mut Cursive| {
         let label_ref = get_data_from_refname::<EditView>(s, "add_query_text");
@@ -0,0 +1,463 @@
+use chrono::Utc;
+use cursive::{
+    direction::Orientation,
+    view::{Nameable, Resizable, Scrollable},
+    views::{
+        Button, Dialog, EditView, LinearLayout, ListView, NamedView, RadioGroup, SelectView,
+        TextArea, TextView,
+    },
+    Cursive, With,
+};
+use serde_json::Value;
+
+use crate::{
+    queries::{model::Schedule, Model},
+    server::scheduler::{next_run_at, parse_cron},
+    tui::{
+        components::{
+            self,
+            selector::{add_select_item, remove_select_item, select_component, update_select_item},
+        },
+        model::Sidebar,
+        utils::{
+            format_timestamp, get_current_model, get_current_mut_model, get_data_from_refname,
+        },
+    },
+};
+
+pub fn scheduler_dashboard(s: &mut Cursive) -> NamedView<Dialog> {
+    let model = get_current_mut_model(s);
+
+    let on_select = |s: &mut Cursive, idx: &usize| {
+        edit_schedule(s, *idx);
+    };
+
+    let optional_schedules = futures::executor::block_on(model.get_all_schedules());
+
+    let mut schedules = vec![];
+
+    match optional_schedules {
+        Ok(sc) => {
+            schedules = sc;
+        }
+        Err(e) => s.add_layer(Dialog::info(e)),
+    }
+    let schedule_list = components::selector::select_component(
+        schedules
+            .into_iter()
+            .map(|r| (r.id as usize, r.name))
+            .collect(),
+        "schedule_list",
+        on_select,
+    );
+
+    Dialog::new()
+        .title("Scheduler")
+        .content(schedule_list)
+        .padding_lrtb(1, 1, 1, 0)
+        .button("Add Schedule", add_schedule)
+        .with_name(Sidebar::Scheduler.to_string())
+}
+
+fn add_schedule(s: &mut Cursive) {
+    let on_submit = |s: &mut Cursive| {
+        let schedule_ref = get_data_from_refname::<EditView>(s, "add_schedule_name");
+        let schedule_name = schedule_ref.get_content().to_string();
+
+        let model = get_current_mut_model(s);
+        let res = futures::executor::block_on(model.add_new_schedule(schedule_name.clone()));
+
+        match res {
+            Ok(i) => {
+                add_select_item(s, "schedule_list", schedule_name, i as usize);
+
+                s.pop_layer();
+            }
+            Err(e) => {
+                s.add_layer(Dialog::info(e));
+            }
+        }
+    };
+
+    let on_cancel = |s: &mut Cursive| {
+        s.pop_layer();
+    };
+
+    let textedit = EditView::new();
+
+    s.add_layer(
+        Dialog::new()
+            .title("Add Schedule Name")
+            .padding_lrtb(1, 1, 1, 0)
+            .content(textedit.with_name("add_schedule_name"))
+            .button("submit", on_submit)
+            .button("cancel", on_cancel),
+    );
+}
+
+fn get_targets(model: &Model, target_type: &str) -> Result<Vec<(i64, String)>, String> {
+    match target_type {
+        "query" => futures::executor::block_on(model.get_all_queries())
+            .map(|q| q.into_iter().map(|q| (q.id, q.name)).collect()),
+        "webhook" => futures::executor::block_on(model.get_all_webhooks())
+            .map(|w| w.into_iter().map(|w| (w.id, w.name)).collect()),
+        t => Err(format!("invalid target type: {}", t)),
+    }
+}
+
+fn edit_schedule(s: &mut Cursive, idx: usize) {
+    let model = get_current_model(s);
+
+    let optional_schedule = futures::executor::block_on(model.get_schedule_by_id(idx as i64));
+    let schedule = match optional_schedule {
+        Ok(sc) => sc,
+        Err(e) => {
+            s.add_layer(Dialog::info(e));
+            return;
+        }
+    };
+
+    let target_name = get_targets(&model, &schedule.target_type)
+        .unwrap_or_default()
+        .into_iter()
+        .find(|(id, _)| *id == schedule.target_id)
+        .map(|(_, name)| name)
+        .unwrap_or_default();
+
+    let mut list = ListView::new();
+    list.add_child(
+        "Label",
+        EditView::new()
+            .content(schedule.name)
+            .with_name("edit_schedule_label"),
+    );
+
+    list.add_child(
+        "Cron",
+        EditView::new()
+            .content(schedule.cron)
+            .with_name("edit_schedule_cron"),
+    );
+
+    list.add_child(
+        "Target Type",
+        Button::new(schedule.target_type, |s: &mut Cursive| {
+            let items: Vec<(usize, String)> =
+                vec![(0, "query".to_string()), (1, "webhook".to_string())];
+
+            let target_type = select_component(
+                items.clone(),
+                "target_type",
+                move |s: &mut Cursive, idx: &usize| {
+                    let mut button_label_ref =
+                        get_data_from_refname::<Button>(s, "edit_schedule_target_type");
+
+                    let (_, selected_label) = items.get(*idx).unwrap();
+
+                    if button_label_ref.label().replace(['<', '>'], "") != *selected_label {
+                        button_label_ref.set_label(selected_label);
+
+                        let mut target_ref =
+                            get_data_from_refname::<Button>(s, "edit_schedule_target");
+                        target_ref.set_label("");
+                    }
+
+                    s.pop_layer();
+                },
+            );
+
+            s.add_layer(Dialog::new().content(target_type.scrollable()).button(
+                "cancel",
+                |s: &mut Cursive| {
+                    s.pop_layer();
+                },
+            ));
+
+            let button_label_ref = get_data_from_refname::<Button>(s, "edit_schedule_target_type");
+            let btn_label = button_label_ref.label().replace(['<', '>'], "").to_string();
+
+            let items: Vec<(usize, String)> =
+                vec![(0, "query".to_string()), (1, "webhook".to_string())];
+
+            let optional_i = items.iter().find(|(_, f)| *f == btn_label);
+            if let Some((i, _)) = optional_i {
+                let mut target_type_ref =
+                    get_data_from_refname::<SelectView<usize>>(s, "target_type");
+                target_type_ref.set_selection(*i);
+            }
+        })
+        .with_name("edit_schedule_target_type"),
+    );
+
+    list.add_child(
+        "Target",
+        Button::new(target_name, |s: &mut Cursive| {
+            let target_type_ref = get_data_from_refname::<Button>(s, "edit_schedule_target_type");
+            let target_type = target_type_ref.label().replace(['<', '>'], "").to_string();
+
+            let model = get_current_model(s);
+            let items: Vec<(usize, String)> = match get_targets(&model, &target_type) {
+                Ok(t) => t
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, (_, name))| (i, name))
+                    .collect(),
+                Err(e) => {
+                    s.add_layer(Dialog::info(e));
+                    return;
+                }
+            };
+
+            let targets = select_component(
+                items.clone(),
+                "schedule_target",
+                move |s: &mut Cursive, idx: &usize| {
+                    let mut button_label_ref =
+                        get_data_from_refname::<Button>(s, "edit_schedule_target");
+
+                    let (_, selected_label) = items.get(*idx).unwrap();
+
+                    button_label_ref.set_label(selected_label);
+
+                    s.pop_layer();
+                },
+            );
+
+            s.add_layer(Dialog::new().content(targets.scrollable()).button(
+                "cancel",
+                |s: &mut Cursive| {
+                    s.pop_layer();
+                },
+            ));
+        })
+        .with_name("edit_schedule_target"),
+    );
+
+    list.add_child(
+        "Args",
+        TextArea::new()
+            .content(schedule.args)
+            .with_name("edit_schedule_args")
+            .max_height(5)
+            .max_width(28),
+    );
+
+    let mut enabled_group: RadioGroup<bool> = RadioGroup::new();
+    list.add_child(
+        "Status",
+        LinearLayout::new(Orientation::Horizontal)
+            .child(enabled_group.button(false, "Paused"))
+            .child(
+                enabled_group
+                    .button(true, "Enabled")
+                    .with_if(schedule.enabled, |b| {
+                        b.select();
+                    }),
+            ),
+    );
+
+    list.add_child(
+        "Next Run",
+        TextView::new(match schedule.next_run_at {
+            0 => "".to_string(),
+            t => format_timestamp(t),
+        }),
+    );
+
+    list.add_child(
+        "Runs",
+        Button::new("", move |s: &mut Cursive| {
+            schedule_runs(s, idx);
+        }),
+    );
+
+    let on_submit = move |s: &mut Cursive| {
+        let label_ref = get_data_from_refname::<EditView>(s, "edit_schedule_label");
+        let label = label_ref.get_content().to_string();
+
+        let cron_ref = get_data_from_refname::<EditView>(s, "edit_schedule_cron");
+        let cron = cron_ref.get_content().trim().to_string();
+
+        let target_type_ref = get_data_from_refname::<Button>(s, "edit_schedule_target_type");
+        let target_type = target_type_ref.label().replace(['<', '>'], "").to_string();
+
+        let target_ref = get_data_from_refname::<Button>(s, "edit_schedule_target");
+        let target = target_ref.label().replace(['<', '>'], "").to_string();
+
+        let args_ref = get_data_from_refname::<TextArea>(s, "edit_schedule_args");
+        let args = args_ref.get_content().to_string();
+
+        let enabled = *enabled_group.selection();
+
+        if let Err(e) = parse_cron(&cron) {
+            s.add_layer(Dialog::info(e));
+            return;
+        }
+
+        if !matches!(serde_json::from_str::<Value>(&args), Ok(Value::Object(_))) {
+            s.add_layer(Dialog::info("args must be a json object"));
+            return;
+        }
+
+        let model = get_current_model(s);
+
+        let optional_target = get_targets(&model, &target_type).map(|t| {
+            t.into_iter()
+                .find(|(_, name)| *name == target)
+                .map(|(id, _)| id)
+        });
+        let target_id = match optional_target {
+            Ok(Some(id)) => id,
+            Ok(None) => {
+                s.add_layer(Dialog::info("select a target"));
+                return;
+            }
+            Err(e) => {
+                s.add_layer(Dialog::info(e));
+                return;
+            }
+        };
+
+        let next_run = match enabled {
+            true => match next_run_at(&cron, Utc::now().timestamp()) {
+                Ok(n) => n,
+                Err(e) => {
+                    s.add_layer(Dialog::info(e));
+                    return;
+                }
+            },
+            false => 0,
+        };
+
+        let res = futures::executor::block_on(model.edit_schedule(Schedule {
+            id: idx as i64,
+            name: label.clone(),
+            cron,
+            target_type,
+            target_id,
+            args,
+            enabled,
+            next_run_at: next_run,
+        }));
+
+        if let Err(e) = res {
+            s.add_layer(Dialog::info(e));
+            return;
+        }
+
+        update_select_item(s, "schedule_list", label.clone(), idx);
+
+        s.pop_layer();
+    };
+
+    let on_delete = move |s: &mut Cursive| {
+        s.add_layer(
+            Dialog::new()
+                .content(TextView::new("Are you sure you want to remove schedule?"))
+                .button("cancel", |s: &mut Cursive| {
+                    s.pop_layer();
+                })
+                .button("continue", move |s: &mut Cursive| {
+                    let model = get_current_mut_model(s);
+
+                    let res = futures::executor::block_on(model.delete_schedule(idx as i64));
+                    if let Err(e) = res {
+                        s.add_layer(Dialog::info(e));
+                        return;
+                    }
+
+                    remove_select_item(s, "schedule_list", idx);
+
+                    s.pop_layer();
+                    s.pop_layer();
+                }),
+        );
+    };
+
+    s.add_layer(
+        Dialog::new()
+            .title("Edit Schedule")
+            .content(list.scrollable())
+            .padding_lrtb(1, 1, 1, 0)
+            .button("submit", on_submit)
+            .button("delete", on_delete)
+            .button("cancel", |s: &mut Cursive| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn schedule_runs(s: &mut Cursive, schedule_id: usize) {
+    let model = get_current_model(s);
+
+    let optional_runs =
+        futures::executor::block_on(model.get_schedule_runs_by_schedule_id(schedule_id as i64));
+    let runs = match optional_runs {
+        Ok(r) => r,
+        Err(e) => {
+            s.add_layer(Dialog::info(e));
+            return;
+        }
+    };
+
+    let on_select = |s: &mut Cursive, idx: &usize| {
+        schedule_run(s, *idx);
+    };
+
+    let run_list = select_component(
+        runs.into_iter()
+            .map(|r| {
+                (
+                    r.id as usize,
+                    format!(
+                        "{} {} {}ms",
+                        format_timestamp(r.started_at),
+                        r.status,
+                        r.duration_ms
+                    ),
+                )
+            })
+            .collect(),
+        "schedule_run_list",
+        on_select,
+    );
+
+    s.add_layer(
+        Dialog::new()
+            .title("Runs")
+            .content(run_list)
+            .padding_lrtb(1, 1, 1, 0)
+            .button("cancel", |s: &mut Cursive| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn schedule_run(s: &mut Cursive, idx: usize) {
+    let model = get_current_model(s);
+
+    let optional_run = futures::executor::block_on(model.get_schedule_run_by_id(idx as i64));
+    let run = match optional_run {
+        Ok(r) => r,
+        Err(e) => {
+            s.add_layer(Dialog::info(e));
+            return;
+        }
+    };
+
+    let mut list = ListView::new();
+    list.add_child("Time", TextView::new(format_timestamp(run.started_at)));
+    list.add_child("Status", TextView::new(run.status));
+    list.add_child("Duration", TextView::new(format!("{}ms", run.duration_ms)));
+    list.add_child("Output", TextView::new(run.output));
+
+    s.add_layer(
+        Dialog::new()
+            .title("Run")
+            .content(list.scrollable())
+            .padding_lrtb(1, 1, 1, 0)
+            .button("cancel", |s: &mut Cursive| {
+                s.pop_layer();
+            }),
+    );
+}
@@ -0,0 +1,57 @@
+use cursive::{
+    align::Align,
+    view::{Nameable, Scrollable},
+    views::{Dialog, ListView, NamedView, TextView},
+    Cursive,
+};
+
+use crate::tui::{
+    model::Sidebar,
+    utils::{get_current_model, get_data_from_refname},
+};
+
+pub fn stats_dashboard(s: &mut Cursive) -> NamedView<Dialog> {
+    let mut stats = ListView::new();
+    add_stats_items(&mut stats, get_stats_items(s));
+
+    let on_refresh = |s: &mut Cursive| {
+        let items = get_stats_items(s);
+
+        let mut stats = get_data_from_refname::<ListView>(s, "endpoint_stats");
+        stats.clear();
+        add_stats_items(&mut stats, items);
+    };
+
+    Dialog::new()
+        .title("Stats (last minute)")
+        .content(stats.with_name("endpoint_stats").scrollable())
+        .padding_lrtb(1, 1, 1, 0)
+        .button("refresh", on_refresh)
+        .with_name(Sidebar::Stats.to_string())
+}
+
+fn get_stats_items(s: &mut Cursive) -> Vec<(String, String)> {
+    let model = get_current_model(s);
+
+    model
+        .metrics
+        .endpoint_stats()
+        .into_iter()
+        .map(|e| {
+            (
+                e.endpoint,
+                format!(
+                    "{:.2} req/s  {:.1}% errors",
+                    e.requests_per_sec,
+                    e.error_rate * 100.0
+                ),
+            )
+        })
+        .collect()
+}
+
+fn add_stats_items(stats: &mut ListView, items: Vec<(String, String)>) {
+    for (endpoint, stat) in items {
+        stats.add_child(endpoint, TextView::new(stat).align(Align::center_right()));
+    }
+}
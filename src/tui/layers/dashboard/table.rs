@@ -0,0 +1,572 @@
+use std::collections::BTreeMap;
+
+use cursive::{
+    align::Align,
+    direction::Orientation,
+    view::{Nameable, Resizable, Scrollable},
+    views::{Dialog, EditView, LinearLayout, ListView, NamedView, SelectView, TextView},
+    Cursive,
+};
+
+use crate::{
+    database::model::{ColInfo, ColType},
+    queries::{
+        model::QueryAccess,
+        rest::REST_OPERATIONS,
+        table::{format_col_value, parse_col_value},
+    },
+    tui::{
+        components::{
+            self,
+            checkbox_group::get_checked_data,
+            selector::{add_select_item, get_select_item},
+        },
+        layers::dashboard::api::update_rest_apis,
+        model::Sidebar,
+        utils::{get_current_model, get_current_mut_model, get_data_from_refname},
+    },
+};
+
+const PAGE_SIZE: i64 = 20;
+const CELL_WIDTH: usize = 16;
+
+pub fn table_dashboard(s: &mut Cursive) -> NamedView<Dialog> {
+    let on_select = |s: &mut Cursive, idx: &usize| {
+        let table_name = get_select_item(s, "table_list", *idx);
+        open_table(s, table_name);
+    };
+
+    let table_list =
+        components::selector::select_component(get_table_items(s), "table_list", on_select);
+
+    let on_refresh = |s: &mut Cursive| {
+        let items = get_table_items(s);
+
+        let mut table_list = get_data_from_refname::<SelectView<usize>>(s, "table_list");
+        table_list.clear();
+        for (idx, item) in items {
+            table_list.add_item(item, idx);
+        }
+    };
+
+    Dialog::new()
+        .title("Tables")
+        .content(table_list)
+        .padding_lrtb(1, 1, 1, 0)
+        .button("refresh", on_refresh)
+        .with_name(Sidebar::Tables.to_string())
+}
+
+fn get_table_items(s: &mut Cursive) -> Vec<(usize, String)> {
+    let model = get_current_model(s);
+
+    match futures::executor::block_on(model.get_all_tables()) {
+        Ok(tables) => tables
+            .into_iter()
+            .enumerate()
+            .map(|(i, t)| (i, t.name))
+            .collect(),
+        Err(e) => {
+            s.add_layer(Dialog::info(e));
+            vec![]
+        }
+    }
+}
+
+fn open_table(s: &mut Cursive, table_name: String) {
+    let model = get_current_model(s);
+
+    let columns = match futures::executor::block_on(model.get_table_columns(&table_name)) {
+        Ok(c) => c,
+        Err(e) => {
+            s.add_layer(Dialog::info(e));
+            return;
+        }
+    };
+
+    let model = get_current_mut_model(s);
+    model.offset.table = 0;
+
+    let mut column_list = ListView::new();
+    for c in columns.iter() {
+        let mut details = vec![c.ctype.clone()];
+        if c.pk {
+            details.push("primary key".to_string());
+        }
+        if c.notnull {
+            details.push("not null".to_string());
+        }
+        if let Some(d) = &c.dflt_value {
+            details.push(format!("default {}", d));
+        }
+
+        column_list.add_child(&c.name, TextView::new(details.join(", ")));
+    }
+
+    let header = columns
+        .iter()
+        .map(|c| cell(&c.name))
+        .collect::<Vec<String>>()
+        .join(" | ");
+
+    let select_table = table_name.clone();
+    let select_columns = columns.clone();
+    let on_select = move |s: &mut Cursive, idx: &usize| {
+        let offset = get_current_mut_model(s).offset.table;
+        let model = get_current_model(s);
+
+        let rows =
+            futures::executor::block_on(model.get_table_rows(&select_table, offset, PAGE_SIZE));
+        match rows {
+            Ok(rows) => match rows.into_iter().nth(*idx) {
+                Some(row) => edit_row(s, select_table.clone(), select_columns.clone(), Some(row)),
+                None => s.add_layer(Dialog::info("row no longer exists")),
+            },
+            Err(e) => s.add_layer(Dialog::info(e)),
+        }
+    };
+
+    let rows = LinearLayout::new(Orientation::Vertical)
+        .child(TextView::new(header))
+        .child(components::selector::select_component(
+            vec![],
+            "table_rows",
+            on_select,
+        ));
+
+    let prev_table = table_name.clone();
+    let prev_columns = columns.clone();
+    let on_prev = move |s: &mut Cursive| {
+        let model = get_current_mut_model(s);
+        model.offset.table = (model.offset.table - PAGE_SIZE).max(0);
+
+        refresh_rows(s, &prev_table, &prev_columns);
+    };
+
+    let next_table = table_name.clone();
+    let next_columns = columns.clone();
+    let on_next = move |s: &mut Cursive| {
+        let model = get_current_mut_model(s);
+        model.offset.table += PAGE_SIZE;
+
+        if !refresh_rows(s, &next_table, &next_columns) {
+            let model = get_current_mut_model(s);
+            model.offset.table -= PAGE_SIZE;
+
+            refresh_rows(s, &next_table, &next_columns);
+        }
+    };
+
+    let insert_table = table_name.clone();
+    let insert_columns = columns.clone();
+    let on_insert = move |s: &mut Cursive| {
+        edit_row(s, insert_table.clone(), insert_columns.clone(), None);
+    };
+
+    let rest_table_name = table_name.clone();
+    let on_rest = move |s: &mut Cursive| {
+        rest_settings(s, rest_table_name.clone());
+    };
+
+    s.add_layer(
+        Dialog::new()
+            .title(&table_name)
+            .content(
+                LinearLayout::new(Orientation::Vertical)
+                    .child(column_list.scrollable().max_height(8))
+                    .child(TextView::new("").with_name("table_page"))
+                    .child(rows.scrollable().scroll_x(true)),
+            )
+            .padding_lrtb(1, 1, 1, 0)
+            .button("prev", on_prev)
+            .button("next", on_next)
+            .button("insert", on_insert)
+            .button("rest", on_rest)
+            .button("close", |s: &mut Cursive| {
+                s.pop_layer();
+            })
+            .full_screen(),
+    );
+
+    refresh_rows(s, &table_name, &columns);
+}
+
+// returns false when the current page is empty
+fn refresh_rows(s: &mut Cursive, table_name: &str, columns: &[ColInfo]) -> bool {
+    let offset = get_current_mut_model(s).offset.table;
+    let model = get_current_model(s);
+
+    let rows =
+        match futures::executor::block_on(model.get_table_rows(table_name, offset, PAGE_SIZE)) {
+            Ok(r) => r,
+            Err(e) => {
+                s.add_layer(Dialog::info(e));
+                return false;
+            }
+        };
+
+    let mut page_ref = get_data_from_refname::<TextView>(s, "table_page");
+    page_ref.set_content(match rows.len() {
+        0 => "no rows".to_string(),
+        n => format!("rows {}-{}", offset + 1, offset + n as i64),
+    });
+
+    if rows.is_empty() && offset > 0 {
+        return false;
+    }
+
+    let mut rows_ref = get_data_from_refname::<SelectView<usize>>(s, "table_rows");
+    rows_ref.clear();
+
+    for (i, row) in rows.iter().enumerate() {
+        let label = columns
+            .iter()
+            .map(|c| cell(&row.get(&c.name).map(format_col_value).unwrap_or_default()))
+            .collect::<Vec<String>>()
+            .join(" | ");
+
+        rows_ref.add_item(label, i);
+    }
+
+    true
+}
+
+fn cell(value: &str) -> String {
+    let value = value.replace('\n', " ");
+
+    match value.chars().count() > CELL_WIDTH {
+        true => format!(
+            "{}…",
+            value.chars().take(CELL_WIDTH - 1).collect::<String>()
+        ),
+        false => format!("{:width$}", value, width = CELL_WIDTH),
+    }
+}
+
+fn edit_row(
+    s: &mut Cursive,
+    table_name: String,
+    columns: Vec<ColInfo>,
+    row: Option<BTreeMap<String, ColType>>,
+) {
+    let mut list = ListView::new();
+    let mut shown = vec![];
+
+    for (i, c) in columns.iter().enumerate() {
+        let value = row
+            .as_ref()
+            .and_then(|r| r.get(&c.name))
+            .map(format_col_value)
+            .filter(|v| v != "NULL")
+            .unwrap_or_default();
+        shown.push(value.clone());
+
+        // primary keys identify the row being edited so they stay read only
+        if c.pk && row.is_some() {
+            list.add_child(&c.name, TextView::new(value));
+        } else {
+            list.add_child(
+                &c.name,
+                EditView::new()
+                    .content(value)
+                    .with_name(format!("table_row_{}", i)),
+            );
+        }
+    }
+
+    let primary_key: Vec<(String, ColType)> = match &row {
+        Some(r) => columns
+            .iter()
+            .filter(|c| c.pk)
+            .filter_map(|c| r.get(&c.name).map(|v| (c.name.clone(), v.clone())))
+            .collect(),
+        None => vec![],
+    };
+    let is_insert = row.is_none();
+
+    let submit_table = table_name.clone();
+    let submit_columns = columns.clone();
+    let submit_primary_key = primary_key.clone();
+    let on_submit = move |s: &mut Cursive| {
+        let mut values = vec![];
+
+        for (i, c) in submit_columns.iter().enumerate() {
+            if c.pk && !is_insert {
+                continue;
+            }
+
+            let content = get_data_from_refname::<EditView>(s, &format!("table_row_{}", i))
+                .get_content()
+                .to_string();
+
+            // untouched fields are left alone, the text of a blob isn't its bytes
+            if !is_insert && content == shown[i] {
+                continue;
+            }
+
+            // empty fields fall back to the column default on insert and NULL on update
+            match (content.is_empty(), is_insert, c.notnull) {
+                (true, true, _) => {}
+                (true, false, false) => values.push((c.name.clone(), ColType::String(None))),
+                _ => match parse_col_value(c, &content) {
+                    Ok(value) => values.push((c.name.clone(), value)),
+                    Err(e) => {
+                        s.add_layer(Dialog::info(e));
+                        return;
+                    }
+                },
+            }
+        }
+
+        if !is_insert && values.is_empty() {
+            s.pop_layer();
+            return;
+        }
+
+        let model = get_current_model(s);
+
+        let res = match is_insert {
+            true => futures::executor::block_on(model.insert_table_row(&submit_table, values))
+                .map(|_| ()),
+            false => futures::executor::block_on(model.update_table_row(
+                &submit_table,
+                submit_primary_key.clone(),
+                values,
+            ))
+            .map(|_| ()),
+        };
+
+        match res {
+            Ok(_) => {
+                s.pop_layer();
+                refresh_rows(s, &submit_table, &submit_columns);
+            }
+            Err(e) => s.add_layer(Dialog::info(e)),
+        }
+    };
+
+    let delete_table = table_name.clone();
+    let delete_columns = columns.clone();
+    let on_delete = move |s: &mut Cursive| {
+        let table_name = delete_table.clone();
+        let columns = delete_columns.clone();
+        let primary_key = primary_key.clone();
+
+        s.add_layer(
+            Dialog::new()
+                .content(TextView::new("Are you sure you want to remove row?"))
+                .button("cancel", |s: &mut Cursive| {
+                    s.pop_layer();
+                })
+                .button("continue", move |s: &mut Cursive| {
+                    let model = get_current_model(s);
+
+                    let res = futures::executor::block_on(
+                        model.delete_table_row(&table_name, primary_key.clone()),
+                    );
+                    if let Err(e) = res {
+                        s.add_layer(Dialog::info(e));
+                        return;
+                    }
+
+                    s.pop_layer();
+                    s.pop_layer();
+                    refresh_rows(s, &table_name, &columns);
+                }),
+        );
+    };
+
+    let mut dialog = Dialog::new()
+        .title(match is_insert {
+            true => format!("Insert into {}", table_name),
+            false => format!("Edit {}", table_name),
+        })
+        .content(list.scrollable())
+        .padding_lrtb(1, 1, 1, 0)
+        .button("submit", on_submit);
+
+    if !is_insert {
+        dialog = dialog.button("delete", on_delete);
+    }
+
+    s.add_layer(dialog.button("cancel", |s: &mut Cursive| {
+        s.pop_layer();
+    }));
+}
+
+fn rest_settings(s: &mut Cursive, table_name: String) {
+    let model = get_current_model(s);
+
+    let rest_table = match futures::executor::block_on(model.get_rest_table_by_name(&table_name)) {
+        Ok(t) => t,
+        Err(_) => {
+            let on_expose = move |s: &mut Cursive| {
+                let model = get_current_model(s);
+
+                let res = futures::executor::block_on(model.add_rest_table(table_name.clone()));
+                if let Err(e) = res {
+                    s.add_layer(Dialog::info(e));
+                    return;
+                }
+
+                s.pop_layer();
+                refresh_rest_apis(s);
+                rest_settings(s, table_name.clone());
+            };
+
+            s.add_layer(
+                Dialog::new()
+                    .content(TextView::new(
+                        "Table is not exposed, generate rest endpoints?",
+                    ))
+                    .button("cancel", |s: &mut Cursive| {
+                        s.pop_layer();
+                    })
+                    .button("continue", on_expose),
+            );
+            return;
+        }
+    };
+
+    let rest_table_id = rest_table.id;
+    let on_select = move |s: &mut Cursive, idx: &usize| {
+        rest_access(s, rest_table_id, REST_OPERATIONS[*idx]);
+    };
+
+    let operations = components::selector::select_component(
+        REST_OPERATIONS
+            .into_iter()
+            .enumerate()
+            .map(|(i, o)| (i, o.to_string()))
+            .collect(),
+        "rest_operations",
+        on_select,
+    );
+
+    let materialize_table = rest_table.clone();
+    let on_materialize = move |s: &mut Cursive| {
+        let model = get_current_model(s);
+
+        match futures::executor::block_on(model.materialize_rest_table(&materialize_table)) {
+            Ok(queries) => {
+                let count = queries.len();
+
+                for q in queries {
+                    add_select_item(s, "query_list", q.name.clone(), q.id as usize);
+
+                    s.call_on_name("server_apis", |list: &mut ListView| {
+                        list.add_child(
+                            format!("/api/{}", q.name),
+                            TextView::new(q.exec_type).align(Align::center_right()),
+                        );
+                    });
+                }
+
+                s.add_layer(Dialog::info(format!("added {} queries", count)));
+            }
+            Err(e) => s.add_layer(Dialog::info(e)),
+        }
+    };
+
+    let on_disable = move |s: &mut Cursive| {
+        s.add_layer(
+            Dialog::new()
+                .content(TextView::new(
+                    "Are you sure you want to remove rest endpoints?",
+                ))
+                .button("cancel", |s: &mut Cursive| {
+                    s.pop_layer();
+                })
+                .button("continue", move |s: &mut Cursive| {
+                    let model = get_current_model(s);
+
+                    let res = futures::executor::block_on(model.delete_rest_table(rest_table_id));
+                    if let Err(e) = res {
+                        s.add_layer(Dialog::info(e));
+                        return;
+                    }
+
+                    refresh_rest_apis(s);
+
+                    s.pop_layer();
+                    s.pop_layer();
+                }),
+        );
+    };
+
+    s.add_layer(
+        Dialog::new()
+            .title(format!("/rest/{}", rest_table.table_name))
+            .content(operations)
+            .padding_lrtb(1, 1, 1, 0)
+            .button("materialize", on_materialize)
+            .button("disable", on_disable)
+            .button("close", |s: &mut Cursive| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn rest_access(s: &mut Cursive, rest_table_id: i64, operation: &'static str) {
+    let model = get_current_model(s);
+
+    let access =
+        match futures::executor::block_on(model.get_rest_access_by_id(rest_table_id, operation)) {
+            Ok(a) => a,
+            Err(e) => {
+                s.add_layer(Dialog::info(e));
+                return;
+            }
+        };
+
+    let check_box = components::checkbox_group::checkbox_group_component(
+        "role_list",
+        access
+            .iter()
+            .map(|role| (role.name.clone(), role.has_access))
+            .collect(),
+    );
+
+    let on_submit = move |s: &mut Cursive| {
+        let checked = get_checked_data(s, access.iter().map(|r| r.name.clone()).collect());
+
+        let access = access
+            .iter()
+            .zip(checked)
+            .map(|(r, has_access)| QueryAccess {
+                id: r.id,
+                name: r.name.clone(),
+                has_access,
+            })
+            .collect();
+
+        let model = get_current_model(s);
+
+        let res =
+            futures::executor::block_on(model.edit_rest_access(rest_table_id, operation, access));
+        match res {
+            Ok(_) => {
+                s.pop_layer();
+            }
+            Err(e) => s.add_layer(Dialog::info(e)),
+        }
+    };
+
+    s.add_layer(
+        Dialog::new()
+            .title(format!("{} access", operation))
+            .content(check_box.scrollable())
+            .button("submit", on_submit)
+            .button("cancel", |s: &mut Cursive| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn refresh_rest_apis(s: &mut Cursive) {
+    let rest_apis = update_rest_apis(s, ListView::new());
+
+    s.call_on_name("rest_apis", move |list: &mut ListView| {
+        *list = rest_apis;
+    });
+}
@@ -16,7 +16,9 @@ use crate::{
             selector::{add_select_item, remove_select_item, select_component, update_select_item},
         },
         model::Sidebar,
-        utils::{get_current_model, get_current_mut_model, get_data_from_refname},
+        utils::{
+            format_timestamp, get_current_model, get_current_mut_model, get_data_from_refname,
+        },
     },
 };
 
@@ -242,6 +244,78 @@ fn edit_webhook(s: &mut Cursive, idx: usize) {
             ),
     );
 
+    list.add_child(
+        "Secret",
+        EditView::new()
+            .secret()
+            .content(webhook.secret)
+            .with_name("edit_webhook_secret"),
+    );
+
+    list.add_child(
+        "Deliveries",
+        Button::new("", move |s: &mut Cursive| {
+            webhook_deliveries(s, idx);
+        }),
+    );
+
+    list.add_child(
+        "Triggers",
+        Button::new("", move |s: &mut Cursive| {
+            webhook_triggers(s, idx);
+        }),
+    );
+
+    list.add_child(
+        "Return Mode",
+        Button::new(webhook.return_mode, |s: &mut Cursive| {
+            let items: Vec<(usize, String)> = vec![
+                (0, "keep".to_string()),
+                (1, "merge".to_string()),
+                (2, "replace".to_string()),
+            ];
+
+            let return_mode = select_component(
+                items.clone(),
+                "return_mode",
+                move |s: &mut Cursive, idx: &usize| {
+                    let mut button_label_ref =
+                        get_data_from_refname::<Button>(s, "edit_webhook_return_mode");
+
+                    let (_, selected_label) = items.get(*idx).unwrap();
+
+                    button_label_ref.set_label(selected_label);
+
+                    s.pop_layer();
+                },
+            );
+
+            s.add_layer(Dialog::new().content(return_mode.scrollable()).button(
+                "cancel",
+                |s: &mut Cursive| {
+                    s.pop_layer();
+                },
+            ));
+
+            let button_label_ref = get_data_from_refname::<Button>(s, "edit_webhook_return_mode");
+            let btn_label = button_label_ref.label().replace(['<', '>'], "").to_string();
+
+            let items: Vec<(usize, String)> = vec![
+                (0, "keep".to_string()),
+                (1, "merge".to_string()),
+                (2, "replace".to_string()),
+            ];
+
+            // an unknown stored mode just leaves the first item selected
+            if let Some((i, _)) = items.iter().find(|(_, f)| *f == btn_label) {
+                let mut return_mode_ref =
+                    get_data_from_refname::<SelectView<usize>>(s, "return_mode");
+                return_mode_ref.set_selection(*i);
+            }
+        })
+        .with_name("edit_webhook_return_mode"),
+    );
+
     let on_submit = move |s: &mut Cursive| {
         let label_ref = get_data_from_refname::<EditView>(s, "edit_webhook_label");
         let label = label_ref.get_content().to_string();
@@ -260,6 +334,12 @@ fn edit_webhook(s: &mut Cursive, idx: usize) {
 
         let is_returned = *boolean_group.selection();
 
+        let return_mode_ref = get_data_from_refname::<Button>(s, "edit_webhook_return_mode");
+        let return_mode = return_mode_ref.label().replace(['<', '>'], "").to_string();
+
+        let secret_ref = get_data_from_refname::<EditView>(s, "edit_webhook_secret");
+        let secret = secret_ref.get_content().to_string();
+
         let model = get_current_mut_model(s);
 
         let res = futures::executor::block_on(model.edit_webhook(Webhook {
@@ -270,6 +350,8 @@ fn edit_webhook(s: &mut Cursive, idx: usize) {
             url,
             args,
             is_returned,
+            return_mode,
+            secret,
         }));
 
         if let Err(e) = res {
@@ -327,3 +409,259 @@ fn edit_webhook(s: &mut Cursive, idx: usize) {
             .button("cancel", on_cancel),
     );
 }
+
+fn webhook_triggers(s: &mut Cursive, webhook_id: usize) {
+    let model = get_current_model(s);
+
+    let optional_triggers =
+        futures::executor::block_on(model.get_table_triggers_by_webhook_id(webhook_id as i64));
+    let triggers = match optional_triggers {
+        Ok(t) => t,
+        Err(e) => {
+            s.add_layer(Dialog::info(e));
+            return;
+        }
+    };
+
+    let on_select = |s: &mut Cursive, idx: &usize| {
+        let idx = *idx;
+
+        s.add_layer(
+            Dialog::new()
+                .content(TextView::new("Are you sure you want to remove trigger?"))
+                .button("cancel", |s: &mut Cursive| {
+                    s.pop_layer();
+                })
+                .button("continue", move |s: &mut Cursive| {
+                    let model = get_current_model(s);
+
+                    let res = futures::executor::block_on(model.delete_table_trigger(idx as i64));
+                    if let Err(e) = res {
+                        s.add_layer(Dialog::info(e));
+                        return;
+                    }
+
+                    remove_select_item(s, "webhook_trigger_list", idx);
+
+                    s.pop_layer();
+                }),
+        );
+    };
+
+    let trigger_list = select_component(
+        triggers
+            .into_iter()
+            .map(|t| (t.id as usize, format!("{}:{}", t.table_name, t.event)))
+            .collect(),
+        "webhook_trigger_list",
+        on_select,
+    );
+
+    s.add_layer(
+        Dialog::new()
+            .title("Triggers")
+            .content(trigger_list)
+            .padding_lrtb(1, 1, 1, 0)
+            .button("add trigger", move |s: &mut Cursive| {
+                add_webhook_trigger(s, webhook_id);
+            })
+            .button("cancel", |s: &mut Cursive| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn add_webhook_trigger(s: &mut Cursive, webhook_id: usize) {
+    let model = get_current_model(s);
+
+    let tables = match futures::executor::block_on(model.get_all_tables()) {
+        Ok(t) => t,
+        Err(e) => {
+            s.add_layer(Dialog::info(e));
+            return;
+        }
+    };
+
+    let table_items: Vec<(usize, String)> = tables
+        .into_iter()
+        .enumerate()
+        .map(|(i, t)| (i, t.name))
+        .collect();
+    let table_list_items = table_items.clone();
+
+    let on_table_select = move |s: &mut Cursive, idx: &usize| {
+        let (_, table_name) = table_items.get(*idx).unwrap().clone();
+
+        let events: Vec<(usize, String)> = vec![
+            (0, "insert".to_string()),
+            (1, "update".to_string()),
+            (2, "delete".to_string()),
+        ];
+        let event_list_items = events.clone();
+
+        let on_event_select = move |s: &mut Cursive, idx: &usize| {
+            let (_, event) = events.get(*idx).unwrap().clone();
+            let model = get_current_model(s);
+
+            let res = futures::executor::block_on(model.add_table_trigger(
+                webhook_id as i64,
+                table_name.clone(),
+                event.clone(),
+            ));
+
+            match res {
+                Ok(id) => {
+                    s.pop_layer();
+                    s.pop_layer();
+                    add_select_item(
+                        s,
+                        "webhook_trigger_list",
+                        format!("{}:{}", table_name, event),
+                        id as usize,
+                    );
+                }
+                Err(e) => s.add_layer(Dialog::info(e)),
+            }
+        };
+
+        let event_list =
+            select_component(event_list_items, "webhook_trigger_event", on_event_select);
+
+        s.add_layer(Dialog::new().title("Event").content(event_list).button(
+            "cancel",
+            |s: &mut Cursive| {
+                s.pop_layer();
+            },
+        ));
+    };
+
+    let table_list = select_component(table_list_items, "webhook_trigger_table", on_table_select);
+
+    s.add_layer(
+        Dialog::new()
+            .title("Table")
+            .content(table_list.scrollable())
+            .button("cancel", |s: &mut Cursive| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn webhook_deliveries(s: &mut Cursive, webhook_id: usize) {
+    let model = get_current_model(s);
+
+    let optional_deliveries =
+        futures::executor::block_on(model.get_webhook_deliveries_by_webhook_id(webhook_id as i64));
+    let deliveries = match optional_deliveries {
+        Ok(d) => d,
+        Err(e) => {
+            s.add_layer(Dialog::info(e));
+            return;
+        }
+    };
+
+    let on_select = |s: &mut Cursive, idx: &usize| {
+        webhook_delivery(s, *idx);
+    };
+
+    let delivery_list = select_component(
+        deliveries
+            .into_iter()
+            .map(|d| {
+                let status = match d.status_code {
+                    Some(code) => code.to_string(),
+                    None => "error".to_string(),
+                };
+
+                (
+                    d.id as usize,
+                    format!(
+                        "{} {} {}ms",
+                        format_timestamp(d.created_at),
+                        status,
+                        d.latency_ms
+                    ),
+                )
+            })
+            .collect(),
+        "webhook_delivery_list",
+        on_select,
+    );
+
+    s.add_layer(
+        Dialog::new()
+            .title("Deliveries")
+            .content(delivery_list)
+            .padding_lrtb(1, 1, 1, 0)
+            .button("cancel", |s: &mut Cursive| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn webhook_delivery(s: &mut Cursive, idx: usize) {
+    let model = get_current_model(s);
+
+    let optional_delivery =
+        futures::executor::block_on(model.get_webhook_delivery_by_id(idx as i64));
+    let delivery = match optional_delivery {
+        Ok(d) => d,
+        Err(e) => {
+            s.add_layer(Dialog::info(e));
+            return;
+        }
+    };
+
+    let mut list = ListView::new();
+    list.add_child("Time", TextView::new(format_timestamp(delivery.created_at)));
+    list.add_child(
+        "Request",
+        TextView::new(format!("{} {}", delivery.method, delivery.url)),
+    );
+    list.add_child("Payload", TextView::new(delivery.payload.clone()));
+    list.add_child(
+        "Status",
+        TextView::new(
+            delivery
+                .status_code
+                .map(|c| c.to_string())
+                .unwrap_or_default(),
+        ),
+    );
+    list.add_child(
+        "Latency",
+        TextView::new(format!("{}ms", delivery.latency_ms)),
+    );
+    list.add_child("Response", TextView::new(delivery.response.clone()));
+    list.add_child("Error", TextView::new(delivery.error.clone()));
+
+    let on_replay = move |s: &mut Cursive| {
+        let model = get_current_model(s);
+
+        let res = futures::executor::block_on(model.add_webhook_outbox(
+            delivery.webhook_id,
+            delivery.method.clone(),
+            delivery.url.clone(),
+            delivery.payload.clone(),
+        ));
+
+        match res {
+            Ok(_) => {
+                s.pop_layer();
+                s.add_layer(Dialog::info("delivery queued"));
+            }
+            Err(e) => s.add_layer(Dialog::info(e)),
+        }
+    };
+
+    s.add_layer(
+        Dialog::new()
+            .title("Delivery")
+            .content(list.scrollable())
+            .padding_lrtb(1, 1, 1, 0)
+            .button("replay", on_replay)
+            .button("cancel", |s: &mut Cursive| {
+                s.pop_layer();
+            }),
+    );
+}
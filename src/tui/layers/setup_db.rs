@@ -57,6 +57,7 @@ fn setup_db_connection(s: &mut Cursive, dbtype: DbType) {
 
         let setup = Setup {
             dbpath: dbpath.clone(),
+            dbtype: dbtype.to_string(),
         };
 
         let model = get_current_mut_model(s);
@@ -99,6 +100,7 @@ fn setup_db_connection(s: &mut Cursive, dbtype: DbType) {
             log::error!("{:#?}", e);
             Setup {
                 dbpath: "".to_string(),
+                dbtype: "".to_string(),
             }
         }
     };
@@ -9,8 +9,11 @@ pub enum Sidebar {
     User,
     Query,
     Webhook,
+    Scheduler,
     Migration,
+    Tables,
     Api,
+    Stats,
     Quit,
 }
 
@@ -22,8 +25,11 @@ impl fmt::Display for Sidebar {
             Sidebar::User => write!(f, "USER"),
             Sidebar::Query => write!(f, "QUERY"),
             Sidebar::Webhook => write!(f, "WEBHOOK"),
+            Sidebar::Scheduler => write!(f, "SCHEDULER"),
             Sidebar::Migration => write!(f, "MIGRATION"),
+            Sidebar::Tables => write!(f, "TABLES"),
             Sidebar::Api => write!(f, "API"),
+            Sidebar::Stats => write!(f, "STATS"),
             Sidebar::Quit => write!(f, "QUIT"),
         }
     }
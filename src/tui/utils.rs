@@ -1,3 +1,4 @@
+use chrono::{DateTime, Local};
 use cursive::{views::ViewRef, Cursive, View};
 
 use crate::queries::Model;
@@ -16,3 +17,13 @@ where
 {
     s.find_name::<T>(refname).unwrap()
 }
+
+pub fn format_timestamp(timestamp: i64) -> String {
+    match DateTime::from_timestamp(timestamp, 0) {
+        Some(t) => t
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+        None => "".to_string(),
+    }
+}